@@ -0,0 +1,638 @@
+/*
+    arena.rs
+    ----------------------------------------
+    Description:
+    * Provides the arena in which the system's bodies live, and the handles by which they are referred to
+    * A handle is a slot index plus the generation of the slot when the body was inserted; removing a body bumps its
+      slot's generation, so stale handles to it no longer resolve, even once the slot is reused
+    * Look-up is O(1); bodies are owned by the arena, so there is no RefCell to borrow while iterating
+    * The motion of the bodies (position, rotation, velocity, inverse mass & inertia, ...) is kept in one array per
+      field, apart from their shape & material, so the per-body passes of a step are tight loops over plain arrays.
+      Bodies are reached through BodyRef & BodyMut views
+    * Each body's transform & world-space geometry are cached, so views read them rather than redoing the trigonometry
+      for every point they convert; moving a body marks it stale, & `refresh_geometry` redoes the stale ones. Views
+      of stale bodies work them out on demand
+    * The arena can be split into islands; groups of bodies linked by contacts, which can be solved on separate
+      threads. Frozen bodies are shared by every island touching them, so each island gets its own copy of their
+      motion, & reads their data in place
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::ops::Index;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::integrator::{Integrator, TForceField};
+use crate::app::objects::{Body, BodyData, BodyMut, BodyRef, BodyView, Derived, Geometry, Mode, Motion, MotionMut};
+use crate::common::{Crd, Vector2, Real, Transform};
+use crate::v2;
+
+/* ------------------- STRUCTURES ------------------- */
+/// Copyable reference to a body in a BodyArena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BodyHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// Generational arena of bodies.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(into = "StoredArena", from = "StoredArena")]
+pub struct BodyArena {
+    slots: Vec<Slot>,
+    motion: Columns, // Indexed by slot
+    free: Vec<u32>,  // Indices of empty slots
+    len: usize,
+}
+
+/// A BodyArena as serialized; the generation & body of every slot, so that handles into it stay valid.
+#[derive(Serialize, Deserialize)]
+struct StoredArena {
+    slots: Vec<(u32, Option<Body>)>,
+    free: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Slot {
+    generation: u32,
+    data: Option<BodyData>,
+}
+
+/// Motion of every slot, one array per field; empty slots are disabled.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Columns {
+    position: Vec<Vector2<Crd>>,
+    rotation: Vec<Real>,
+    velocity: Vec<Vector2<Real>>,
+    angular_velocity: Vec<Real>,
+    torque: Vec<Real>,
+    force: Vec<Vector2<Real>>,
+    prev_position: Vec<Vector2<Crd>>,
+    prev_rotation: Vec<Real>,
+
+    // Derived from the body's data
+    inv_mass: Vec<Real>,
+    inv_inertia: Vec<Real>,
+    mode: Vec<Mode>,
+    transform: Vec<Transform>,
+    geometry: Vec<Geometry>,
+    stale: Vec<bool>,
+}
+
+/// A run of slots, borrowed from the arena; split into the views of single bodies.
+#[derive(Default)]
+struct Slice<'a> {
+    data: &'a mut [Slot],
+    position: &'a mut [Vector2<Crd>],
+    rotation: &'a mut [Real],
+    velocity: &'a mut [Vector2<Real>],
+    angular_velocity: &'a mut [Real],
+    prev_position: &'a mut [Vector2<Crd>],
+    prev_rotation: &'a mut [Real],
+    inv_mass: &'a mut [Real],
+    inv_inertia: &'a mut [Real],
+    mode: &'a mut [Mode],
+    transform: &'a mut [Transform],
+    stale: &'a mut [bool],
+}
+
+/// Bodies linked by contacts, borrowed from a BodyArena; changes to its frozen bodies are dropped.
+pub struct Island<'a> {
+    members: Vec<(BodyHandle, Member<'a>)>, // In handle order
+}
+
+enum Member<'a> {
+    Moving(MotionMut<'a>, &'a mut BodyData), // The body's slot; each pair views it through a BodyMut of its own
+    Fixed(Motion, &'a BodyData),             // A frozen body; its motion is copied, its data shared with the other islands
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl BodyHandle {
+    /* --------------------- GETTERS -------------------- */
+    /// Slot of the body; unique among the bodies currently in the arena
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+impl BodyArena {
+    pub fn new() -> Self {
+        BodyArena::default()
+    }
+
+    pub fn insert(&mut self, body: Body) -> BodyHandle {
+        let index = match self.free.pop() {
+            Some(index) => index as usize,
+            None => self.push_slot(0),
+        };
+        self.fill(index, body);
+
+        BodyHandle { index: index as u32, generation: self.slots[index].generation }
+    }
+
+    /// Adds an empty slot of the given generation; returns its index
+    fn push_slot(&mut self, generation: u32) -> usize {
+        self.slots.push(Slot { generation, data: None });
+        self.motion.push();
+        self.slots.len() - 1
+    }
+
+    /// Puts the body in the given empty slot
+    fn fill(&mut self, index: usize, body: Body) {
+        self.len += 1;
+        let Body { position, rotation, velocity, angular_velocity, torque, force_buffer, prev_position, prev_rotation, data } = body;
+        let (inv_mass, inv_inertia, mode) = (data.inv_mass(), data.inv_inertia(), data.mode());
+        let transform = Transform::new(data.origin + position, rotation);
+        self.slots[index].data = Some(data);
+
+        let c = &mut self.motion;
+        c.position[index] = position;
+        c.rotation[index] = rotation;
+        c.velocity[index] = velocity;
+        c.angular_velocity[index] = angular_velocity;
+        c.torque[index] = torque;
+        c.force[index] = force_buffer;
+        c.prev_position[index] = prev_position;
+        c.prev_rotation[index] = prev_rotation;
+        c.inv_mass[index] = inv_mass;
+        c.inv_inertia[index] = inv_inertia;
+        c.mode[index] = mode;
+        c.transform[index] = transform;
+        c.stale[index] = true;
+    }
+
+    /// Removes the body; the handle, & any copies of it, no longer resolve
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<Body> {
+        let body = self.cloned(handle)?;
+
+        let slot = &mut self.slots[handle.index()];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.data = None;
+        self.motion.mode[handle.index()] = Mode::Disabled;
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(body)
+    }
+
+    pub fn contains(&self, handle: BodyHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Both bodies at once; None if either handle is stale, or they are the same body
+    pub fn pair_mut(&mut self, a: BodyHandle, b: BodyHandle) -> Option<(BodyMut<'_>, BodyMut<'_>)> {
+        if a.index == b.index || !self.contains(a) || !self.contains(b) { return None; }
+
+        let (low, high) = (a.index().min(b.index()), a.index().max(b.index()));
+        let (head, tail) = self.slice().split_at(high);
+        let (body_low, body_high) = (head.split_at(low).1.first()?, tail.first()?);
+
+        Some(if a.index() < b.index() { (body_low, body_high) } else { (body_high, body_low) })
+    }
+
+    /// Splits the bodies linked by the given pairs into islands; pairs of moving bodies are linked, frozen bodies link
+    /// nothing. Also returns the island of each pair, or None if the pair is stale or holds the same body twice
+    pub fn islands(&mut self, pairs: &[[BodyHandle; 2]]) -> (Vec<Island<'_>>, Vec<Option<usize>>) {
+        // Union-find over the slots of moving bodies
+        let mut parent: Vec<usize> = (0..self.slots.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let valid: Vec<bool> = pairs.iter().map(|&[a, b]| a.index != b.index && self.contains(a) && self.contains(b)).collect();
+        for (&[a, b], _) in pairs.iter().zip(&valid).filter(|(_, &valid)| valid) {
+            if self[a].frozen || self[b].frozen { continue; }
+            let (root_a, root_b) = (root(&mut parent, a.index()), root(&mut parent, b.index()));
+            parent[root_a.max(root_b)] = root_a.min(root_b);
+        }
+
+        // Number the islands by their first pair, & find the frozen bodies each one touches
+        let mut island_of_root: Vec<Option<usize>> = vec![None; self.slots.len()];
+        let mut count = 0;
+        let mut fixed: Vec<(BodyHandle, usize)> = Vec::new(); // Each frozen body, with an island it touches
+        let mut of_pair: Vec<Option<usize>> = Vec::new();
+        for (&[a, b], &valid) in pairs.iter().zip(&valid) {
+            if !valid || (self[a].frozen && self[b].frozen) {
+                of_pair.push(None);
+                continue;
+            }
+
+            let moving = if self[a].frozen { b } else { a };
+            let r = root(&mut parent, moving.index());
+            let island = *island_of_root[r].get_or_insert_with(|| { count += 1; count - 1 });
+            for handle in [a, b] {
+                if self[handle].frozen {
+                    fixed.push((handle, island));
+                }
+            }
+            of_pair.push(Some(island));
+        }
+        fixed.sort_unstable();
+        fixed.dedup();
+
+        // Members are added in slot order, so each island is in handle order
+        let mut islands: Vec<Island> = (0..count).map(|_| Island { members: Vec::new() }).collect();
+        let mut fixed = fixed.into_iter().peekable();
+        for (handle, slot) in self.slots_mut() {
+            if slot.data[0].data.as_ref().is_some_and(|data| data.frozen) {
+                let Some((motion, data)) = slot.shared() else { continue; };
+                while let Some((_, island)) = fixed.next_if(|(h, _)| *h == handle) {
+                    islands[island].members.push((handle, Member::Fixed(motion, data)));
+                }
+            } else {
+                let Some(island) = island_of_root[root(&mut parent, handle.index())] else { continue; };
+                let Some((target, data)) = slot.first_target() else { continue; };
+                islands[island].members.push((handle, Member::Moving(target, data)));
+            }
+        }
+
+        (islands, of_pair)
+    }
+
+    /// Every body, with its handle, in slot order
+    pub fn iter(&self) -> impl Iterator<Item=(BodyHandle, BodyRef<'_>)> {
+        (0..self.slots.len()).filter_map(|i| {
+            let generation = self.slots[i].generation;
+            self.view(i).map(|body| (BodyHandle { index: i as u32, generation }, body))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=(BodyHandle, BodyMut<'_>)> {
+        self.slots_mut().filter_map(|(handle, slot)| Some((handle, slot.first()?)))
+    }
+
+    /// Advances every moving body by dt, & starts the substep of every enabled body.
+    /// Linear motion is advanced by the given integrator, under gravity, the body's force & the force fields;
+    /// rotation always uses semi-implicit Euler.
+    pub fn integrate(&mut self, dt: Real, integrator: Integrator, gravity: Vector2<Real>, fields: &[TForceField]) {
+        let c = &mut self.motion;
+        // Every column cut to the same length, so the loop isn't bounds checked
+        let n = c.mode.len();
+        let (position, rotation, velocity, angular_velocity) = (&mut c.position[..n], &mut c.rotation[..n], &mut c.velocity[..n], &mut c.angular_velocity[..n]);
+        let (prev_position, prev_rotation, force, torque) = (&mut c.prev_position[..n], &mut c.prev_rotation[..n], &mut c.force[..n], &mut c.torque[..n]);
+        let (mode, inv_inertia, stale, slots) = (&c.mode[..n], &c.inv_inertia[..n], &mut c.stale[..n], &self.slots[..n]);
+
+        for i in 0..n {
+            if mode[i] == Mode::Disabled { continue; }
+
+            prev_position[i] = position[i];
+            prev_rotation[i] = rotation[i];
+
+            if mode[i] == Mode::Frozen { continue; }
+
+            let f = gravity + force[i];
+            (position[i], velocity[i]) = if fields.is_empty() {
+                // The acceleration is the same at every stage of the integrator
+                integrator.step(position[i], velocity[i], dt, |_, _| f)
+            } else {
+                // Force fields are evaluated about the center of mass, not the drawing position
+                let origin = slots[i].data.as_ref().map_or(v2!(0.0), |data| data.origin);
+                integrator.step(position[i], velocity[i], dt, |x, v| fields.iter().fold(f, |a, field| a + field(x + origin, v)))
+            };
+
+            angular_velocity[i] += torque[i] * inv_inertia[i] * dt;
+            rotation[i] += angular_velocity[i] * dt;
+            stale[i] = true;
+
+            force[i] = v2!(0.0);
+            torque[i] = 0.0;
+        }
+    }
+
+    /// Derives the velocity of every moving body from its positional change over the substep of length dt
+    pub fn derive_velocities(&mut self, dt: Real) {
+        let c = &mut self.motion;
+        let n = c.mode.len();
+        let (position, rotation, velocity, angular_velocity) = (&c.position[..n], &c.rotation[..n], &mut c.velocity[..n], &mut c.angular_velocity[..n]);
+        let (prev_position, prev_rotation, mode) = (&c.prev_position[..n], &c.prev_rotation[..n], &c.mode[..n]);
+
+        for i in 0..n {
+            if mode[i] != Mode::Moving { continue; }
+
+            velocity[i] = (position[i] - prev_position[i]) / dt;
+            angular_velocity[i] = (rotation[i] - prev_rotation[i]) / dt;
+        }
+    }
+
+    /// Redoes the cached transform & geometry of every body that has moved since they were last done
+    pub fn refresh_geometry(&mut self) {
+        for i in 0..self.slots.len() {
+            if !self.motion.stale[i] { continue; }
+
+            // Taken out while the body is viewed; the view of a stale body doesn't borrow it
+            let mut geometry = std::mem::take(&mut self.motion.geometry[i]);
+            let mut transform = self.motion.transform[i];
+            if let Some(body) = self.view(i) {
+                body.write_geometry(&mut geometry);
+                transform = body.transform;
+            }
+            self.motion.geometry[i] = geometry;
+            self.motion.transform[i] = transform;
+            self.motion.stale[i] = false;
+        }
+    }
+
+    /// Read-only view of the body in the given slot
+    fn view(&self, i: usize) -> Option<BodyRef<'_>> {
+        let (c, data) = (&self.motion, self.slots[i].data.as_ref()?);
+        Some(BodyRef {
+            position: c.position[i],
+            rotation: c.rotation[i],
+            velocity: c.velocity[i],
+            angular_velocity: c.angular_velocity[i],
+            prev_position: c.prev_position[i],
+            prev_rotation: c.prev_rotation[i],
+            transform: if c.stale[i] { Transform::new(data.origin + c.position[i], c.rotation[i]) } else { c.transform[i] },
+            geometry: if c.stale[i] { None } else { Some(&c.geometry[i]) },
+            data,
+        })
+    }
+
+    /// Every slot on its own, empty or not, with the handle of its current body
+    fn slots_mut(&mut self) -> impl Iterator<Item=(BodyHandle, Slice<'_>)> {
+        let mut rest = self.slice();
+        let mut index = 0;
+
+        std::iter::from_fn(move || {
+            if rest.data.is_empty() { return None; }
+
+            let (head, tail) = std::mem::take(&mut rest).split_at(1);
+            let handle = BodyHandle { index, generation: head.data[0].generation };
+            rest = tail;
+            index += 1;
+            Some((handle, head))
+        })
+    }
+
+    /// Every slot, for splitting into views
+    fn slice(&mut self) -> Slice<'_> {
+        let c = &mut self.motion;
+        Slice {
+            data: &mut self.slots,
+            position: &mut c.position,
+            rotation: &mut c.rotation,
+            velocity: &mut c.velocity,
+            angular_velocity: &mut c.angular_velocity,
+            prev_position: &mut c.prev_position,
+            prev_rotation: &mut c.prev_rotation,
+            inv_mass: &mut c.inv_mass,
+            inv_inertia: &mut c.inv_inertia,
+            mode: &mut c.mode,
+            transform: &mut c.transform,
+            stale: &mut c.stale,
+        }
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn get(&self, handle: BodyHandle) -> Option<BodyRef<'_>> {
+        let slot = self.slots.get(handle.index())?;
+        if slot.generation != handle.generation { return None; }
+        self.view(handle.index())
+    }
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<BodyMut<'_>> {
+        let slot = self.slots.get(handle.index())?;
+        if slot.generation != handle.generation { return None; }
+        self.slice().split_at(handle.index()).1.first()
+    }
+    pub fn body(&self, handle: BodyHandle) -> BodyRef<'_> {
+        self.get(handle).expect("stale body handle")
+    }
+    pub fn body_mut(&mut self, handle: BodyHandle) -> BodyMut<'_> {
+        self.get_mut(handle).expect("stale body handle")
+    }
+    /// Owned copy of the body
+    pub fn cloned(&self, handle: BodyHandle) -> Option<Body> {
+        let body = self.get(handle)?;
+        let (c, i) = (&self.motion, handle.index());
+
+        Some(Body {
+            position: body.position,
+            rotation: body.rotation,
+            velocity: body.velocity,
+            angular_velocity: body.angular_velocity,
+            torque: c.torque[i],
+            force_buffer: c.force[i],
+            prev_position: body.prev_position,
+            prev_rotation: body.prev_rotation,
+            data: body.data.clone(),
+        })
+    }
+    /// Handles of every body, in slot order
+    pub fn handles(&self) -> Vec<BodyHandle> {
+        self.iter().map(|(handle, _)| handle).collect()
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl From<BodyArena> for StoredArena {
+    fn from(arena: BodyArena) -> Self {
+        let slots = (0..arena.slots.len() as u32)
+            .map(|index| {
+                let handle = BodyHandle { index, generation: arena.slots[index as usize].generation };
+                (handle.generation, arena.cloned(handle))
+            })
+            .collect();
+        StoredArena { slots, free: arena.free }
+    }
+}
+
+impl From<StoredArena> for BodyArena {
+    fn from(stored: StoredArena) -> Self {
+        let mut arena = BodyArena::new();
+        for (generation, body) in stored.slots {
+            let index = arena.push_slot(generation);
+            if let Some(body) = body {
+                arena.fill(index, body);
+            }
+        }
+        arena.free = stored.free;
+        arena
+    }
+}
+
+impl Columns {
+    /// Adds an empty slot
+    fn push(&mut self) {
+        self.position.push(v2!(0.0));
+        self.rotation.push(0.0);
+        self.velocity.push(v2!(0.0));
+        self.angular_velocity.push(0.0);
+        self.torque.push(0.0);
+        self.force.push(v2!(0.0));
+        self.prev_position.push(v2!(0.0));
+        self.prev_rotation.push(0.0);
+        self.inv_mass.push(0.0);
+        self.inv_inertia.push(0.0);
+        self.mode.push(Mode::Disabled);
+        self.transform.push(Transform::IDENTITY);
+        self.geometry.push(Geometry::default());
+        self.stale.push(false);
+    }
+}
+
+impl<'a> Slice<'a> {
+    fn split_at(self, mid: usize) -> (Slice<'a>, Slice<'a>) {
+        let (data_0, data_1) = self.data.split_at_mut(mid);
+        let (position_0, position_1) = self.position.split_at_mut(mid);
+        let (rotation_0, rotation_1) = self.rotation.split_at_mut(mid);
+        let (velocity_0, velocity_1) = self.velocity.split_at_mut(mid);
+        let (angular_velocity_0, angular_velocity_1) = self.angular_velocity.split_at_mut(mid);
+        let (prev_position_0, prev_position_1) = self.prev_position.split_at_mut(mid);
+        let (prev_rotation_0, prev_rotation_1) = self.prev_rotation.split_at_mut(mid);
+        let (inv_mass_0, inv_mass_1) = self.inv_mass.split_at_mut(mid);
+        let (inv_inertia_0, inv_inertia_1) = self.inv_inertia.split_at_mut(mid);
+        let (mode_0, mode_1) = self.mode.split_at_mut(mid);
+        let (transform_0, transform_1) = self.transform.split_at_mut(mid);
+        let (stale_0, stale_1) = self.stale.split_at_mut(mid);
+
+        (
+            Slice {
+                data: data_0, position: position_0, rotation: rotation_0, velocity: velocity_0,
+                angular_velocity: angular_velocity_0, prev_position: prev_position_0, prev_rotation: prev_rotation_0,
+                inv_mass: inv_mass_0, inv_inertia: inv_inertia_0, mode: mode_0, transform: transform_0, stale: stale_0,
+            },
+            Slice {
+                data: data_1, position: position_1, rotation: rotation_1, velocity: velocity_1,
+                angular_velocity: angular_velocity_1, prev_position: prev_position_1, prev_rotation: prev_rotation_1,
+                inv_mass: inv_mass_1, inv_inertia: inv_inertia_1, mode: mode_1, transform: transform_1, stale: stale_1,
+            },
+        )
+    }
+
+    /// View of the body in the first slot; None if the slot is empty
+    fn first(self) -> Option<BodyMut<'a>> {
+        let (target, data) = self.first_target()?;
+        Some(BodyMut::new(target, data))
+    }
+
+    /// Where the motion of the body in the first slot is kept, & its data; None if the slot is empty
+    fn first_target(self) -> Option<(MotionMut<'a>, &'a mut BodyData)> {
+        let data = self.data.first_mut()?.data.as_mut()?;
+        let target = MotionMut {
+            position: self.position.first_mut()?,
+            rotation: self.rotation.first_mut()?,
+            velocity: self.velocity.first_mut()?,
+            angular_velocity: self.angular_velocity.first_mut()?,
+            prev_position: self.prev_position.first_mut()?,
+            prev_rotation: self.prev_rotation.first_mut()?,
+            derived: Some(Derived {
+                inv_mass: self.inv_mass.first_mut()?,
+                inv_inertia: self.inv_inertia.first_mut()?,
+                mode: self.mode.first_mut()?,
+                transform: self.transform.first_mut()?,
+                stale: self.stale.first_mut()?,
+            }),
+        };
+
+        Some((target, data))
+    }
+
+    /// Copy of the motion of the body in the first slot, & its data; None if the slot is empty
+    fn shared(self) -> Option<(Motion, &'a BodyData)> {
+        let slots: &'a [Slot] = self.data;
+        Some((
+            Motion {
+                position: *self.position.first()?,
+                rotation: *self.rotation.first()?,
+                velocity: *self.velocity.first()?,
+                angular_velocity: *self.angular_velocity.first()?,
+                prev_position: *self.prev_position.first()?,
+                prev_rotation: *self.prev_rotation.first()?,
+            },
+            slots.first()?.data.as_ref()?,
+        ))
+    }
+}
+
+impl Island<'_> {
+    /// Both bodies at once; None if either isn't in the island, or they are the same body
+    pub fn pair_mut(&mut self, a: BodyHandle, b: BodyHandle) -> Option<(BodyMut<'_>, BodyMut<'_>)> {
+        let i = self.members.binary_search_by_key(&a, |(handle, _)| *handle).ok()?;
+        let j = self.members.binary_search_by_key(&b, |(handle, _)| *handle).ok()?;
+        if i == j { return None; }
+
+        let (low, high) = (i.min(j), i.max(j));
+        let (head, tail) = self.members.split_at_mut(high);
+        let (body_low, body_high) = (head[low].1.body_mut(), tail[0].1.body_mut());
+
+        Some(if i < j { (body_low, body_high) } else { (body_high, body_low) })
+    }
+}
+
+impl Member<'_> {
+    fn body_mut(&mut self) -> BodyMut<'_> {
+        match self {
+            Member::Moving(target, data) => BodyMut::new(target.reborrow(), data),
+            Member::Fixed(motion, data) => {
+                let target = MotionMut {
+                    position: &mut motion.position,
+                    rotation: &mut motion.rotation,
+                    velocity: &mut motion.velocity,
+                    angular_velocity: &mut motion.angular_velocity,
+                    prev_position: &mut motion.prev_position,
+                    prev_rotation: &mut motion.prev_rotation,
+                    derived: None,
+                };
+                BodyMut::shared(target, data)
+            }
+        }
+    }
+}
+
+impl Index<BodyHandle> for BodyArena {
+    type Output = BodyData;
+    fn index(&self, handle: BodyHandle) -> &BodyData {
+        self.get(handle).expect("stale body handle").data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::objects::BodyView;
+    use crate::common::{BodyForm, Materials};
+    use crate::{rect, v2};
+
+    #[test]
+    fn removed_handles_go_stale() {
+        let mut arena = BodyArena::new();
+        let removed = arena.insert(rect!(v2!(0.0, 0.0), 1.0, 1.0));
+        assert!(arena.remove(removed).is_some());
+        assert!(!arena.contains(removed) && arena.remove(removed).is_none());
+
+        // The slot is reused under a new generation; the old handle still resolves to nothing
+        let reused = arena.insert(rect!(v2!(5.0, 0.0), 1.0, 1.0));
+        assert_eq!(reused.index(), removed.index());
+        assert_ne!(reused, removed);
+        assert!(arena.get(removed).is_none() && arena.get_mut(removed).is_none() && !arena.contains(removed));
+        assert!(arena.pair_mut(removed, reused).is_none());
+        assert_eq!(arena.handles(), vec![reused]);
+        assert_eq!(arena.body(reused).center(), v2!(5.5, 0.5));
+    }
+
+    #[test]
+    fn identical_bodies_stay_distinct() {
+        // Two equal boxes at the same spot are two bodies, each with a handle of its own
+        let mut arena = BodyArena::new();
+        let (a, b) = (arena.insert(rect!(v2!(2.0, 2.0), 1.0, 1.0)), arena.insert(rect!(v2!(2.0, 2.0), 1.0, 1.0)));
+        assert_ne!(a, b);
+        assert_eq!(arena.len(), 2);
+
+        let (mut body_a, body_b) = arena.pair_mut(a, b).unwrap();
+        body_a.velocity = v2!(1.0, 0.0);
+        drop((body_a, body_b));
+        assert_eq!(arena.body(a).velocity, v2!(1.0, 0.0));
+        assert_eq!(arena.body(b).velocity, v2!(0.0, 0.0));
+
+        arena.remove(a);
+        assert!(arena.contains(b) && arena.len() == 1);
+    }
+}
@@ -0,0 +1,134 @@
+/*
+    camera.rs
+    ----------------------------------------
+    Description:
+    * Provides the Camera; the view of the world drawn to the window
+    * A camera looks at a point in the world, at a zoom & rotation; world points are taken to pixels through its
+      transform, & pixels back to world points through the inverse
+    * The camera may follow a body, closing on it smoothly rather than snapping to it
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::objects::BodyView;
+use crate::common::{Crd, Disp, Transform, Vector2, AABB, Real};
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+const MIN_ZOOM: Real = 0.05;
+const MAX_ZOOM: Real = 50.0;
+const FOLLOW_RATE: Real = 5.0; // Default rate the camera closes on its target at; the gap shrinks by e every 1/rate s
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vector2<Crd>, // World point at the centre of the view
+    pub zoom: Real,
+    pub rotation: Real,         // Radians; the world is drawn turned back by this
+    pixels_per_metre: Real,     // At a zoom of 1
+    viewport: Vector2<Real>,    // Window size, in pixels
+
+    // Following
+    target: Option<BodyHandle>,
+    follow_rate: Real,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Camera {
+    /// Constructor for the Camera struct; the world's origin starts at the top-left of the viewport.
+    pub fn new(viewport: Vector2<u32>, pixels_per_metre: Real) -> Self {
+        let viewport = v2!(viewport.x as Real, viewport.y as Real);
+
+        Camera {
+            position: viewport / (2.0 * pixels_per_metre),
+            zoom: 1.0,
+            rotation: 0.0,
+            pixels_per_metre,
+            viewport,
+
+            // Following
+            target: None,
+            follow_rate: FOLLOW_RATE,
+        }
+    }
+
+    /// Pixel the point in the world is drawn at; unrounded
+    pub fn world_to_screen(&self, p: Vector2<Crd>) -> Vector2<Real> {
+        self.transform().local_point(p) * self.scale() + self.viewport / 2.0
+    }
+    /// Point in the world drawn at the pixel
+    pub fn screen_to_world(&self, p: Vector2<Real>) -> Vector2<Crd> {
+        self.transform().world_point((p - self.viewport / 2.0) / self.scale())
+    }
+    pub fn to_screen(&self, p: Vector2<Crd>) -> Vector2<Disp> {
+        self.world_to_screen(p).disp()
+    }
+    pub fn to_world(&self, p: Vector2<Disp>) -> Vector2<Crd> {
+        self.screen_to_world(v2!(p.x as Real, p.y as Real))
+    }
+
+    /// Zooms by the factor, keeping the world point under the given pixel in place
+    pub fn zoom_at(&mut self, pixel: Vector2<Disp>, factor: Real) {
+        let anchor = self.to_world(pixel);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.position = self.position + anchor - self.to_world(pixel);
+    }
+    /// Moves the view so that the world follows a drag of the given pixels; stops following any body
+    pub fn pan(&mut self, pixels: Vector2<Disp>) {
+        let d = self.transform().world_vector(v2!(pixels.x as Real, pixels.y as Real) / self.scale());
+        self.position = self.position - d;
+        self.target = None;
+    }
+
+    /// Closes on the followed body, if any; called once per frame
+    pub fn update(&mut self, dt: Real, bodies: &BodyArena) {
+        let Some(target) = self.target else { return; };
+        let Some(body) = bodies.get(target) else {
+            self.target = None;
+            return;
+        };
+
+        // Exponential approach; the same feel at any frame rate
+        let t = 1.0 - (-self.follow_rate * dt).exp();
+        self.position = self.position + (body.center() - self.position) * t;
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    /// Pixels per metre at the current zoom
+    pub fn scale(&self) -> Real {
+        self.pixels_per_metre * self.zoom
+    }
+    pub fn pixels_per_metre(&self) -> Real {
+        self.pixels_per_metre
+    }
+    pub fn transform(&self) -> Transform {
+        Transform::new(self.position, self.rotation)
+    }
+    /// Box around the part of the world in view
+    pub fn view_bounds(&self) -> AABB {
+        let (w, h) = (self.viewport.x, self.viewport.y);
+        let corners = [v2!(0.0, 0.0), v2!(w, 0.0), v2!(w, h), v2!(0.0, h)].map(|p| self.screen_to_world(p));
+        AABB::from_points(&corners)
+    }
+    pub fn target(&self) -> Option<BodyHandle> {
+        self.target
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_zoom(&mut self, zoom: Real) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+    pub fn set_pixels_per_metre(&mut self, pixels_per_metre: Real) {
+        self.pixels_per_metre = pixels_per_metre;
+    }
+    pub fn set_viewport(&mut self, viewport: Vector2<u32>) {
+        self.viewport = v2!(viewport.x as Real, viewport.y as Real);
+    }
+    /// Follows the body smoothly; None stops following
+    pub fn follow(&mut self, target: Option<BodyHandle>) {
+        self.target = target;
+    }
+    pub fn set_follow_rate(&mut self, follow_rate: Real) {
+        self.follow_rate = follow_rate;
+    }
+}
@@ -0,0 +1,232 @@
+/*
+    contact.rs
+    ----------------------------------------
+    Description:
+    * Provides the narrow phase & impulse response shared by the engine & the fixed-point lockstep world; SAT between
+      convex polygons, their contact points, and the positional correction & impulses resolving a contact
+    * Generic over Number, so that Real & Fixed run the same code; the engine's results are those of its float-only
+      code before it, bit for bit
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::common::{Number, Vector2};
+
+/* -------------------- VARIABLES ------------------- */
+const CORRECTION_PERCENTAGE: (i32, i32) = (2, 5); // Share of the overlap corrected per contact, as a ratio
+
+/* ------------------- STRUCTURES ------------------- */
+/// A body contacts can be resolved against; the engine's bodies, & the lockstep world's.
+pub trait Contactable<T: Number> {
+    /// Center of mass, in world space
+    fn center(&self) -> Vector2<T>;
+    fn inv_mass(&self) -> T;
+    fn inv_inertia(&self) -> T;
+    /// Restitution, static & dynamic friction coefficients
+    fn coefficients(&self) -> (T, T, T);
+    /// Velocity & angular velocity
+    fn velocities(&self) -> (Vector2<T>, T);
+    /// Position, velocity & angular velocity, to be written to
+    fn motion_mut(&mut self) -> (&mut Vector2<T>, &mut Vector2<T>, &mut T);
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+/// SAT test between two polygons, given their vertices & the unit normals of their edges; the axis of least
+/// overlap, & the overlap along it
+pub fn sat<T: Number>(v1: &[Vector2<T>], n1: &[Vector2<T>], v2: &[Vector2<T>], n2: &[Vector2<T>]) -> Option<(Vector2<T>, T)> {
+    let mut min: Option<(Vector2<T>, T)> = None;
+
+    for &axis in n1.iter().chain(n2) {
+        let (min_1, max_1) = projection_bounds(v1, axis);
+        let (min_2, max_2) = projection_bounds(v2, axis);
+        if max_1 <= min_2 || max_2 <= min_1 { return None; }
+
+        let overlap = max_1 - min_2;
+        if min.is_none_or(|(_, o)| overlap < o) {
+            min = Some((axis, overlap));
+        }
+    }
+
+    min
+}
+
+/// Unit normal of the edge from each vertex to the next
+pub fn normals<T: Number>(vertices: &[Vector2<T>]) -> Vec<Vector2<T>> {
+    (0..vertices.len())
+        .map(|i| unit(perp(vertices[(i + 1) % vertices.len()] - vertices[i])))
+        .collect()
+}
+
+/// Find the min/max points of a polygon projected onto a given axis
+pub fn projection_bounds<T: Number>(vertices: &[Vector2<T>], axis: Vector2<T>) -> (T, T) {
+    let (mut min, mut max) = (T::MAX, T::MIN);
+
+    for &vertex in vertices {
+        let proj = dot(vertex, axis);
+        if proj < min { min = proj; }
+        if proj > max { max = proj; }
+    }
+
+    (min, max)
+}
+
+/// Find collision contact points; the closest vertex-edge pair, & another if it's as close
+pub fn find_contacts<T: Number>(v1: &[Vector2<T>], v2: &[Vector2<T>]) -> Vec<Vector2<T>> {
+    let mut contacts: Vec<Vector2<T>> = Vec::with_capacity(2);
+    let mut min_dist = T::MAX;
+
+    for (points, edges) in [(v1, v2), (v2, v1)] {
+        for &p in points {
+            for j in 0..edges.len() {
+                let (d, contact) = p_dist(p, edges[j], edges[(j + 1) % edges.len()]);
+
+                if !contacts.is_empty() && (d - min_dist).abs() < T::DIST_SQ_TOLERANCE {
+                    let apart = contact - contacts[0];
+                    if dot(apart, apart) > T::LENGTH_TOLERANCE * T::LENGTH_TOLERANCE {
+                        contacts.truncate(1);
+                        contacts.push(contact);
+                    }
+                } else if d < min_dist {
+                    min_dist = d;
+                    contacts.clear();
+                    contacts.push(contact);
+                }
+            }
+        }
+    }
+
+    contacts
+}
+
+/// Squared distance from p to the segment l1-l2, & the closest point on it
+fn p_dist<T: Number>(p: Vector2<T>, l1: Vector2<T>, l2: Vector2<T>) -> (T, Vector2<T>) {
+    let l1_l2 = l2 - l1;
+    let d = dot(p - l1, l1_l2) / dot(l1_l2, l1_l2);
+
+    let out = if d <= T::ZERO {
+        l1
+    } else if d >= T::ONE {
+        l2
+    } else {
+        l1 + l1_l2 * d
+    };
+
+    let offset = out - p;
+    (dot(offset, offset), out)
+}
+
+/// Resolve a contact between two bodies, with the normal & overlap found by `sat`; positional correction shared out
+/// by inverse mass, then normal & frictional impulses, each calculated for every contact point before any is applied
+pub fn resolve<T: Number, B: Contactable<T>>(b1: &mut B, b2: &mut B, n: Vector2<T>, overlap: T, contacts: &[Vector2<T>]) {
+    // Ensure bodies always oriented in an expected manner
+    let (b1, b2) = if dot(b1.center(), n) >= dot(b2.center(), n) { (b2, b1) } else { (b1, b2) };
+
+    // Bodies which can't move (e.g. a kinematic character against the world) can't be resolved against each other
+    let w = b1.inv_mass() + b2.inv_mass();
+    if w == T::ZERO { return; }
+
+    // Body constants
+    let points = T::ratio(contacts.len() as i32, 1);
+    let (e_1, ks_1, kd_1) = b1.coefficients();
+    let (e_2, ks_2, kd_2) = b2.coefficients();
+    let e = (e_1 + e_2) * T::HALF;
+    let ks = (ks_1 + ks_2) * T::HALF; // Static frictional coefficient
+    let kd = (kd_1 + kd_2) * T::HALF; // Dynamic frictional coefficient
+
+    // Apply positional correction, shared out by inverse mass
+    let correction = n * overlap * T::ratio(CORRECTION_PERCENTAGE.0, CORRECTION_PERCENTAGE.1) / (w * points);
+    let (im_1, im_2) = (b1.inv_mass(), b2.inv_mass());
+    let (p_1, _, _) = b1.motion_mut();
+    *p_1 = *p_1 - correction * im_1;
+    let (p_2, _, _) = b2.motion_mut();
+    *p_2 = *p_2 + correction * im_2;
+
+    let rs: Vec<(Vector2<T>, Vector2<T>)> = contacts.iter().map(|&p| (p - b1.center(), p - b2.center())).collect();
+    let denominator = |b1: &B, b2: &B, r_1: Vector2<T>, r_2: Vector2<T>, d: Vector2<T>| {
+        let (r_1d, r_2d) = (dot(perp(r_1), d), dot(perp(r_2), d));
+        b1.inv_mass() + b2.inv_mass() + (r_1d * r_1d) * b1.inv_inertia() + (r_2d * r_2d) * b2.inv_inertia()
+    };
+
+    // Normal impulses
+    let js: Vec<T> = rs.iter().map(|&(r_1, r_2)| {
+        let v_n = dot(relative_velocity(b1, b2, r_1, r_2), n);
+        (v_n * -(e + T::ONE)) / denominator(b1, b2, r_1, r_2, n) / points
+    }).collect();
+    for (&(r_1, r_2), &j) in rs.iter().zip(&js) {
+        apply(b1, b2, r_1, r_2, n * j);
+    }
+
+    // Frictional impulses; static below ks * j, dynamic above
+    let f_impulses: Vec<Vector2<T>> = rs.iter().zip(&js).map(|(&(r_1, r_2), &j)| {
+        let rel_v = relative_velocity(b1, b2, r_1, r_2);
+        let t = unit(rel_v - n * dot(rel_v, n));
+        let f_j = -dot(rel_v, t) / denominator(b1, b2, r_1, r_2, t) / points;
+
+        if f_j.abs() <= ks * j { t * f_j } else { t * -j * kd }
+    }).collect();
+    for (&(r_1, r_2), &f_impulse) in rs.iter().zip(&f_impulses) {
+        apply(b1, b2, r_1, r_2, f_impulse);
+    }
+}
+
+/// Velocity of b2 relative to b1, at offsets r_1 & r_2 from their centers of mass
+fn relative_velocity<T: Number, B: Contactable<T>>(b1: &B, b2: &B, r_1: Vector2<T>, r_2: Vector2<T>) -> Vector2<T> {
+    let ((v_1, w_1), (v_2, w_2)) = (b1.velocities(), b2.velocities());
+    (v_2 + perp(r_2) * w_2) - (v_1 + perp(r_1) * w_1)
+}
+
+/// Apply an impulse from b1 to b2 at offsets r_1 & r_2 from their centers of mass
+fn apply<T: Number, B: Contactable<T>>(b1: &mut B, b2: &mut B, r_1: Vector2<T>, r_2: Vector2<T>, impulse: Vector2<T>) {
+    let (im_1, ii_1, im_2, ii_2) = (b1.inv_mass(), b1.inv_inertia(), b2.inv_mass(), b2.inv_inertia());
+
+    let (_, v_1, w_1) = b1.motion_mut();
+    *v_1 = *v_1 - impulse * im_1;
+    *w_1 = *w_1 - cross(r_1, impulse) * ii_1;
+
+    let (_, v_2, w_2) = b2.motion_mut();
+    *v_2 = *v_2 + impulse * im_2;
+    *w_2 = *w_2 + cross(r_2, impulse) * ii_2;
+}
+
+// Free functions, as Vector2's own are only for Scalars
+fn dot<T: Number>(v1: Vector2<T>, v2: Vector2<T>) -> T {
+    (v1.x * v2.x) + (v1.y * v2.y)
+}
+fn cross<T: Number>(v1: Vector2<T>, v2: Vector2<T>) -> T {
+    (v1.x * v2.y) - (v1.y * v2.x)
+}
+/// Rotated a quarter turn; (-y, x)
+fn perp<T: Number>(v: Vector2<T>) -> Vector2<T> {
+    Vector2 { x: -v.y, y: v.x }
+}
+/// Unit vector in the same direction; zero for the zero vector
+fn unit<T: Number>(v: Vector2<T>) -> Vector2<T> {
+    let n = (v.x * v.x + v.y * v.y).sqrt();
+    if n > T::ZERO { Vector2 { x: v.x / n, y: v.y / n } } else { Vector2 { x: T::ZERO, y: T::ZERO } }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Real;
+    use crate::v2;
+
+    fn square(x: Real, y: Real) -> Vec<Vector2<Real>> {
+        vec![v2!(x - 0.5, y - 0.5), v2!(x - 0.5, y + 0.5), v2!(x + 0.5, y + 0.5), v2!(x + 0.5, y - 0.5)]
+    }
+
+    #[test]
+    fn squares_overlap_along_the_shallowest_axis() {
+        // A square resting 0.1 m into the top of another, & offset to the side; y is down
+        let (below, above) = (square(0.0, 0.0), square(0.3, -0.9));
+        let (normal, overlap) = sat(&above, &normals(&above), &below, &normals(&below)).unwrap();
+        assert!((normal.y.abs() - 1.0).abs() < 1e-5 && (overlap - 0.1).abs() < 1e-5);
+
+        // Contacts at both ends of the shared span of the faces
+        let mut contacts: Vec<Real> = find_contacts(&above, &below).iter().map(|c| c.x).collect();
+        contacts.sort_by(Real::total_cmp);
+        assert_eq!(contacts.len(), 2);
+        assert!((contacts[0] + 0.2).abs() < 1e-5 && (contacts[1] - 0.5).abs() < 1e-5);
+
+        assert_eq!(sat(&square(0.0, -1.1), &normals(&square(0.0, -1.1)), &below, &normals(&below)), None);
+    }
+}
@@ -3,23 +3,36 @@
     ----------------------------------------
     Description:
     * Provides methods to resolve collision
-    * Broad phase uses a scaled grid
-    * Narrow phase uses SAT (Separating Axis Theorem)
+    * Broad phase uses a hashed grid; cells divide the world in view into GRID_SIZE, but carry on past it, so bodies
+      outside the view are only paired with their neighbours too. Bodies spanning more cells than the view holds are
+      kept aside & paired with every body instead
+    * Narrow phase uses SAT (Separating Axis Theorem); candidate pairs are tested on worker threads
+    * Bodies can also be queried directly; by a swept shape, a ray, or a point
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
-use crate::app::objects::Body;
-use crate::common::{ConvertPrimitives, Disp, GRID_SIZE, TBodyRef, TCollisionPairs, TCollisionGrid, TSharedRef, Vector2, Crd, CollisionResult, Projection, Axis, Vertex, almost_eq};
+use std::collections::{HashMap, HashSet};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::collision::contact;
+use crate::app::objects::{BodyRef, BodyView, ParticleSystem, Rope};
+use crate::app::parallel;
+use crate::common::{ConvertPrimitives, Disp, GRID_SIZE, TCollisionPairs, TSharedRef, TSoftBodyRef, Vector2, Crd, CollisionResult, SoftCollisionResult, ParticleCollisionResult, CastResult, Projection, Vertex, AABB, almost_eq, Real};
 use crate::v2;
 
 /* -------------------- VARIABLES ------------------- */
-
+const PARALLEL_PAIRS: usize = 128; // Least number of candidate pairs worth handing to a thread
+const MARGIN: Real = 0.01;         // Metres each AABB is grown by before it is placed in the grid, so resting contacts stay paired
+const MAX_CELLS: i64 = (GRID_SIZE.x * GRID_SIZE.y) as i64; // Bodies spanning more cells than this are paired with every body
+/// Global vertices of a body, & their winding; met by particles & rope points
+type TShape = (Vec<Vector2<Real>>, Real);
 
 /* ------------------- STRUCTURES ------------------- */
 pub struct CollisionDetector {
     shared: TSharedRef,
-    collision_grid: TCollisionGrid, // 2D vector of AABBs
-    out_of_bounds: Vec<TBodyRef>,   // 1D vector of AABBs which are out of bounds, but still should be accounted for in collision
+    collision_grid: HashMap<(Disp, Disp), Vec<BodyHandle>>, // Bodies overlapping each cell, by row & column
+    oversized: Vec<BodyHandle>,                              // Bodies spanning more than MAX_CELLS cells
+    threads: usize,                 // Threads that the narrow phase runs on
 }
 
 /* -------------------- FUNCTIONS ------------------- */
@@ -27,264 +40,517 @@ impl CollisionDetector {
     pub fn new(shared: TSharedRef) -> Self {
         CollisionDetector {
             shared,
-            collision_grid: vec![vec![vec![]; GRID_SIZE.y]; GRID_SIZE.x],
-            out_of_bounds: Vec::new(),
+            collision_grid: HashMap::new(),
+            oversized: Vec::new(),
+            threads: parallel::available_threads(),
         }
     }
 
-    pub fn evaluate(&mut self, bodies: &Vec<TBodyRef>) -> Vec<CollisionResult> {
-        self.collision_grid = vec![vec![vec![]; GRID_SIZE.y]; GRID_SIZE.x];
-        self.out_of_bounds = Vec::new();
+    /// Find collisions between the enabled bodies
+    pub fn evaluate(&mut self, bodies: &BodyArena) -> Vec<CollisionResult> {
+        self.collision_grid.clear();
+        self.oversized.clear();
 
         let candidate_pairs = self.broad_phase(bodies);
-        let colliding_pairs = self.narrow_phase(candidate_pairs);
+        let colliding_pairs = self.narrow_phase(candidate_pairs, bodies);
 
         // println!("Colliding={:?}", colliding_pairs);
         colliding_pairs
     }
 
-    /// Returns object pairs for more precise analysis in the narrow phase
-    fn broad_phase(&mut self, bodies: &Vec<TBodyRef>) -> TCollisionPairs {
-        let window_size: Vector2<Crd> = self.shared.borrow_mut().window_size.to();
-        let bounds: Vector2<Crd> = window_size / GRID_SIZE.to();
-        // Broad-phase results
-        let mut marked: Vec<(usize, usize)> = Vec::new();
-        let mut pairs: TCollisionPairs = Vec::new();
+    /// Drop a removed body from the collision grid & the pairs found by the last `evaluate`
+    pub fn forget(&mut self, handle: BodyHandle) {
+        for cell in self.collision_grid.values_mut() {
+            cell.retain(|&h| h != handle);
+        }
+        self.oversized.retain(|&h| h != handle);
 
-        for body_ref in bodies {
-            let body = body_ref.borrow_mut();
+        let mut shared = self.shared.borrow_mut();
+        for cell in shared.collision_grid.iter_mut().flatten() {
+            cell.retain(|&h| h != handle);
+        }
+        shared.broad_phase_pairs.retain(|pair| !pair.contains(&handle));
+        shared.narrow_phase_pairs.retain(|result| !result.bodies.contains(&handle));
+    }
 
-            // Evaluate whether body out of bounds
-            let mut points = vec![];
-            let aabb = body.aabb();
+    /// Find collisions between soft body hull edges & rigid bodies
+    pub fn evaluate_soft(&self, soft_bodies: &Vec<TSoftBodyRef>, bodies: &BodyArena) -> Vec<SoftCollisionResult> {
+        let mut results: Vec<SoftCollisionResult> = Vec::new();
 
-            for point in aabb.clone().points {
-                let point: Vector2<Crd> = (point / bounds).to();
-                points.push(point);
-            }
+        for soft_ref in soft_bodies {
+            let soft = soft_ref.borrow();
+            let soft_aabb = soft.aabb();
 
-            // Find maximum and minimum points
-            let max_x= points.iter().map(|p| p.disp().x).max().unwrap_or(-1);
-            let max_y= points.iter().map(|p| p.disp().y).max().unwrap_or(-1);
-            let min_x= points.iter().map(|p| p.disp().x).min().unwrap_or(-1);
-            let min_y= points.iter().map(|p| p.disp().y).min().unwrap_or(-1);
+            for (handle, body) in bodies.iter() {
+                // Broad phase; bounding box overlap
+                if !body.enabled
+                    || soft.ignore_groups.contains(&body.collision_group)
+                    || body.ignore_groups.contains(&soft.collision_group)
+                    || !soft_aabb.overlaps(&body.geometry().aabb)
+                { continue; }
 
-            // Fill grid
-            let grid_size_crd: Vector2<Disp> = GRID_SIZE.to();
-            let mut oob = false;
-
-            for i in min_y..=max_y { for j in min_x..=max_x {
-                // Allow not completely OOB objects to interact with collision
-                if i >= 0 && i < grid_size_crd.y && j >= 0 && j < grid_size_crd.x {
-                    let i = i as usize;
-                    let j = j as usize;
-                    self.collision_grid[i][j].push(body_ref.clone());
-
-                    // If cell has >= 2 objects, mark it as a collision candidate
-                    if self.collision_grid[i][j].len() < 2 || marked.contains(&(i, j)) { continue; }
-                    marked.push((i, j));
-                } else {
-                    if !oob {
-                        oob = true;
-                        self.out_of_bounds.push(body_ref.clone());
+                // Narrow phase; each hull edge is tested as a segment
+                for i in 0..soft.hull.len() {
+                    let edge = [soft.hull[i], soft.hull[(i + 1) % soft.hull.len()]];
+                    let a = soft.points[edge[0]].position;
+                    let b = soft.points[edge[1]].position;
+
+                    if let Some((normal, overlap)) = self.segment_collision(a, b, &body) {
+                        // Deepest end of the segment, or its midpoint if both ends are equally deep
+                        let (d_a, d_b) = (Vector2::dot(a, normal), Vector2::dot(b, normal));
+                        let contact = if almost_eq(d_a, d_b) { (a + b) / 2.0 } else if d_a < d_b { a } else { b };
+
+                        results.push(SoftCollisionResult {
+                            soft_body: soft_ref.clone(),
+                            body: handle,
+                            edge,
+                            normal,
+                            overlap,
+                            contact,
+                        });
                     }
                 }
-            }}
+            }
         }
 
-        // Update shared collision grid information
-        self.shared.borrow_mut().collision_grid = self.collision_grid.clone();
+        results
+    }
 
-        // Fetch in-bound collision pairs
-        for n in 0..marked.len() {
-            let (i, j) = marked[n];
-            let cell = self.collision_grid[i][j].clone();
+    /// Find collisions between particles & rigid bodies
+    pub fn evaluate_particles(&self, system: &ParticleSystem, bodies: &BodyArena) -> Vec<ParticleCollisionResult> {
+        let points: Vec<(Vector2<Real>, Real)> = system.particles.iter().map(|p| (p.position, p.radius)).collect();
+        self.evaluate_points(&points, &system.ignore_groups, bodies)
+    }
 
-            // Iterate through all possible cell permutations
-            for a in 0..cell.len() { for b in 1..cell.len() {
-                // Ensure no duplicates
-                if cell[a] == cell[b]
-                    || pairs.contains(&[cell[a].clone(), cell[b].clone()])
-                    || pairs.contains(&[cell[b].clone(), cell[a].clone()])
-                    || cell[a].borrow().ignore_groups.contains(&cell[b].borrow().collision_group)
-                    || cell[b].borrow().ignore_groups.contains(&cell[a].borrow().collision_group)
-                { continue; }
+    /// Find collisions between verlet rope points & rigid bodies
+    pub fn evaluate_rope(&self, rope: &Rope, bodies: &BodyArena) -> Vec<ParticleCollisionResult> {
+        let points: Vec<(Vector2<Real>, Real)> = rope.points.iter().map(|&p| (p, rope.thickness / 2.0)).collect();
+        self.evaluate_points(&points, &rope.ignore_groups, bodies)
+    }
 
-                pairs.push([cell[a].clone(), cell[b].clone()]);
-            }}
-        }
+    /// Find collisions between circular points & rigid bodies; candidates come from the grid built by the last `evaluate`
+    fn evaluate_points(&self, points: &[(Vector2<Real>, Real)], ignore_groups: &[i32], bodies: &BodyArena) -> Vec<ParticleCollisionResult> {
+        let cell_size = self.cell_size();
 
-        // Fetch out-of-bounds collision pairs
-        for a in 0..self.out_of_bounds.len() { for b in 1..self.out_of_bounds.len() {
-            if self.out_of_bounds[a] == self.out_of_bounds[b]
-                || pairs.contains(&[self.out_of_bounds[a].clone(), self.out_of_bounds[b].clone()])
-                || pairs.contains(&[self.out_of_bounds[b].clone(), self.out_of_bounds[a].clone()])
-                || self.out_of_bounds[a].borrow().ignore_groups.contains(&self.out_of_bounds[b].borrow().collision_group)
-                || self.out_of_bounds[b].borrow().ignore_groups.contains(&self.out_of_bounds[a].borrow().collision_group)
-            { continue; }
+        let mut results: Vec<ParticleCollisionResult> = Vec::new();
+        // Global vertices & winding of every body met so far; shared by all particles
+        let mut shapes: HashMap<BodyHandle, Option<TShape>> = HashMap::new();
+        let mut candidates: Vec<BodyHandle> = Vec::new();
 
-            pairs.push([self.out_of_bounds[a].clone(), self.out_of_bounds[b].clone()]);
-        }}
+        for (index, &(position, r)) in points.iter().enumerate() {
+            let (min, max) = (Self::cell(position - v2!(r), cell_size), Self::cell(position + v2!(r), cell_size));
 
-        // Update shared broad-phase pair information
-        self.shared.borrow_mut().broad_phase_pairs = pairs.clone();
+            // Gather bodies in every cell the particle touches
+            candidates.clear();
+            for i in min.y..=max.y { for j in min.x..=max.x {
+                if let Some(cell) = self.collision_grid.get(&(i, j)) { candidates.extend(cell.iter()); }
+            }}
+            candidates.extend(self.oversized.iter());
+            // Drop duplicates; bodies are then met in handle order
+            candidates.sort();
+            candidates.dedup();
+
+            for &handle in &candidates {
+                let shape = shapes.entry(handle).or_insert_with(|| {
+                    let body = bodies.body(handle);
+                    if ignore_groups.contains(&body.collision_group) { return None; }
+
+                    let vertices = body.geometry().into_owned().vertices;
+                    let mut winding = 0.0;
+                    for i in 0..vertices.len() {
+                        winding += Vector2::cross(vertices[i], vertices[(i + 1) % vertices.len()]);
+                    }
+                    Some((vertices, winding))
+                });
+
+                let Some((vertices, winding)) = shape else { continue; };
+                if let Some((normal, overlap)) = Self::particle_collision(position, r, vertices, *winding) {
+                    results.push(ParticleCollisionResult {
+                        particle: index,
+                        body: handle,
+                        normal,
+                        overlap,
+                    });
+                }
+            }
+        }
 
-        pairs
+        results
     }
 
-    /// Confirm/deny collision using the Separating Axis Theorem (SAT)
-    fn narrow_phase(&self, pairs: TCollisionPairs) -> Vec<CollisionResult> {
-        let mut colliding_pairs: Vec<CollisionResult> = Vec::new();
-
-        for pair in pairs {
-            let body1 = pair[0].borrow();
-            let body2 = pair[1].borrow();
-            // Collision result
-            let mut colliding = true;
-            let mut min_overlap: f64 = -1.0;
-            let mut min_axis: Vector2<f64> = v2!(-1.0);
-            // let mut min_point: Vector2<f64> = v2!(0.0);
-
-            // Get all non-duplicate axes
-            let mut axes: Vec<Axis> = body1.axes().iter().map(|&ax| Axis { v2: ax, parent: pair[0].clone() }).collect();
-            for axis in body2.axes() {
-                let ax = Axis { v2: axis, parent: pair[1].clone() };
-                if axes.contains(&ax) { continue; }
-                axes.push(ax);
+    /// Sweeps a convex shape (given by its global vertices) along delta, & returns the earliest hit against the bodies.
+    /// Bodies in an ignored collision group, and the skipped body, are not hit.
+    pub fn shape_cast(
+        shape: &[Vector2<Real>], delta: Vector2<Real>,
+        bodies: &BodyArena, ignore_groups: &[i32], skip: Option<BodyHandle>,
+    ) -> Option<CastResult> {
+        let start = AABB::from_points(shape);
+        let swept = start.union(AABB::new(start.min + delta, start.max + delta));
+        let mut first: Option<CastResult> = None;
+
+        for (handle, body) in bodies.iter() {
+            if skip == Some(handle) || !body.enabled { continue; }
+            let geometry = body.geometry();
+            if ignore_groups.contains(&body.collision_group) || !swept.overlaps(&geometry.aabb) { continue; }
+
+            if let Some((t, normal, depth)) = Self::sweep(shape, &geometry.vertices, delta) {
+                if first.as_ref().is_none_or(|hit| t < hit.t) {
+                    first = Some(CastResult { body: handle, normal, t, depth });
+                }
             }
+        }
 
-            // Check whether points overlap in axis projection
-            for axis in axes {
-                let ax = axis.v2;
-                let proj_1 = self.projection_bounds(&body1, ax.norm());
-                let proj_2 = self.projection_bounds(&body2, ax.norm());
-
-                // Check if they are colliding
-                if proj_1.max <= proj_2.min || proj_2.max <= proj_1.min {
-                    colliding = false;
-                    break;
-                } else {
-                    // Update minimum overlap
-                    let overlap = proj_1.max - proj_2.min;
-
-                    if min_overlap == -1.0 || overlap < min_overlap {
-                        min_overlap = overlap;
-                        min_axis = ax.norm();
-                    }
+        first
+    }
+
+    /// Casts the segment from origin to origin + delta, & returns the earliest body it enters.
+    /// Bodies in an ignored collision group are not hit; a ray starting inside a body hits it at t = 0.
+    pub fn ray_cast(origin: Vector2<Real>, delta: Vector2<Real>, bodies: &BodyArena, ignore_groups: &[i32]) -> Option<CastResult> {
+        let mut first: Option<CastResult> = None;
+
+        for (handle, body) in bodies.iter() {
+            if !body.enabled || ignore_groups.contains(&body.collision_group) { continue; }
+            let geometry = body.geometry();
+            let Some(t) = geometry.aabb.ray_cast(origin, delta) else { continue; };
+            if first.as_ref().is_some_and(|hit| hit.t <= t) { continue; }
+
+            if let Some((t, normal)) = Self::clip_ray(origin, delta, &geometry.vertices) {
+                if first.as_ref().is_none_or(|hit| t < hit.t) {
+                    first = Some(CastResult { body: handle, normal, t, depth: 0.0 });
                 }
             }
+        }
 
-            if colliding {
-                let contacts = self.find_contacts(&body1, &body2);
-
-                let proj_1 = self.projection_bounds(&body1, min_axis);
-                let proj_2 = self.projection_bounds(&body2, min_axis);
+        first
+    }
 
-                let colliding_pair = CollisionResult {
-                    bodies: pair.clone(),
-                    normal: min_axis,
-                    overlap: min_overlap,
-                    contacts,
-                };
+    /// The body containing the point; where bodies overlap, the one drawn last (on top).
+    /// Bodies in an ignored collision group are skipped.
+    pub fn point_query(point: Vector2<Real>, bodies: &BodyArena, ignore_groups: &[i32]) -> Option<BodyHandle> {
+        let mut top: Option<BodyHandle> = None;
 
-                colliding_pairs.push(colliding_pair);
+        for (handle, body) in bodies.iter() {
+            if !body.enabled || ignore_groups.contains(&body.collision_group) { continue; }
+            let geometry = body.geometry();
+            if geometry.aabb.contains_point(point) && Self::contains(point, &geometry.vertices) {
+                top = Some(handle);
             }
         }
 
-        // Update shared narrow-phase collision pair indicator
-        self.shared.borrow_mut().narrow_phase_pairs = colliding_pairs.clone();
+        top
+    }
 
-        colliding_pairs
+    /// Whether the point lies inside (or on) a convex polygon
+    fn contains(point: Vector2<Real>, vertices: &[Vector2<Real>]) -> bool {
+        if vertices.len() < 3 { return false; }
+        let centroid = vertices.iter().fold(v2!(0.0), |sum, &v| sum + v) / vertices.len() as Real;
+
+        (0..vertices.len()).all(|i| {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            let edge = b - a;
+            let mut n = v2!(-edge.y, edge.x);
+            if Vector2::dot(n, a - centroid) < 0.0 { n = n * -1.0; }
+            Vector2::dot(n, point - a) <= 0.0
+        })
     }
 
-    /// Find the min/max points of body projected onto a given axis
-    fn projection_bounds(&self, body: &Body, axis: Vector2<f64>) -> Projection {
-        let vertices: Vec<Vector2<f64>> = body.vertices.clone().iter().map(|vtx| body.globalise(vtx.to_vec2()).to()).collect();
-
-        // let proj = Vector2::dot(vertices[0], axis);
-        // let mut max: f64 = proj;
-        // let mut min: f64 = proj;
-        let mut min = f64::MAX;
-        let mut max = f64::MIN;
-
-        // Get bounds over polygon`
-        for i in 0..body.sides as usize {
-            let proj = Vector2::dot(vertices[i], axis);
-            if proj < min {
-                min = proj;
+    /// Clips the segment against a convex polygon (Cyrus-Beck); returns the fraction of delta at which it enters, & the
+    /// outward normal of the edge it enters through
+    fn clip_ray(origin: Vector2<Real>, delta: Vector2<Real>, vertices: &[Vector2<Real>]) -> Option<(Real, Vector2<Real>)> {
+        if vertices.len() < 3 { return None; }
+        let centroid = vertices.iter().fold(v2!(0.0), |sum, &v| sum + v) / vertices.len() as Real;
+        let (mut t_enter, mut t_exit): (Real, Real) = (0.0, 1.0);
+        let mut normal = delta.norm() * -1.0;
+
+        for i in 0..vertices.len() {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            let edge = b - a;
+            let mut n = v2!(-edge.y, edge.x).norm();
+            if Vector2::dot(n, a - centroid) < 0.0 { n = n * -1.0; }
+
+            // Signed distance of the origin inside the edge, & how fast the ray leaves it
+            let (inside, rate) = (Vector2::dot(n, a - origin), Vector2::dot(n, delta));
+            if rate == 0.0 {
+                if inside < 0.0 { return None; }
+                continue;
             }
-            if proj > max {
-                max = proj;
+
+            let t = inside / rate;
+            if rate < 0.0 {
+                if t > t_enter { (t_enter, normal) = (t, n); }
+            } else {
+                t_exit = t_exit.min(t);
             }
+            if t_enter > t_exit { return None; }
         }
 
-        // println!("{min}, {max} | {:?}, {:?}", p_min, p_max);
-        Projection { min, max }
+        Some((t_enter, normal))
     }
 
-    /// Find collision contact points
-    fn find_contacts(&self, b1: &Body, b2: &Body) -> Vec<Vector2<f64>> {
-        let v1: Vec<Vector2<f64>> = b1.vertices.clone().iter().map(|v| b1.globalise(v.to_vec2()).to()).collect();
-        let v2: Vec<Vector2<f64>> = b2.vertices.clone().iter().map(|v| b2.globalise(v.to_vec2()).to()).collect();
-
-        let mut contacts: Vec<Vector2<f64>> = vec![v2!(0.0), v2!(0.0)];
-        let mut min_dist: f64 = 10f64.powi(10);
+    /// Swept SAT between convex polygons a (moving by delta) & b; returns the time of impact, the normal (b -> a) &
+    /// the initial penetration. Polygons which already overlap hit at t = 0, along the axis of least penetration.
+    fn sweep(a: &[Vector2<Real>], b: &[Vector2<Real>], delta: Vector2<Real>) -> Option<(Real, Vector2<Real>, Real)> {
+        let normals = |poly: &[Vector2<Real>]| (0..poly.len()).map(|i| {
+            let edge = poly[(i + 1) % poly.len()] - poly[i];
+            v2!(-edge.y, edge.x).norm()
+        }).collect::<Vec<_>>();
+        let project = |poly: &[Vector2<Real>], axis: Vector2<Real>| poly.iter()
+            .map(|&p| Vector2::dot(p, axis))
+            .fold((Real::MAX, Real::MIN), |(min, max), d| (min.min(d), max.max(d)));
+
+        let (mut t_enter, mut t_exit) = (Real::MIN, Real::MAX);
+        let mut normal = v2!(0.0);
+        let (mut depth, mut depth_normal) = (Real::MAX, v2!(0.0));
+
+        for axis in normals(a).into_iter().chain(normals(b)) {
+            let ((a_min, a_max), (b_min, b_max)) = (project(a, axis), project(b, axis));
+            let d = Vector2::dot(delta, axis);
+
+            if a_max <= b_min {
+                // a lies before b on this axis, & must move forwards to reach it
+                if d <= 0.0 { return None; }
+                if (b_min - a_max) / d > t_enter {
+                    t_enter = (b_min - a_max) / d;
+                    normal = axis * -1.0;
+                }
+                t_exit = t_exit.min((b_max - a_min) / d);
+            } else if b_max <= a_min {
+                if d >= 0.0 { return None; }
+                if (b_max - a_min) / d > t_enter {
+                    t_enter = (b_max - a_min) / d;
+                    normal = axis;
+                }
+                t_exit = t_exit.min((b_min - a_max) / d);
+            } else {
+                // Overlapping on this axis; remember the least penetration in case the polygons already overlap
+                for (overlap, n) in [(b_max - a_min, axis), (a_max - b_min, axis * -1.0)] {
+                    if overlap < depth {
+                        (depth, depth_normal) = (overlap, n);
+                    }
+                }
+                if d > 0.0 {
+                    t_exit = t_exit.min((b_max - a_min) / d);
+                } else if d < 0.0 {
+                    t_exit = t_exit.min((b_min - a_max) / d);
+                }
+            }
 
-        for i in 0..v1.len() {
-            let p = v1[i];
+            if t_enter > t_exit || t_enter > 1.0 { return None; }
+        }
 
-            for j in 0..v2.len() {
-                let l1 = v2[j];
-                let l2 = v2[if j + 1 == v2.len() { 0 } else { j + 1 }];
+        if t_enter == Real::MIN {
+            Some((0.0, depth_normal, depth))
+        } else {
+            Some((t_enter, normal, 0.0))
+        }
+    }
 
-                let (d, contact) = Vector2::<f64>::p_dist(p, l1, l2);
+    /// Circle-polygon test for a particle; returns the normal (body -> particle) & overlap
+    fn particle_collision(p: Vector2<Real>, radius: Real, vertices: &[Vector2<Real>], winding: Real) -> Option<(Vector2<Real>, Real)> {
+        let mut inside = true;
+        let mut min_dist = Real::MAX;
+        let mut closest: Vector2<Real> = v2!(0.0);
 
-                // println!("d={}, md={}, eq={}", d.sqrt(), min_dist.sqrt(), almost_eq(d.sqrt(), min_dist.sqrt()));
+        for i in 0..vertices.len() {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            if Vector2::cross(b - a, p - a) * winding < 0.0 {
+                inside = false;
+            }
 
-                if almost_eq(d, min_dist) {
-                    if !Vector2::<f64>::almost_eq(contact, contacts[0]) {
-                       contacts[1] = contact;
-                    }
-                } else if d < min_dist {
-                    min_dist = d;
-                    contacts[0] = contact;
-                    contacts[1] = v2!(0f64);
-                }
+            let (d, point) = Vector2::<Real>::p_dist(p, a, b);
+            if d < min_dist {
+                min_dist = d;
+                closest = point;
             }
         }
 
-        for i in 0..v2.len() {
-            let p = v2[i];
+        let dist = min_dist.sqrt();
+        if inside {
+            Some(((closest - p).norm(), dist + radius))
+        } else if dist < radius {
+            Some(((p - closest).norm(), radius - dist))
+        } else {
+            None
+        }
+    }
 
-            for j in 0..v1.len() {
-                let l1 = v1[j];
-                let l2 = v1[if j + 1 == v1.len() { 0 } else { j + 1 }];
+    /// SAT test between the segment a-b and a body; returns the normal (body -> segment) & overlap
+    fn segment_collision(&self, a: Vector2<Real>, b: Vector2<Real>, body: &BodyRef) -> Option<(Vector2<Real>, Real)> {
+        let edge = b - a;
+        let shape = body.geometry();
+        let mut axes: Vec<Vector2<Real>> = shape.normals.clone();
+        axes.push(v2!(-edge.y, edge.x).norm());
+
+        let mut min_overlap = Real::MAX;
+        let mut min_axis: Vector2<Real> = v2!(0.0);
+
+        for axis in axes {
+            let (min, max) = contact::projection_bounds(&shape.vertices, axis);
+            let proj_b = Projection { min, max };
+            let (p_a, p_b) = (Vector2::dot(a, axis), Vector2::dot(b, axis));
+            let proj_s = Projection { min: p_a.min(p_b), max: p_a.max(p_b) };
+
+            let overlap = (proj_b.max - proj_s.min).min(proj_s.max - proj_b.min);
+            if overlap <= 0.0 {
+                return None;
+            }
 
-                let (d, contact) = Vector2::<f64>::p_dist(p, l1, l2);
+            if overlap < min_overlap {
+                min_overlap = overlap;
+                // Orient the axis from the body towards the segment
+                min_axis = if proj_s.min + proj_s.max < proj_b.min + proj_b.max { axis * -1.0 } else { axis };
+            }
+        }
 
-                // println!("d={}, md={}, eq={}", d.sqrt(), min_dist.sqrt(), almost_eq(d.sqrt(), min_dist.sqrt()));
+        Some((min_axis, min_overlap))
+    }
 
-                if almost_eq(d, min_dist) {
-                    if !Vector2::<f64>::almost_eq(contact, contacts[0]) {
-                        contacts[1] = contact;
-                    }
-                } else if d < min_dist {
-                    min_dist = d;
-                    contacts[0] = contact;
-                    contacts[1] = v2!(0f64);
-                }
+    /// Returns object pairs for more precise analysis in the narrow phase
+    fn broad_phase(&mut self, bodies: &BodyArena) -> TCollisionPairs {
+        let cell_size = self.cell_size();
+        // Broad-phase results
+        let mut marked: Vec<(Disp, Disp)> = Vec::new();
+        let mut pairs: TCollisionPairs = Vec::new();
+        let mut seen: HashSet<[BodyHandle; 2]> = HashSet::new(); // Pairs considered so far, lowest handle first
+        let mut enabled: Vec<BodyHandle> = Vec::new();
+
+        for (handle, body) in bodies.iter() {
+            if !body.enabled { continue; }
+            enabled.push(handle);
+
+            let aabb = body.geometry().aabb.fattened(MARGIN);
+            let (min, max) = (Self::cell(aabb.min, cell_size), Self::cell(aabb.max, cell_size));
+            let cells = (max.x as i64 - min.x as i64 + 1) * (max.y as i64 - min.y as i64 + 1);
+            if cells > MAX_CELLS {
+                self.oversized.push(handle);
+                continue;
             }
+
+            // Fill grid
+            for i in min.y..=max.y { for j in min.x..=max.x {
+                let cell = self.collision_grid.entry((i, j)).or_default();
+                cell.push(handle);
+
+                // Once a cell has 2 objects, mark it as a collision candidate
+                if cell.len() == 2 { marked.push((i, j)); }
+            }}
         }
 
-        // Remove all occurrences of (0, 0)
-        for i in 0..contacts.len() {
-            let el = contacts[i];
-            if el.x == 0f64 && el.y == 0f64 {
-                contacts.remove(i);
+        // Update shared collision grid information; only the cells in view are drawn
+        let mut shared_grid = vec![vec![vec![]; GRID_SIZE.y]; GRID_SIZE.x];
+        for (&(i, j), cell) in &self.collision_grid {
+            if (0..GRID_SIZE.y as Disp).contains(&i) && (0..GRID_SIZE.x as Disp).contains(&j) {
+                shared_grid[i as usize][j as usize] = cell.clone();
             }
         }
+        self.shared.borrow_mut().collision_grid = shared_grid;
+
+        // Fetch collision pairs in each cell
+        for key in &marked {
+            let cell = &self.collision_grid[key];
+
+            // Iterate through all possible cell permutations
+            for a in 0..cell.len() { for b in 1..cell.len() {
+                // Ensure no duplicates
+                if cell[a] == cell[b]
+                    || !seen.insert([cell[a].min(cell[b]), cell[a].max(cell[b])])
+                    || bodies[cell[a]].ignore_groups.contains(&bodies[cell[b]].collision_group)
+                    || bodies[cell[b]].ignore_groups.contains(&bodies[cell[a]].collision_group)
+                { continue; }
+
+                pairs.push([cell[a], cell[b]]);
+            }}
+        }
+
+        // Fetch oversized collision pairs
+        for &a in &self.oversized { for &b in &enabled {
+            if a == b
+                || !seen.insert([a.min(b), a.max(b)])
+                || bodies[a].ignore_groups.contains(&bodies[b].collision_group)
+                || bodies[b].ignore_groups.contains(&bodies[a].collision_group)
+            { continue; }
+
+            pairs.push([a, b]);
+        }}
+
+        // Update shared broad-phase pair information
+        self.shared.borrow_mut().broad_phase_pairs = pairs.clone();
+
+        pairs
+    }
+
+    /// Size of a grid cell; the world in view is divided into GRID_SIZE cells
+    fn cell_size(&self) -> Vector2<Crd> {
+        self.shared.borrow().world_size / GRID_SIZE.to()
+    }
+
+    /// Column (x) & row (y) of the cell holding a point; coordinates past Disp's range saturate
+    fn cell(point: Vector2<Crd>, cell_size: Vector2<Crd>) -> Vector2<Disp> {
+        v2!((point.x / cell_size.x).floor() as Disp, (point.y / cell_size.y).floor() as Disp)
+    }
+
+    /// Confirm/deny collision using the Separating Axis Theorem (SAT); pairs are tested in parallel
+    fn narrow_phase(&self, pairs: TCollisionPairs, bodies: &BodyArena) -> Vec<CollisionResult> {
+        let colliding_pairs: Vec<CollisionResult> = parallel::map(&pairs, self.threads, PARALLEL_PAIRS, |&pair| Self::sat(pair, bodies))
+            .into_iter().flatten().collect();
+
+        // Update shared narrow-phase collision pair indicator
+        self.shared.borrow_mut().narrow_phase_pairs = colliding_pairs.clone();
+
+        colliding_pairs
+    }
+
+    /// SAT test between a pair of bodies
+    fn sat(pair: [BodyHandle; 2], bodies: &BodyArena) -> Option<CollisionResult> {
+        let (body1, body2) = (bodies.body(pair[0]), bodies.body(pair[1]));
+        let (shape1, shape2) = (body1.geometry(), body2.geometry());
+
+        let (normal, overlap) = contact::sat(&shape1.vertices, &shape1.normals, &shape2.vertices, &shape2.normals)?;
+        Some(CollisionResult {
+            bodies: pair,
+            normal,
+            overlap,
+            contacts: contact::find_contacts(&shape1.vertices, &shape2.vertices),
+        })
+    }
 
-        contacts
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::objects::Body;
+    use crate::common::{BodyForm, Materials, Shared};
+    use crate::rect;
+
+    #[test]
+    fn bodies_out_of_view_are_only_paired_with_neighbours() {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        let mut detector = CollisionDetector::new(shared);
+
+        // A row of separate boxes far outside the view, with one pair touching at its end, & a floor spanning it all
+        let mut bodies = BodyArena::new();
+        for i in 0..50 {
+            bodies.insert(rect!(v2!(100.0 + i as Real * 3.0, -40.0), 1.0, 1.0));
+        }
+        let a = bodies.insert(rect!(v2!(300.0, -40.0), 1.0, 1.0));
+        let b = bodies.insert(rect!(v2!(300.5, -40.0), 1.0, 1.0));
+        let floor = bodies.insert(rect!(v2!(-1000.0, -38.0), 2000.0, 1.0));
+
+        let pairs = detector.broad_phase(&bodies);
+        assert_eq!(pairs.iter().filter(|pair| !pair.contains(&floor)).collect::<Vec<_>>(), vec![&[a, b]]);
+        assert_eq!(pairs.len(), 1 + 52);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod contact;
 pub mod detector;
 pub mod resolver;
 
@@ -6,8 +6,11 @@
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
-use crate::common::{CollisionResult, TSharedRef, Vector2};
-use crate::v2;
+use crate::app::arena::{BodyArena, Island};
+use crate::app::collision::contact;
+use crate::app::objects::{BodyView, ParticleSystem, Rope};
+use crate::app::parallel;
+use crate::common::{CollisionResult, ParticleCollisionResult, SoftCollisionResult, TSharedRef, Vector2, Real};
 
 
 /* -------------------- VARIABLES ------------------- */
@@ -16,160 +19,175 @@ use crate::v2;
 /* ------------------- STRUCTURES ------------------- */
 pub struct CollisionResolver {
     shared: TSharedRef,
+    threads: usize, // Threads that islands are solved on
 }
 
 /* -------------------- FUNCTIONS ------------------- */
-const CORRECTION_PERCENTAGE: f64 = 0.4;
+const CORRECTION_PERCENTAGE: Real = 0.4;
+const PARALLEL_COLLISIONS: usize = 64; // Fewer collisions than this are resolved on the calling thread
 
 impl CollisionResolver {
     pub fn new(shared: TSharedRef) -> Self {
         CollisionResolver {
-            shared
+            shared,
+            threads: parallel::available_threads(),
         }
     }
 
-    pub fn resolve(&self, collisions: Vec<CollisionResult>) {
+    /// Resolve collisions between rigid bodies; independent islands are resolved in parallel
+    pub fn resolve(&self, collisions: Vec<CollisionResult>, bodies: &mut BodyArena) {
+        let threads = if collisions.len() < PARALLEL_COLLISIONS { 1 } else { self.threads };
+        let pairs: Vec<_> = collisions.iter().map(|result| result.bodies).collect();
+        let (islands, of_pair) = bodies.islands(&pairs);
+
+        let mut work: Vec<(Island, Vec<CollisionResult>)> = islands.into_iter().map(|island| (island, Vec::new())).collect();
+        for (result, island) in collisions.into_iter().zip(of_pair) {
+            if let Some(island) = island { work[island].1.push(result); }
+        }
+
+        parallel::for_each_mut(&mut work, threads, 1, |(island, collisions)| Self::resolve_island(collisions, island));
+    }
+
+    fn resolve_island(collisions: &[CollisionResult], bodies: &mut Island) {
+        for result in collisions {
+            let Some((mut b1, mut b2)) = bodies.pair_mut(result.bodies[0], result.bodies[1]) else { continue; };
+            contact::resolve(&mut b1, &mut b2, result.normal, result.overlap, &result.contacts);
+        }
+    }
+
+    /// Resolve collisions between soft body hull edges & rigid bodies.
+    /// Both ends of an edge move together; the edge is treated as a single particle with their combined mass.
+    pub fn resolve_soft(&self, collisions: Vec<SoftCollisionResult>, bodies: &mut BodyArena) {
+        for result in collisions {
+            let mut soft = result.soft_body.borrow_mut();
+            let mut body = bodies.body_mut(result.body);
+            let [a, b] = result.edge;
+            let n = result.normal;
+
+            let e = (soft.material.e + body.material.e) * 0.5;
+            let kd = (soft.material.kd + body.material.kd) * 0.5;
+
+            let r = result.contact - body.center();
+            let w_edge = 1.0 / (soft.points[a].mass + soft.points[b].mass);
+            let w_body = body.generalised_inv_mass(r, n);
+            let w = w_edge + w_body;
+
+            // Apply positional correction
+            let correction = n * result.overlap * CORRECTION_PERCENTAGE / w;
+            soft.points[a].position = soft.points[a].position + correction * w_edge;
+            soft.points[b].position = soft.points[b].position + correction * w_edge;
+            body.apply_correction(correction * -1.0, r);
+
+            // Skip resolution if the edge & body are moving apart
+            let edge_v = (soft.points[a].velocity + soft.points[b].velocity) / 2.0;
+            let rel_v = edge_v - body.point_velocity(r);
+            let v_n = Vector2::dot(rel_v, n);
+            if v_n > 0.0 { continue; }
+
+            // Normal impulse
+            let j = -(e + 1.0) * v_n / w;
+
+            // Frictional impulse, clamped by the dynamic friction coefficient
+            let v_t = rel_v - n * v_n;
+            let t = v_t.norm();
+            let w_t = w_edge + body.generalised_inv_mass(r, t);
+            let f_j = (-Vector2::dot(rel_v, t) / w_t).max(-kd * j);
+
+            let impulse = n * j + t * f_j;
+            soft.points[a].velocity = soft.points[a].velocity + impulse * w_edge;
+            soft.points[b].velocity = soft.points[b].velocity + impulse * w_edge;
+            body.apply_impulse(impulse * -1.0, r);
+        }
+    }
+
+    /// Resolve collisions between particles & rigid bodies. One-way; the bodies are left untouched.
+    pub fn resolve_particles(&self, system: &mut ParticleSystem, collisions: Vec<ParticleCollisionResult>, bodies: &BodyArena) {
         for result in collisions {
-            // Deconstruct collision pair info
-            let mut b1 = result.bodies[0].borrow_mut();
-            let mut b2 = result.bodies[1].borrow_mut();
-            let overlap = result.overlap;
+            let body = bodies.body(result.body);
             let n = result.normal;
+            let (e, friction) = (system.restitution, system.friction);
+            let particle = &mut system.particles[result.particle];
 
-            let points = result.contacts.len();
+            particle.position = particle.position + n * result.overlap;
 
-            // Ensure bodies always oriented in an expected manner
-            if Vector2::dot(b1.globalise(v2!(0.0)), n) >= Vector2::dot(b2.globalise(v2!(0.0)), n) {
-                (b1, b2) = (b2, b1);
-            }
+            // Reflect the approaching part of the velocity relative to the body's surface
+            let rel_v = particle.velocity - body.point_velocity(particle.position - body.center());
+            let v_n = Vector2::dot(rel_v, n);
+            if v_n >= 0.0 { continue; }
+
+            let v_t = rel_v - n * v_n;
+            particle.velocity = particle.velocity - n * (v_n * (1.0 + e)) - v_t * friction;
+        }
+    }
 
-            // Body constants
-            let e = (b1.material.e + b2.material.e) * 0.5;
-            let ks = (b1.material.ks + b2.material.ks) * 0.5; // Static frictional coefficient
-            let kd = (b1.material.kd + b2.material.kd) * 0.5; // Dynamic frictional coefficient
+    /// Resolve collisions between verlet rope points & rigid bodies. Two-way; the body is pushed back by the rope.
+    pub fn resolve_rope(&self, rope: &mut Rope, collisions: Vec<ParticleCollisionResult>, bodies: &mut BodyArena, dt: Real) {
+        for result in collisions {
+            let i = result.particle;
+            if rope.anchored_to(i, result.body) { continue; }
 
-            // Shared arrays
-            let mut js: Vec<f64> = Vec::new();
-            let mut impulses: Vec<Vector2<f64>> = Vec::new();
-            let mut f_impulses: Vec<Vector2<f64>> = Vec::new();
+            let mut body = bodies.body_mut(result.body);
+            let n = result.normal;
 
-            let mut r_1s: Vec<Vector2<f64>> = Vec::new();
-            let mut r_2s: Vec<Vector2<f64>> = Vec::new();
+            let r = rope.points[i] - body.center();
+            let w_point = 1.0 / rope.point_mass;
+            let w = w_point + body.generalised_inv_mass(r, n);
 
             // Apply positional correction
-            let correction = n * overlap * (if b1.frozen || b2.frozen { 1.0 } else { 0.5 }) * CORRECTION_PERCENTAGE / (points as f64);
-            b1.position = b1.position - correction * b1.inv_mass();
-            b2.position = b2.position + correction * b2.inv_mass();
-
-            // Calculate normal impulse
-            for i in 0..points {
-                let point = result.contacts[i].clone();
-
-                // Relative contact points
-                let r_1 = point - b1.center();
-                let r_2 = point - b2.center();
-                let r_1p = v2!(-1.0 * r_1.y, r_1.x);
-                let r_2p = v2!(-1.0 * r_2.y, r_2.x);
-
-                // Pre-resolution setup
-                let rel_v = (b2.velocity + r_2p * b2.angular_velocity) - (b1.velocity + r_1p * b1.angular_velocity);
-                let v_n = Vector2::dot(rel_v, n);
-
-                // Skip resolution if bodies moving apart
-                // if v_n > 0.0 {
-                //     continue;
-                // }
-
-                // Calculate impulse required to resolve collision
-                let r_1pn = Vector2::dot(r_1p, n);
-                let r_2pn = Vector2::dot(r_2p, n);
-
-                let j = (v_n * -(e + 1.0)) /
-                    (b1.inv_mass() + b2.inv_mass()     +
-                    (r_1pn * r_1pn) * b1.inv_inertia() +
-                    (r_2pn * r_2pn) * b2.inv_inertia()
-                    ) / (points as f64);
-                let impulse = n * j;
-
-                // Store calculation results
-                r_1s.push(r_1);
-                r_2s.push(r_2);
-
-                js.push(j);
-                impulses.push(impulse);
-            }
-
-            // Apply normal impulse
-            for i in 0..points {
-                let r_1 = r_1s[i];
-                let r_2 = r_2s[i];
-                let impulse = impulses[i];
-
-                // Apply linear impulse
-                b1.velocity = b1.velocity - impulse * b1.inv_mass();
-                b2.velocity = b2.velocity + impulse * b2.inv_mass();
-                // Apply angular impulse
-                b1.angular_velocity = b1.angular_velocity - Vector2::cross(r_1, impulse) * b1.inv_inertia();
-                b2.angular_velocity = b2.angular_velocity + Vector2::cross(r_2, impulse) * b2.inv_inertia();
-            }
-
-            // Calculate frictional impulse
-            for i in 0..points {
-                let r_1 = r_1s[i];
-                let r_2 = r_2s[i];
-                let r_1p = v2!(-1.0 * r_1.y, r_1.x);
-                let r_2p = v2!(-1.0 * r_2.y, r_2.x);
-
-                let rel_v = (b2.velocity + r_2p * b2.angular_velocity) - (b1.velocity + r_1p * b1.angular_velocity);
-
-                // Tangent vector
-                let mut t = (rel_v - n * Vector2::dot(rel_v, n)).norm(); // Calculate tangent vector
-
-                // Do not resolve friction if tangent impulse negligible
-                // if Vector2::<f64>::almost_eq(t, v2!(0f64)) {
-                //     continue;
-                // } else {
-                //     t = t.norm();
-                // }
-
-                let v_t = Vector2::dot(rel_v, t);
-
-                // Find friction
-                let r_1pt = Vector2::dot(r_1p, t);
-                let r_2pt = Vector2::dot(r_2p, t);
-
-                let f_j = -v_t /
-                    (b1.inv_mass() + b2.inv_mass()     +
-                    (r_1pt * r_1pt) * b1.inv_inertia() +
-                    (r_2pt * r_2pt) * b2.inv_inertia()
-                    ) / (points as f64);
-
-                // If the frictional force below mew*R, apply it
-                let f_impulse;
-                let j = js[i];
-
-                if f_j.abs() <= ks * j {
-                    f_impulse = t * f_j;
-                } else {
-                    f_impulse = t * -j * kd;
-                }
-
-                f_impulses.push(f_impulse);
-            }
-
-            // Apply tangent impulse
-            for i in 0..points {
-                let r_1 = r_1s[i];
-                let r_2 = r_2s[i];
-                let f_impulse = f_impulses[i];
-
-                // Apply linear impulse
-                b1.velocity = b1.velocity - f_impulse * b1.inv_mass();
-                b2.velocity = b2.velocity + f_impulse * b2.inv_mass();
-                // Apply angular impulse
-                b1.angular_velocity = b1.angular_velocity - Vector2::cross(r_1, f_impulse) * b1.inv_inertia();
-                b2.angular_velocity = b2.angular_velocity + Vector2::cross(r_2, f_impulse) * b2.inv_inertia();
-            }
+            let correction = n * result.overlap / w;
+            rope.points[i] = rope.points[i] + correction * w_point;
+            body.apply_correction(correction * -1.0, r);
+
+            // Inelastic normal impulse; the point's velocity is implicit, so its previous position is adjusted instead
+            let rel_v = rope.velocity(i, dt) - body.point_velocity(r);
+            let v_n = Vector2::dot(rel_v, n);
+            if v_n >= 0.0 { continue; }
+
+            let impulse = n * (-v_n / w);
+            rope.prev_points[i] = rope.prev_points[i] - impulse * (w_point * dt);
+            body.apply_impulse(impulse * -1.0, r);
         }
     }
-}
\ No newline at end of file
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::app::engine::Engine;
+    use crate::app::objects::{Body, BodyView};
+    use crate::app::ssm::SystemStateManager;
+    use crate::common::{BodyForm, Materials, Shared, Vector2};
+    use crate::{rect, v2};
+
+    #[test]
+    fn resting_box_stays_put() {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        let mut engine = Engine::new(shared);
+
+        // A 1 m box sitting on a floor, in metres
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(0.0, 10.0), 20.0, 1.0).set_frozen(true));
+        let crate_ = ssm.add_body(rect!(v2!(9.5, 9.0), 1.0, 1.0));
+        let start = ssm.bodies().body(crate_).position;
+
+        // Every step, the box neither sinks, bounces nor tips; at most one substep of gravity is left in its velocity
+        for _ in 0..300 {
+            engine.step(&mut ssm, 1.0 / 60.0);
+
+            let body = ssm.bodies().body(crate_);
+            assert!((body.position - start).mag() < 0.001, "drifted to {:?}", body.position);
+            assert!(body.velocity.mag() < 9.81 / 600.0, "moving at {:?}", body.velocity);
+            assert!(body.rotation.abs() < 1e-6 && body.angular_velocity.abs() < 1e-6);
+        }
+    }
+}
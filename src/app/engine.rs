@@ -4,11 +4,21 @@
     Description:
     * Handles the computational step of the simulation
     * Tracks all global parameters
+    * Bodies live in a plain arena, so the narrow phase & contact islands can be worked on by scoped threads;
+      joints, soft bodies, ropes & particles are solved on the calling thread. Results don't depend on the thread count
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::BodyHandle;
 use crate::app::collision::{CollisionDetector, CollisionResolver};
-use crate::common::{TBodyRef, TSharedRef, Vector2, Vector2M};
+use crate::app::integrator::{Integrator, TForceField};
+use crate::app::objects::{Body, BodyView};
+use crate::app::parallel;
+use crate::app::ssm::SystemStateManager;
+use crate::app::xpbd::XpbdSolver;
+use crate::common::{AABB, Crd, TDespawnListener, TJointListener, TJointRef, TSharedRef, Vector2, Vector2M, Real};
 use crate::v2;
 
 /* -------------------- VARIABLES ------------------- */
@@ -16,12 +26,58 @@ const ITERATIONS: u32 = 10;
 
 
 /* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Solver {
+    /// Sequential impulses; velocities are corrected by the CollisionResolver
+    Impulse,
+    /// Extended Position Based Dynamics; positions are corrected by the XpbdSolver
+    XPBD,
+}
+
+/// Region bodies are kept in; bodies which leave it entirely are despawned
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WorldBounds {
+    pub min: Vector2<Crd>,
+    pub max: Vector2<Crd>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum DespawnCause {
+    /// The body's lifetime ran out
+    Lifetime,
+    /// The body left the world bounds
+    OutOfBounds,
+}
+
+/// A body which the engine removed from the system during a step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Despawn {
+    pub handle: BodyHandle, // Now stale
+    pub body: Body,         // State at removal
+    pub cause: DespawnCause,
+}
 
 pub struct Engine {
     shared: TSharedRef,
-    gravity: Vector2M<f64>,
+    gravity: Vector2M<Real>,
+    integrator: Integrator,
+    solver: Solver,
+    force_fields: Vec<TForceField>,
     detector: CollisionDetector,
     resolver: CollisionResolver,
+    xpbd: XpbdSolver,
+    world_bounds: Option<WorldBounds>,
+    threads: usize, // Worker threads for the narrow phase & contact islands
+
+    // Clock
+    time: Real,  // Simulated seconds
+    steps: u64, // Steps taken
+
+    // Events
+    broken_joints: Vec<TJointRef>, // Joints which broke during the last step
+    break_listeners: Vec<TJointListener>,
+    despawned: Vec<Despawn>, // Bodies which despawned during the last step
+    despawn_listeners: Vec<TDespawnListener>,
 }
 
 /* -------------------- FUNCTIONS ------------------- */
@@ -29,44 +85,374 @@ impl Engine {
     pub fn new(shared: TSharedRef) -> Self {
         Engine {
             shared: shared.clone(),
-            gravity: v2!(0f64, 1f64, 9.81),
+            gravity: v2!(0.0, 1.0, 9.81),
+            integrator: Integrator::SymplecticEuler,
+            solver: Solver::Impulse,
+            force_fields: Vec::new(),
             detector: CollisionDetector::new(shared.clone()),
             resolver: CollisionResolver::new(shared.clone()),
+            xpbd: XpbdSolver::new(),
+            world_bounds: None,
+            threads: parallel::available_threads(),
+
+            // Clock
+            time: 0.0,
+            steps: 0,
+
+            // Events
+            broken_joints: Vec::new(),
+            break_listeners: Vec::new(),
+            despawned: Vec::new(),
+            despawn_listeners: Vec::new(),
         }
     }
 
-    pub fn step(&mut self, bodies: &Vec<TBodyRef>, dt: f64) {
-        let dt = dt / (ITERATIONS as f64);
+    pub fn step(&mut self, ssm: &mut SystemStateManager, dt: Real) {
+        let dt = dt / (ITERATIONS as Real);
+        let soft_bodies = ssm.soft_bodies().clone();
+        let ropes = ssm.ropes().clone();
+        let characters = ssm.characters().clone();
+        let particles_ref = ssm.particles().clone();
+
+        // Disabled bodies & joints sit the step out
+        let joints: &Vec<TJointRef> = &ssm.joints().iter().filter(|j| j.borrow().is_active(ssm.bodies())).cloned().collect();
+        let intact: Vec<TJointRef> = joints.iter().filter(|j| !j.borrow().broken).cloned().collect();
+        let bodies = ssm.bodies_mut();
+
+        // Kinematic characters move once per step, before the simulation
+        for character_ref in &characters {
+            let mut character = character_ref.borrow_mut();
+            if !bodies[character.body].enabled { continue; }
+
+            character.update(dt * ITERATIONS as Real, bodies, self.gravity_step(dt * ITERATIONS as Real));
+        }
 
         for _ in 0..ITERATIONS {
-            // Resolve gravity
-            for body_ref in bodies {
-                if body_ref.borrow().frozen { continue; }
-                self.resolve_gravity(body_ref, dt);
+            // Update body position/rotation; gravity is integrated with the other forces
+            bodies.integrate(dt, self.integrator, self.gravity_acceleration(), &self.force_fields);
+            bodies.refresh_geometry();
+            for soft_ref in &soft_bodies {
+                soft_ref.borrow_mut().update(dt, self.gravity_step(dt));
+            }
+
+            match self.solver {
+                Solver::Impulse => {
+                    // Resolve constraints
+                    for joint_ref in joints {
+                        joint_ref.borrow_mut().solve_velocity(bodies, dt);
+                    }
+
+                    let result = self.detector.evaluate(bodies);
+                    self.resolver.resolve(result, bodies);
+                }
+                Solver::XPBD => {
+                    let result = self.detector.evaluate(bodies);
+                    self.xpbd.solve_positions(result, joints, bodies, dt);
+
+                    // Derive velocities from the positional change over the substep
+                    bodies.derive_velocities(dt);
+
+                    self.xpbd.solve_velocities(joints, bodies, dt);
+                }
             }
 
-            // Update body position/rotation
-            for body_ref in bodies {
-                let mut body = body_ref.borrow_mut();
-                body.update(dt);
+            let soft_result = self.detector.evaluate_soft(&soft_bodies, bodies);
+            self.resolver.resolve_soft(soft_result, bodies);
+
+            // Verlet ropes
+            for rope_ref in &ropes {
+                let mut rope = rope_ref.borrow_mut();
+                rope.update(dt, self.gravity_step(dt), bodies);
+
+                let rope_result = self.detector.evaluate_rope(&rope, bodies);
+                self.resolver.resolve_rope(&mut rope, rope_result, bodies, dt);
             }
 
-            // TODO: Resolve constraints
+            // Particles
+            let mut particles = particles_ref.borrow_mut();
+            particles.update(dt, self.gravity_step(dt));
+            particles.collide_self();
 
-            let result = self.detector.evaluate(bodies);
-            self.resolver.resolve(result);
+            let particle_result = self.detector.evaluate_particles(&particles, bodies);
+            self.resolver.resolve_particles(&mut particles, particle_result, bodies);
         }
-    }
+        // Bodies moved by the last substep's solve; for drawing & queries between steps
+        bodies.refresh_geometry();
+
+        // Count down lifetimes & check the world bounds
+        let mut despawns: Vec<(BodyHandle, DespawnCause)> = Vec::new();
+        for (handle, mut body) in bodies.iter_mut() {
+            if !body.enabled { continue; }
 
-    fn resolve_gravity(&self, body: &TBodyRef, dt: f64) {
-        let mut body = body.borrow_mut();
+            if let Some(lifetime) = body.lifetime.as_mut() {
+                *lifetime -= dt * ITERATIONS as Real;
+                if *lifetime <= 0.0 {
+                    despawns.push((handle, DespawnCause::Lifetime));
+                    continue;
+                }
+            }
+            if self.world_bounds.is_some_and(|bounds| !bounds.overlaps(&body)) {
+                despawns.push((handle, DespawnCause::OutOfBounds));
+            }
+        }
+
+        self.time += dt * ITERATIONS as Real;
+        self.steps += 1;
 
-        if body.frozen {
-            return;
+        // Report joints which broke during this step
+        self.broken_joints = intact.into_iter().filter(|j| j.borrow().broken).collect();
+        for joint_ref in &self.broken_joints {
+            for listener in self.break_listeners.iter_mut() {
+                listener(joint_ref);
+            }
         }
 
-        let gravity = self.gravity.to_vec2() * self.gravity.m / (ITERATIONS as f64);
-        body.velocity = body.velocity + gravity;
+        // Despawn, then report, the bodies whose lifetime ran out or which left the world
+        self.despawned = Vec::new();
+        for (handle, cause) in despawns {
+            let Some(body) = ssm.remove_body(handle) else { continue; };
+            self.forget_body(handle);
+            self.despawned.push(Despawn { handle, body, cause });
+        }
+        for despawn in &self.despawned {
+            for listener in self.despawn_listeners.iter_mut() {
+                listener(despawn);
+            }
+        }
+    }
+
+    /// Drop a body removed from the system from the contacts & broad phase
+    pub fn forget_body(&mut self, handle: BodyHandle) {
+        self.detector.forget(handle);
+        self.xpbd.forget(handle);
+    }
+
+    /// Acceleration due to gravity, in m/s²
+    fn gravity_acceleration(&self) -> Vector2<Real> {
+        self.gravity.to_vec2() * self.gravity.m
+    }
+    /// Velocity change due to gravity over dt
+    fn gravity_step(&self, dt: Real) -> Vector2<Real> {
+        self.gravity_acceleration() * dt
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn broken_joints(&self) -> &Vec<TJointRef> {
+        &self.broken_joints
+    }
+    pub fn despawned(&self) -> &Vec<Despawn> {
+        &self.despawned
+    }
+    pub fn time(&self) -> Real {
+        self.time
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+    pub fn gravity(&self) -> Vector2M<Real> {
+        self.gravity
+    }
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+    pub fn solver(&self) -> Solver {
+        self.solver
+    }
+    /// Inverse stiffness of contacts under the XPBD solver
+    pub fn contact_compliance(&self) -> Real {
+        self.xpbd.compliance()
+    }
+    pub fn world_bounds(&self) -> Option<WorldBounds> {
+        self.world_bounds
+    }
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_clock(&mut self, time: Real, steps: u64) {
+        self.time = time;
+        self.steps = steps;
+    }
+    /// Sets what the last step reported; used to restore a snapshot. Listeners aren't called
+    pub fn set_events(&mut self, broken_joints: Vec<TJointRef>, despawned: Vec<Despawn>) {
+        self.broken_joints = broken_joints;
+        self.despawned = despawned;
+    }
+    pub fn set_gravity(&mut self, gravity: Vector2M<Real>) {
+        self.gravity = gravity;
+    }
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+    pub fn set_solver(&mut self, solver: Solver) {
+        self.solver = solver;
+    }
+    /// Sets the inverse stiffness of contacts under the XPBD solver; 0 keeps them perfectly rigid
+    pub fn set_contact_compliance(&mut self, compliance: Real) {
+        self.xpbd.set_compliance(compliance);
+    }
+    /// Bodies which leave the given region entirely are despawned; None keeps every body
+    pub fn set_world_bounds(&mut self, bounds: Option<WorldBounds>) {
+        self.world_bounds = bounds;
+    }
+    /// Sets the number of worker threads; 1 runs everything on the calling thread
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        self.detector.set_threads(self.threads);
+        self.resolver.set_threads(self.threads);
+        self.xpbd.set_threads(self.threads);
+    }
+    pub fn add_force_field(&mut self, field: TForceField) {
+        self.force_fields.push(field);
+    }
+    pub fn add_break_listener(&mut self, listener: TJointListener) {
+        self.break_listeners.push(listener);
+    }
+    pub fn add_despawn_listener(&mut self, listener: TDespawnListener) {
+        self.despawn_listeners.push(listener);
     }
 }
 
+impl WorldBounds {
+    /// Whether the body's bounding box overlaps the region
+    pub fn overlaps(&self, body: &impl BodyView) -> bool {
+        body.geometry().aabb.overlaps(&AABB::new(self.min, self.max))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::joints::{Joint, JointKind};
+    use crate::app::replay::state_hash;
+    use crate::common::{BodyForm, Materials, Shared};
+    use crate::{poly, rect};
+
+    fn new_engine() -> (Engine, TSharedRef) {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        (Engine::new(shared.clone()), shared)
+    }
+
+    /// Hash of a few separate piles of bodies falling onto a floor, after the given number of steps
+    fn piles(solver: Solver, threads: usize, steps: u32) -> u64 {
+        let (mut engine, _) = new_engine();
+        engine.set_solver(solver);
+        engine.set_threads(threads);
+
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(0.0, 11.6), 20.0, 0.4).set_frozen(true));
+        for pile in 0..4 {
+            for row in 0..6 {
+                for column in 0..6 {
+                    let position = v2!(1.0 + pile as Real * 5.0 + column as Real * 0.32 + (row % 2) as Real * 0.08, 10.8 - row as Real * 0.34);
+                    ssm.add_body(if (row + column) % 3 == 0 { poly!(position, 0.14, 5) } else { rect!(position, 0.28, 0.28) });
+                }
+            }
+        }
+
+        for _ in 0..steps {
+            engine.step(&mut ssm, 1.0 / 60.0);
+        }
+        state_hash(&ssm, &engine)
+    }
+
+    #[test]
+    fn threads_dont_change_the_outcome() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            let hash = piles(solver, 1, 40);
+            for threads in [2, 8] {
+                assert_eq!(piles(solver, threads, 40), hash, "{solver:?} on {threads} threads");
+            }
+        }
+    }
+
+    #[test]
+    fn expired_bodies_despawn_once() {
+        let (mut engine, _) = new_engine();
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let listened = reported.clone();
+        engine.add_despawn_listener(Box::new(move |despawn| listened.borrow_mut().push(despawn.handle)));
+
+        // Just under half a second to live; gone on the 30th step, & reported on that one alone
+        let mut ssm = SystemStateManager::new();
+        let spark = ssm.add_body(rect!(v2!(5.0, 5.0), 0.1, 0.1).set_lifetime(0.49));
+        let kept = ssm.add_body(rect!(v2!(8.0, 5.0), 0.1, 0.1));
+        for step in 1..=40 {
+            engine.step(&mut ssm, 1.0 / 60.0);
+            let despawned: Vec<(BodyHandle, DespawnCause)> = engine.despawned().iter().map(|d| (d.handle, d.cause)).collect();
+            if step == 30 {
+                assert_eq!(despawned, vec![(spark, DespawnCause::Lifetime)]);
+            } else {
+                assert!(despawned.is_empty(), "despawned {despawned:?} on step {step}");
+            }
+            assert_eq!(ssm.bodies().contains(spark), step < 30);
+        }
+        assert_eq!(*reported.borrow(), vec![spark]);
+        assert!(ssm.bodies().contains(kept));
+    }
+
+    #[test]
+    fn bodies_leaving_the_world_despawn() {
+        let (mut engine, _) = new_engine();
+        engine.set_world_bounds(Some(WorldBounds { min: v2!(0.0, 0.0), max: v2!(20.0, 12.0) }));
+
+        // A box dropped from 2 m above the bottom of the world leaves it entirely after about 0.7 s
+        let mut ssm = SystemStateManager::new();
+        let dropped = ssm.add_body(rect!(v2!(5.0, 10.0), 0.5, 0.5));
+        let mut despawn = None;
+        for _ in 0..60 {
+            engine.step(&mut ssm, 1.0 / 60.0);
+            if let Some(d) = engine.despawned().first() { despawn = Some(d.clone()); break; }
+        }
+
+        let despawn = despawn.expect("the box never left the world");
+        assert_eq!((despawn.handle, despawn.cause), (dropped, DespawnCause::OutOfBounds));
+        assert!(despawn.body.position.y > 12.0 && despawn.body.velocity.y > 0.0);
+        assert!(!ssm.bodies().contains(dropped));
+    }
+
+    #[test]
+    fn removed_bodies_take_their_joints_and_contacts() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            let (mut engine, shared) = new_engine();
+            engine.set_solver(solver);
+
+            // A box resting on a floor, tied to another
+            let mut ssm = SystemStateManager::new();
+            let floor = ssm.add_body(rect!(v2!(0.0, 11.0), 20.0, 1.0).set_frozen(true));
+            let (resting, tied) = (ssm.add_body(rect!(v2!(5.0, 10.0), 1.0, 1.0)), ssm.add_body(rect!(v2!(8.0, 10.0), 1.0, 1.0)));
+            let rope = Joint::new(JointKind::Distance, ssm.bodies(), resting, tied, v2!(5.5, 10.5), v2!(8.5, 10.5));
+            ssm.add_joint(rope);
+            for _ in 0..10 {
+                engine.step(&mut ssm, 1.0 / 60.0);
+            }
+            assert!(shared.borrow().narrow_phase_pairs.iter().any(|pair| pair.bodies.contains(&resting)));
+
+            ssm.remove_body(resting).unwrap();
+            engine.forget_body(resting);
+            assert!(ssm.joints().is_empty());
+            assert!(!shared.borrow().broad_phase_pairs.iter().any(|pair| pair.contains(&resting)));
+            assert!(!shared.borrow().narrow_phase_pairs.iter().any(|pair| pair.bodies.contains(&resting)));
+
+            // A body put in the freed slot is met afresh
+            let replaced = ssm.add_body(rect!(v2!(12.0, 10.0), 1.0, 1.0));
+            assert_eq!(replaced.index(), resting.index());
+            for _ in 0..10 {
+                engine.step(&mut ssm, 1.0 / 60.0);
+            }
+            for handle in [floor, tied, replaced] {
+                assert!(ssm.bodies().body(handle).velocity.mag() < 0.1, "{solver:?} {handle:?} disturbed");
+            }
+        }
+    }
+}
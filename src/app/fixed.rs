@@ -0,0 +1,246 @@
+/*
+    fixed.rs
+    ----------------------------------------
+    Description:
+    * Provides Fixed, a Q32.32 fixed-point number; built with the `fixed` feature
+    * All arithmetic is done on integers, so results are bit-identical across builds, optimisation levels & machines
+    * Sine & cosine are read from a table generated at compile time (also in integers), & interpolated
+    * Overflow saturates rather than wrapping or panicking, so debug & release builds behave the same
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Number, Real, Vector2};
+
+/* -------------------- VARIABLES ------------------- */
+const FRAC_BITS: u32 = 32;
+const SINE_STEPS: usize = 1024; // Table entries per quarter turn
+static SINE: [i64; SINE_STEPS + 1] = sine_table();
+
+/* ------------------- STRUCTURES ------------------- */
+/// Q32.32 fixed-point number; 32 integer bits (including sign) & 32 fractional bits
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Fixed(i64);
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+    pub const HALF: Fixed = Fixed(1 << (FRAC_BITS - 1));
+    pub const EPSILON: Fixed = Fixed(1);
+    pub const MAX: Fixed = Fixed(i64::MAX);
+    pub const MIN: Fixed = Fixed(i64::MIN);
+    pub const PI: Fixed = Fixed(13_493_037_705);
+    pub const FRAC_PI_2: Fixed = Fixed(6_746_518_852);
+    pub const TAU: Fixed = Fixed(26_986_075_409);
+
+    pub const fn from_raw(raw: i64) -> Fixed {
+        Fixed(raw)
+    }
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+    pub const fn from_int(n: i32) -> Fixed {
+        Fixed((n as i64) << FRAC_BITS)
+    }
+    /// The fraction num / den, rounded toward zero; for constants that must not pass through floats
+    pub const fn ratio(num: i32, den: i32) -> Fixed {
+        Fixed(((num as i64) << FRAC_BITS) / den as i64)
+    }
+    /// Nearest Fixed to x; deterministic for a given x, so fine for loading scenes, but not for use mid-step
+    pub fn from_real(x: Real) -> Fixed {
+        let scaled = (x * (1u64 << FRAC_BITS) as Real).round();
+        Fixed(scaled as i64) // `as` saturates, & maps NaN to 0
+    }
+    pub fn to_real(self) -> Real {
+        self.0 as Real / (1u64 << FRAC_BITS) as Real
+    }
+
+    pub fn abs(self) -> Fixed {
+        Fixed(self.0.saturating_abs())
+    }
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Square root, rounded down; zero for negative numbers
+    pub fn sqrt(self) -> Fixed {
+        if self.0 <= 0 { return Fixed::ZERO; }
+
+        // sqrt(raw / 2^32) * 2^32 = sqrt(raw * 2^32)
+        let n = (self.0 as u128) << FRAC_BITS;
+        let mut x = 1u128 << (n.ilog2() / 2 + 1); // Above the root; Newton's method then falls onto it
+        loop {
+            let y = (x + n / x) / 2;
+            if y >= x { break; }
+            x = y;
+        }
+
+        Fixed(x as i64)
+    }
+
+    /// Sine of an angle in radians, from the table
+    pub fn sin(self) -> Fixed {
+        let turn = Fixed::TAU.0 as i128;
+        let quarter = Fixed::FRAC_PI_2.0 as i128;
+        let a = (self.0 as i128).rem_euclid(turn);
+
+        // Position within the quarter turn, in table steps
+        let quadrant = (a / quarter).min(3);
+        let t = (a - quadrant * quarter) * SINE_STEPS as i128;
+        let (i, frac) = ((t / quarter) as usize, t % quarter);
+
+        // Read the quarter wave forwards or backwards, & flip it for the second half turn
+        let lookup = |i: usize| if quadrant % 2 == 0 { SINE[i.min(SINE_STEPS)] } else { SINE[SINE_STEPS - i.min(SINE_STEPS)] } as i128;
+        let (s0, s1) = (lookup(i), lookup(i + 1));
+        let s = s0 + (s1 - s0) * frac / quarter;
+
+        Fixed(if quadrant >= 2 { -s } else { s } as i64)
+    }
+    pub fn cos(self) -> Fixed {
+        (self + Fixed::FRAC_PI_2).sin()
+    }
+}
+
+/// Sine over a quarter turn, by Taylor series in Q32.32
+const fn sine_table() -> [i64; SINE_STEPS + 1] {
+    let mut table = [0i64; SINE_STEPS + 1];
+    let mut i = 0;
+    while i <= SINE_STEPS {
+        let x = Fixed::FRAC_PI_2.0 as i128 * i as i128 / SINE_STEPS as i128;
+        let (mut term, mut sum, mut k) = (x, x, 1);
+        while k <= 8 {
+            term = -((((term * x) >> FRAC_BITS) * x) >> FRAC_BITS) / ((2 * k) * (2 * k + 1));
+            sum += term;
+            k += 1;
+        }
+        table[i] = sum as i64;
+        i += 1;
+    }
+    table[SINE_STEPS] = 1 << FRAC_BITS;
+
+    table
+}
+
+impl Vector2<Fixed> {
+    pub fn from_real(v: Vector2<Real>) -> Vector2<Fixed> {
+        Vector2 { x: Fixed::from_real(v.x), y: Fixed::from_real(v.y) }
+    }
+    pub fn to_real(self) -> Vector2<Real> {
+        Vector2 { x: self.x.to_real(), y: self.y.to_real() }
+    }
+    /// Rotated a quarter turn; (-y, x)
+    pub fn perp(self) -> Vector2<Fixed> {
+        Vector2 { x: -self.y, y: self.x }
+    }
+    pub fn length(self) -> Fixed {
+        dot(self, self).sqrt()
+    }
+    /// Unit vector in the same direction; zero for the zero vector
+    pub fn unit(self) -> Vector2<Fixed> {
+        let n = self.length();
+        if n.is_zero() { return Vector2 { x: Fixed::ZERO, y: Fixed::ZERO }; }
+
+        Vector2 { x: self.x / n, y: self.y / n }
+    }
+    /// Rotated by an angle in radians
+    pub fn rotate(self, angle: Fixed) -> Vector2<Fixed> {
+        let (sin, cos) = (angle.sin(), angle.cos());
+        Vector2 { x: self.x * cos - self.y * sin, y: self.x * sin + self.y * cos }
+    }
+}
+
+impl Number for Fixed {
+    const ZERO: Fixed = Fixed::ZERO;
+    const ONE: Fixed = Fixed::ONE;
+    const HALF: Fixed = Fixed::HALF;
+    const MAX: Fixed = Fixed::MAX;
+    const MIN: Fixed = Fixed::MIN;
+    const LENGTH_TOLERANCE: Fixed = Fixed::ratio(2, 10_000);
+    const DIST_SQ_TOLERANCE: Fixed = Fixed::ratio(4, 1_000_000);
+
+    fn ratio(num: i32, den: i32) -> Fixed {
+        Fixed::ratio(num, den)
+    }
+    fn abs(self) -> Fixed {
+        Fixed::abs(self)
+    }
+    fn sqrt(self) -> Fixed {
+        Fixed::sqrt(self)
+    }
+}
+
+// Free functions, as inherent ones on Vector2<Fixed> would clash with Vector2<T>'s in `Vector2::dot(..)` calls
+pub fn dot(v1: Vector2<Fixed>, v2: Vector2<Fixed>) -> Fixed {
+    v1.x * v2.x + v1.y * v2.y
+}
+pub fn cross(v1: Vector2<Fixed>, v2: Vector2<Fixed>) -> Fixed {
+    v1.x * v2.y - v1.y * v2.x
+}
+
+/* --------------------- TRAITS --------------------- */
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, rhs: Fixed) -> Fixed {
+        saturate((self.0 as i128 * rhs.0 as i128) >> FRAC_BITS)
+    }
+}
+impl Div for Fixed {
+    type Output = Fixed;
+    /// Rounds toward zero; dividing by zero saturates toward the sign of the numerator
+    fn div(self, rhs: Fixed) -> Fixed {
+        if rhs.0 == 0 {
+            return match self.0.signum() { 1 => Fixed::MAX, -1 => Fixed::MIN, _ => Fixed::ZERO };
+        }
+        saturate(((self.0 as i128) << FRAC_BITS) / rhs.0 as i128)
+    }
+}
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) { *self = *self + rhs; }
+}
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) { *self = *self - rhs; }
+}
+impl MulAssign for Fixed {
+    fn mul_assign(&mut self, rhs: Fixed) { *self = *self * rhs; }
+}
+impl DivAssign for Fixed {
+    fn div_assign(&mut self, rhs: Fixed) { *self = *self / rhs; }
+}
+
+impl fmt::Debug for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_real())
+    }
+}
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_real(), f)
+    }
+}
+
+fn saturate(raw: i128) -> Fixed {
+    Fixed(raw.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+}
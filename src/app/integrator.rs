@@ -0,0 +1,143 @@
+/*
+    integrator.rs
+    ----------------------------------------
+    Description:
+    * Provides the numerical integrators used to advance bodies through time
+    * Integrators act on the linear state (position, velocity) given an acceleration function a(x, v); gravity is part
+      of it, so higher order schemes integrate it too
+    * RK4 re-evaluates the acceleration at intermediate states, so it is only meaningful for force-only
+      (contact-free) scenarios; impulses from the resolver are applied between steps
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Vector2, Real};
+
+/* -------------------- VARIABLES ------------------- */
+/// Acceleration field evaluated at a (position, velocity) state; position is the body's center of mass.
+pub type TForceField = Box<dyn Fn(Vector2<Real>, Vector2<Real>) -> Vector2<Real>>;
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Semi-implicit (symplectic) Euler; velocity first, then position. First order, bounded energy error.
+    SymplecticEuler,
+    /// Velocity Verlet; second order and symplectic for position-dependent forces.
+    VelocityVerlet,
+    /// Classical 4th order Runge-Kutta; very accurate for smooth forces, but not symplectic.
+    RK4,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [Integrator::SymplecticEuler, Integrator::VelocityVerlet, Integrator::RK4];
+
+    /// Advance a (position, velocity) pair by dt, returning the new pair.
+    #[inline(always)] // Called per body, per substep; inlined, the match is taken out of the loop
+    pub fn step<F>(&self, x: Vector2<Real>, v: Vector2<Real>, dt: Real, a: F) -> (Vector2<Real>, Vector2<Real>)
+        where F: Fn(Vector2<Real>, Vector2<Real>) -> Vector2<Real>
+    {
+        match self {
+            Integrator::SymplecticEuler => {
+                let v_1 = v + a(x, v) * dt;
+                (x + v_1 * dt, v_1)
+            }
+            Integrator::VelocityVerlet => {
+                let a_0 = a(x, v);
+                let x_1 = x + v * dt + a_0 * (0.5 * dt * dt);
+                // Velocity-dependent forces are evaluated at the Euler-predicted velocity
+                let a_1 = a(x_1, v + a_0 * dt);
+
+                (x_1, v + (a_0 + a_1) * (0.5 * dt))
+            }
+            Integrator::RK4 => {
+                let (k1_x, k1_v) = (v, a(x, v));
+                let (k2_x, k2_v) = (v + k1_v * (0.5 * dt), a(x + k1_x * (0.5 * dt), v + k1_v * (0.5 * dt)));
+                let (k3_x, k3_v) = (v + k2_v * (0.5 * dt), a(x + k2_x * (0.5 * dt), v + k2_v * (0.5 * dt)));
+                let (k4_x, k4_v) = (v + k3_v * dt, a(x + k3_x * dt, v + k3_v * dt));
+
+                (
+                    x + (k1_x + k2_x * 2.0 + k3_x * 2.0 + k4_x) * (dt / 6.0),
+                    v + (k1_v + k2_v * 2.0 + k3_v * 2.0 + k4_v) * (dt / 6.0),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::arena::BodyArena;
+    use crate::app::objects::Body;
+    use crate::common::{BodyForm, Materials};
+    use crate::{rect, v2};
+
+    /// Relative energy drift |E_n - E_0| / |E_0| of a unit point mass, for each integrator in the order of
+    /// `Integrator::ALL`, after `steps` steps of dt. `potential` is the potential energy per unit mass matching the
+    /// acceleration field `a`.
+    fn drifts<F, P>(x: Vector2<Real>, v: Vector2<Real>, dt: Real, steps: u32, a: F, potential: P) -> [Real; 3]
+        where F: Fn(Vector2<Real>, Vector2<Real>) -> Vector2<Real>, P: Fn(Vector2<Real>) -> Real
+    {
+        let energy = |x: Vector2<Real>, v: Vector2<Real>| 0.5 * Vector2::dot(v, v) + potential(x);
+        let e_0 = energy(x, v);
+
+        Integrator::ALL.map(|integrator| {
+            let (mut x, mut v) = (x, v);
+            for _ in 0..steps {
+                (x, v) = integrator.step(x, v, dt, &a);
+            }
+            (energy(x, v) - e_0).abs() / e_0.abs().max(Real::EPSILON)
+        })
+    }
+
+    #[test]
+    fn orbit_drift() {
+        // Circular orbit of radius 1 about a unit mass at the origin; a period is 2π
+        let gravity = |x: Vector2<Real>, _: Vector2<Real>| x * (-1.0 / x.mag().powi(3));
+        let potential = |x: Vector2<Real>| -1.0 / x.mag();
+        let [euler, verlet, rk4] = drifts(v2!(1.0, 0.0), v2!(0.0, 1.0), 0.1, 1_000, gravity, potential);
+        let [euler_long, verlet_long, rk4_long] = drifts(v2!(1.0, 0.0), v2!(0.0, 1.0), 0.1, 10_000, gravity, potential);
+
+        // Verlet is second order; both symplectic schemes keep their error bounded, while RK4's keeps growing
+        assert!(verlet < euler / 100.0 && verlet_long < euler_long / 100.0);
+        assert!(euler_long < 0.05 && verlet_long < 1e-5);
+        assert!(rk4_long > rk4 * 5.0);
+    }
+
+    #[test]
+    fn spring_drift() {
+        let spring = |x: Vector2<Real>, _: Vector2<Real>| x * -1.0;
+        let potential = |x: Vector2<Real>| 0.5 * Vector2::dot(x, x);
+        let [euler, verlet, rk4] = drifts(v2!(1.0, 0.0), v2!(0.0, 0.0), 0.2, 1_000, spring, potential);
+        let [euler_long, _, rk4_long] = drifts(v2!(1.0, 0.0), v2!(0.0, 0.0), 0.2, 10_000, spring, potential);
+
+        // Over a short run, higher order is more accurate; over a long one RK4 drifts, & symplectic Euler doesn't
+        assert!(rk4 < verlet && verlet < euler);
+        assert!(rk4_long > rk4 * 5.0);
+        assert!(euler_long < euler * 2.0);
+    }
+
+    #[test]
+    fn gravity_is_integrated() {
+        // Constant acceleration is integrated exactly by Verlet & RK4; Euler is off by g * dt * t / 2
+        let (g, dt, steps) = (9.81, 0.01, 100);
+        let t = dt * steps as Real;
+
+        for integrator in Integrator::ALL {
+            let mut arena = BodyArena::new();
+            let handle = arena.insert(rect!(v2!(0.0), 1.0, 1.0));
+            for _ in 0..steps {
+                arena.integrate(dt, integrator, v2!(0.0, g), &[]);
+            }
+
+            let fallen = arena.body(handle).position.y;
+            let expected = match integrator {
+                Integrator::SymplecticEuler => 0.5 * g * t * t + 0.5 * g * dt * t,
+                Integrator::VelocityVerlet | Integrator::RK4 => 0.5 * g * t * t,
+            };
+            assert!((fallen - expected).abs() < expected * 1e-4, "{integrator:?} fell {fallen}, expected {expected}");
+        }
+    }
+}
@@ -0,0 +1,964 @@
+/*
+    joint.rs
+    ----------------------------------------
+    Description:
+    * Provides struct for constraints which link two bodies together
+    * Joints can be solved at the velocity level (impulse pipeline) or at the position level (XPBD)
+    * Revolute & prismatic joints support motors & limits; distance & prismatic joints can act as damped springs
+    * Pulleys & gears couple two bodies, or two other joints, through a ratio
+    * Mouse joints pull a single body towards a target which can be moved at runtime, e.g. by dragging the mouse
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::common::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::objects::{BodyMut, BodyView};
+use crate::common::{Crd, Mat22, Rot, TJointRef, Vector2, LENGTH_TOLERANCE, Real};
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+const BAUMGARTE: Real = 0.2;         // Fraction of the positional error fed back into the velocity solve
+const RESTING_VELOCITY: Real = 0.04; // m/s; below this approach speed limits don't bounce, preventing jitter at rest
+const MOUSE_FREQUENCY: Real = 5.0;   // Default spring of mouse joints; soft enough for dragged bodies to swing
+const MOUSE_DAMPING: Real = 0.7;
+const MOUSE_ACCELERATION: Real = 200.0; // m/s²; caps the force of mouse joints, relative to the body's mass
+const MOUSE_SPIN_DAMPING: Real = 1.2;   // 1/s; dragged bodies would otherwise spin about the anchor for a long time
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum JointKind {
+    /// Keeps the anchors at a fixed distance from each other
+    Distance,
+    /// Pins the anchors together, leaving the bodies free to rotate about them
+    Revolute,
+    /// Lets the second anchor slide along an axis fixed to the first body; relative rotation is locked
+    Prismatic,
+    /// Hangs both anchors from fixed ground points; keeps `length_1 + ratio * length_2` constant
+    Pulley,
+    /// Ties the coordinates (angle, translation or length) of two other joints; keeps `c_1 + ratio * c_2` constant
+    Gear,
+    /// Pulls the anchor of a single body towards a movable target; both handles are the same body
+    Mouse,
+}
+
+/// Drives a revolute joint's relative rotation, or a prismatic joint's translation, at a target speed.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Motor {
+    pub speed: Real,     // Target speed; rad/s for revolute joints, m/s for prismatic joints
+    pub max_force: Real, // Maximum torque (revolute) or force (prismatic) the motor can exert
+}
+
+/// Bounds on a revolute joint's relative angle, or a prismatic joint's translation along its axis.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Limits {
+    pub lower: Real,
+    pub upper: Real,
+    pub restitution: Real, // Bounciness when a limit is hit
+}
+
+/// Internal struct for constraints between two bodies.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Joint {
+    pub kind: JointKind,
+    pub bodies: [BodyHandle; 2],
+    pub anchors: [Vector2<Crd>; 2], // Anchors in each body's local space
+    pub axis: Vector2<Real>,         // Prismatic slide axis in the first body's local space
+    pub reference_angle: Real,       // Relative rotation of the bodies at creation
+    pub base_rotation: Real,         // Rotation of the first body at creation; axes are given relative to it
+    pub length: Real,                // Rest distance between the anchors; rest translation for prismatic springs;
+                                    // the conserved total for pulleys & gears
+    pub compliance: Real,            // Inverse stiffness; 0 is perfectly rigid
+    pub break_force: Real,           // Constraint force past which the joint breaks
+    pub broken: bool,
+    pub enabled: bool,              // Whether the joint is solved; joints of disabled bodies aren't either
+    pub visible: bool,
+
+    // Extensions
+    pub motor: Option<Motor>,
+    pub limits: Option<Limits>,
+    pub frequency: Real,     // Spring frequency in Hz; 0 makes a rigid joint
+    pub damping_ratio: Real, // Spring damping; 1 is critically damped
+
+    // Coupling
+    pub ground: [Vector2<Crd>; 2],          // Fixed global points pulley ropes run over; a mouse joint's target
+    pub ratio: Real,                         // Pulley & gear ratio
+    #[serde(skip)]
+    pub coupled: Option<[TJointRef; 2]>,    // Joints linked by a gear; not serialized, being shared
+
+    // Solver
+    pub lambda: Real,       // XPBD Lagrange multipliers, accumulated over a substep
+    pub lambda_limit: Real,
+    pub lambda_motor: Real,
+    pub limit_v: Real,      // Relative speed along the limit before the position solve; used for restitution
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Joint {
+    /// Constructor for the Joint struct. Anchors are given in global space.
+    /// The axis of a prismatic joint runs from the first anchor to the second, or along x if they coincide.
+    pub fn new(
+        kind: JointKind, bodies: &BodyArena,
+        b1: BodyHandle, b2: BodyHandle,
+        anchor_1: Vector2<Crd>, anchor_2: Vector2<Crd>,
+    ) -> Self {
+        let anchors = [bodies.body(b1).local_point(anchor_1), bodies.body(b2).local_point(anchor_2)];
+        let length = match kind {
+            JointKind::Distance => (anchor_2 - anchor_1).mag(),
+            JointKind::Revolute | JointKind::Prismatic | JointKind::Pulley | JointKind::Gear | JointKind::Mouse => 0.0,
+        };
+
+        let d = anchor_2 - anchor_1;
+        let axis = if d.mag() < LENGTH_TOLERANCE { v2!(1.0, 0.0) } else { d.norm() };
+        let (r_1, r_2) = (bodies.body(b1).rotation, bodies.body(b2).rotation);
+
+        Joint {
+            kind,
+            bodies: [b1, b2],
+            anchors,
+            axis: Rot::new(-r_1).rotate(axis),
+            reference_angle: r_2 - r_1,
+            base_rotation: r_1,
+            length,
+            compliance: 0.0,
+            break_force: Real::INFINITY,
+            broken: false,
+            enabled: true,
+            visible: true,
+
+            // Extensions
+            motor: None,
+            limits: None,
+            frequency: 0.0,
+            damping_ratio: 0.0,
+
+            // Coupling
+            ground: [anchor_1, anchor_2],
+            ratio: 1.0,
+            coupled: None,
+
+            // Solver
+            lambda: 0.0,
+            lambda_limit: 0.0,
+            lambda_motor: 0.0,
+            limit_v: 0.0,
+        }
+    }
+
+    /// Pulley between two bodies; each anchor hangs from its ground point, given in global space.
+    /// The rope on the second side is `ratio` times as expensive, as with a block & tackle.
+    #[allow(clippy::too_many_arguments)]
+    pub fn pulley(
+        bodies: &BodyArena,
+        b1: BodyHandle, b2: BodyHandle,
+        anchor_1: Vector2<Crd>, anchor_2: Vector2<Crd>,
+        ground_1: Vector2<Crd>, ground_2: Vector2<Crd>,
+        ratio: Real,
+    ) -> Self {
+        let mut joint = Self::new(JointKind::Pulley, bodies, b1, b2, anchor_1, anchor_2);
+        joint.ground = [ground_1, ground_2];
+        joint.ratio = ratio;
+        joint.length = (anchor_1 - ground_1).mag() + ratio * (anchor_2 - ground_2).mag();
+        joint
+    }
+
+    /// Gear between two revolute, prismatic or distance joints. The gear acts on the bodies of both joints;
+    /// it is drawn between the second body of each.
+    pub fn gear(bodies: &BodyArena, j1: TJointRef, j2: TJointRef, ratio: Real) -> Self {
+        let b1 = j1.borrow().bodies[1];
+        let b2 = j2.borrow().bodies[1];
+        let (c_1, c_2) = (bodies.body(b1).center(), bodies.body(b2).center());
+
+        let mut joint = Self::new(JointKind::Gear, bodies, b1, b2, c_1, c_2);
+        joint.ratio = ratio;
+        joint.length = j1.borrow().coordinate(bodies).0 + ratio * j2.borrow().coordinate(bodies).0;
+        joint.coupled = Some([j1, j2]);
+        joint
+    }
+
+    /// Mouse joint grabbing the body at the given global point; the target starts there, and is moved with
+    /// `set_target`. The body is pulled by a damped spring.
+    pub fn mouse(bodies: &BodyArena, body: BodyHandle, point: Vector2<Crd>) -> Self {
+        Self::new(JointKind::Mouse, bodies, body, body, point, point).set_spring(MOUSE_FREQUENCY, MOUSE_DAMPING)
+    }
+
+    /// Velocity-level solve, used by the impulse pipeline. Positional drift is fed back through a Baumgarte term.
+    pub fn solve_velocity(&mut self, bodies: &mut BodyArena, dt: Real) {
+        if self.broken { return; }
+        match self.kind {
+            JointKind::Gear => return self.solve_gear(bodies, dt, false),
+            JointKind::Mouse => return self.solve_mouse_velocity(bodies, dt),
+            _ => {}
+        }
+
+        let Some((mut b1, mut b2)) = bodies.pair_mut(self.bodies[0], self.bodies[1]) else { return; };
+
+        let p_1 = b1.world_point(self.anchors[0]);
+        let p_2 = b2.world_point(self.anchors[1]);
+        let (r_1, r_2) = self.arms(&b1, &b2, p_1, p_2);
+
+        match self.kind {
+            JointKind::Distance => {
+                let d = p_2 - p_1;
+                if d.mag() < LENGTH_TOLERANCE { return; }
+
+                let n = d.norm();
+                let k = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+                let j = self.soft_impulse(&b1, &b2, (r_1, r_2), n, d.mag() - self.length, k, dt);
+
+                if self.breaks(j, dt) { return; }
+                b1.apply_impulse(n * -j, r_1);
+                b2.apply_impulse(n * j, r_2);
+            }
+            JointKind::Revolute => {
+                let k = b1.inv_inertia() + b2.inv_inertia();
+
+                if let (Some(motor), true) = (self.motor, k > 0.0) {
+                    let w = b2.angular_velocity - b1.angular_velocity;
+                    let j = ((motor.speed - w) / k).clamp(-motor.max_force * dt, motor.max_force * dt);
+                    b1.apply_angular_impulse(-j);
+                    b2.apply_angular_impulse(j);
+                }
+
+                if let (Some(limits), true) = (self.limits, k > 0.0) {
+                    let w = b2.angular_velocity - b1.angular_velocity;
+                    let j = limit_impulse(&limits, self.angle(&b1, &b2), w, k, dt);
+                    if self.breaks(j, dt) { return; }
+                    b1.apply_angular_impulse(-j);
+                    b2.apply_angular_impulse(j);
+                }
+
+                let rel_v = b2.point_velocity(r_2) - b1.point_velocity(r_1);
+                let (m, i_1, i_2) = (b1.inv_mass() + b2.inv_mass(), b1.inv_inertia(), b2.inv_inertia());
+                let softness = self.compliance / (dt * dt);
+
+                // Effective mass matrix of the point constraint
+                let k_11 = m + i_1 * r_1.y * r_1.y + i_2 * r_2.y * r_2.y + softness;
+                let k_12 = -i_1 * r_1.x * r_1.y - i_2 * r_2.x * r_2.y;
+                let k_22 = m + i_1 * r_1.x * r_1.x + i_2 * r_2.x * r_2.x + softness;
+                let k = Mat22::new(v2!(k_11, k_12), v2!(k_12, k_22));
+
+                let b = (rel_v + (p_2 - p_1) * (BAUMGARTE / dt)) * -1.0;
+                let Some(impulse) = k.solve(b) else { return; };
+                if self.breaks(impulse.mag(), dt) { return; }
+
+                b1.apply_impulse(impulse * -1.0, r_1);
+                b2.apply_impulse(impulse, r_2);
+            }
+            JointKind::Pulley => {
+                let Some((u_1, u_2, c)) = self.pulley_frame(p_1, p_2) else { return; };
+                let k = b1.generalised_inv_mass(r_1, u_1) + self.ratio * self.ratio * b2.generalised_inv_mass(r_2, u_2);
+                if k == 0.0 { return; }
+
+                let v = Vector2::dot(b1.point_velocity(r_1), u_1) + self.ratio * Vector2::dot(b2.point_velocity(r_2), u_2);
+                let j = -(v + c * BAUMGARTE / dt) / (k + self.compliance / (dt * dt));
+                if self.breaks(j, dt) { return; }
+
+                b1.apply_impulse(u_1 * j, r_1);
+                b2.apply_impulse(u_2 * (self.ratio * j), r_2);
+            }
+            JointKind::Gear | JointKind::Mouse => {}
+            JointKind::Prismatic => {
+                let n = Rot::new(b1.motion().rotation).rotate(self.axis);
+                let t = v2!(-n.y, n.x);
+                let d = p_2 - p_1;
+                let k_n = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+                let along = |b1: &BodyMut, b2: &BodyMut| Vector2::dot(b2.point_velocity(r_2) - b1.point_velocity(r_1), n);
+
+                // Spring along the axis
+                if self.frequency > 0.0 && k_n > 0.0 {
+                    let j = self.soft_impulse(&b1, &b2, (r_1, r_2), n, Vector2::dot(d, n) - self.length, k_n, dt);
+                    b1.apply_impulse(n * -j, r_1);
+                    b2.apply_impulse(n * j, r_2);
+                }
+
+                if let (Some(motor), true) = (self.motor, k_n > 0.0) {
+                    let j = ((motor.speed - along(&b1, &b2)) / k_n).clamp(-motor.max_force * dt, motor.max_force * dt);
+                    b1.apply_impulse(n * -j, r_1);
+                    b2.apply_impulse(n * j, r_2);
+                }
+
+                if let (Some(limits), true) = (self.limits, k_n > 0.0) {
+                    let j = limit_impulse(&limits, Vector2::dot(d, n), along(&b1, &b2), k_n, dt);
+                    if self.breaks(j, dt) { return; }
+                    b1.apply_impulse(n * -j, r_1);
+                    b2.apply_impulse(n * j, r_2);
+                }
+
+                // Lock the relative rotation
+                let k_a = b1.inv_inertia() + b2.inv_inertia();
+                if k_a > 0.0 {
+                    let w = b2.angular_velocity - b1.angular_velocity;
+                    let j = -(w + self.angle(&b1, &b2) * BAUMGARTE / dt) / k_a;
+                    b1.apply_angular_impulse(-j);
+                    b2.apply_angular_impulse(j);
+                }
+
+                // Keep the second anchor on the axis
+                let k_t = b1.generalised_inv_mass(r_1, t) + b2.generalised_inv_mass(r_2, t) + self.compliance / (dt * dt);
+                if k_t == 0.0 { return; }
+
+                let rel_v = b2.point_velocity(r_2) - b1.point_velocity(r_1);
+                let j = -(Vector2::dot(rel_v, t) + Vector2::dot(d, t) * BAUMGARTE / dt) / k_t;
+                if self.breaks(j, dt) { return; }
+
+                b1.apply_impulse(t * -j, r_1);
+                b2.apply_impulse(t * j, r_2);
+            }
+        }
+    }
+
+    /// Position-level solve, used by the XPBD solver. Multipliers are reset here, as joints are solved once per substep.
+    pub fn solve_position(&mut self, bodies: &mut BodyArena, h: Real) {
+        self.lambda = 0.0;
+        self.lambda_limit = 0.0;
+        self.lambda_motor = 0.0;
+        if self.broken { return; }
+        match self.kind {
+            JointKind::Gear => return self.solve_gear(bodies, h, true),
+            JointKind::Mouse => return self.solve_mouse_position(bodies, h),
+            _ => {}
+        }
+
+        let Some((mut b1, mut b2)) = bodies.pair_mut(self.bodies[0], self.bodies[1]) else { return; };
+
+        let p_1 = b1.world_point(self.anchors[0]);
+        let p_2 = b2.world_point(self.anchors[1]);
+        let (r_1, r_2) = self.arms(&b1, &b2, p_1, p_2);
+        let d = p_2 - p_1;
+
+        match self.kind {
+            JointKind::Distance | JointKind::Revolute => {
+                if self.kind == JointKind::Revolute {
+                    self.solve_angular_extensions(&mut b1, &mut b2, h);
+                }
+
+                // The extensions rotate the bodies, so the anchors must be re-evaluated
+                let p_1 = b1.world_point(self.anchors[0]);
+                let p_2 = b2.world_point(self.anchors[1]);
+                let (r_1, r_2) = self.arms(&b1, &b2, p_1, p_2);
+                let d = p_2 - p_1;
+
+                // Both joint kinds are distance constraints; a revolute joint has a rest distance of 0
+                if d.mag() < LENGTH_TOLERANCE { return; }
+
+                let n = d.norm();
+                let spring = self.kind == JointKind::Distance;
+                let d_lambda = self.positional(&mut b1, &mut b2, (r_1, r_2), n, d.mag() - self.length, h, spring);
+                if self.lambda.abs() / (h * h) > self.break_force {
+                    self.broken = true;
+                    return;
+                }
+
+                b1.apply_correction(n * -d_lambda, r_1);
+                b2.apply_correction(n * d_lambda, r_2);
+            }
+            JointKind::Pulley => {
+                let Some((u_1, u_2, c)) = self.pulley_frame(p_1, p_2) else { return; };
+                let w = b1.generalised_inv_mass(r_1, u_1) + self.ratio * self.ratio * b2.generalised_inv_mass(r_2, u_2);
+                if w == 0.0 { return; }
+
+                let alpha = self.compliance / (h * h);
+                let d_lambda = (-c - alpha * self.lambda) / (w + alpha);
+                self.lambda += d_lambda;
+                if self.lambda.abs() / (h * h) > self.break_force {
+                    self.broken = true;
+                    return;
+                }
+
+                b1.apply_correction(u_1 * d_lambda, r_1);
+                b2.apply_correction(u_2 * (self.ratio * d_lambda), r_2);
+            }
+            JointKind::Gear | JointKind::Mouse => {}
+            JointKind::Prismatic => {
+                let n = Rot::new(b1.motion().rotation).rotate(self.axis);
+                let t = v2!(-n.y, n.x);
+                let w_n = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+
+                // Spring along the axis
+                if self.frequency > 0.0 {
+                    let d_lambda = self.positional(&mut b1, &mut b2, (r_1, r_2), n, Vector2::dot(d, n) - self.length, h, true);
+                    b1.apply_correction(n * -d_lambda, r_1);
+                    b2.apply_correction(n * d_lambda, r_2);
+                    self.lambda = 0.0;
+                }
+
+                if let (Some(motor), true) = (self.motor, w_n > 0.0) {
+                    let moved = Vector2::dot(b2.point_displacement(r_2) - b1.point_displacement(r_1), n);
+                    let d_lambda = self.motor_lambda(&motor, moved, w_n, h);
+                    b1.apply_correction(n * -d_lambda, r_1);
+                    b2.apply_correction(n * d_lambda, r_2);
+                }
+
+                if let (Some(limits), true) = (self.limits, w_n > 0.0) {
+                    let v = Vector2::dot(b2.point_velocity(r_2) - b1.point_velocity(r_1), n);
+                    let d_lambda = self.limit_lambda(&limits, Vector2::dot(d, n), v, w_n);
+                    b1.apply_correction(n * -d_lambda, r_1);
+                    b2.apply_correction(n * d_lambda, r_2);
+                }
+
+                // Lock the relative rotation
+                let w_a = b1.inv_inertia() + b2.inv_inertia();
+                if w_a > 0.0 {
+                    let d_lambda = -self.angle(&b1, &b2) / w_a;
+                    b1.apply_angular_correction(-d_lambda);
+                    b2.apply_angular_correction(d_lambda);
+                }
+
+                // Keep the second anchor on the axis
+                let p_1 = b1.world_point(self.anchors[0]);
+                let p_2 = b2.world_point(self.anchors[1]);
+                let (r_1, r_2) = self.arms(&b1, &b2, p_1, p_2);
+                let d_lambda = self.positional(&mut b1, &mut b2, (r_1, r_2), t, Vector2::dot(p_2 - p_1, t), h, false);
+                if (self.lambda.abs() + self.lambda_limit.abs()) / (h * h) > self.break_force {
+                    self.broken = true;
+                    return;
+                }
+
+                b1.apply_correction(t * -d_lambda, r_1);
+                b2.apply_correction(t * d_lambda, r_2);
+            }
+        }
+    }
+
+    /// Velocity pass of the XPBD solver; makes limits bounce with their restitution
+    pub fn solve_limit_velocity(&mut self, bodies: &mut BodyArena) {
+        let Some(limits) = self.limits else { return; };
+        if self.broken || self.lambda_limit == 0.0 { return; }
+
+        let Some((mut b1, mut b2)) = bodies.pair_mut(self.bodies[0], self.bodies[1]) else { return; };
+
+        // The limit pushes in the direction of its multiplier; only ever increase the separating speed
+        let s = self.lambda_limit.signum();
+        let e = if self.limit_v.abs() <= RESTING_VELOCITY { 0.0 } else { limits.restitution };
+        let target = (-e * self.limit_v * s).max(0.0);
+
+        match self.kind {
+            JointKind::Revolute => {
+                let k = b1.inv_inertia() + b2.inv_inertia();
+                let v = (b2.angular_velocity - b1.angular_velocity) * s;
+                if v >= target || k == 0.0 { return; }
+
+                let j = (target - v) / k * s;
+                b1.apply_angular_impulse(-j);
+                b2.apply_angular_impulse(j);
+            }
+            JointKind::Prismatic => {
+                let p_1 = b1.world_point(self.anchors[0]);
+                let p_2 = b2.world_point(self.anchors[1]);
+                let (r_1, r_2) = self.arms(&b1, &b2, p_1, p_2);
+                let n = Rot::new(b1.motion().rotation).rotate(self.axis);
+
+                let k = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+                let v = Vector2::dot(b2.point_velocity(r_2) - b1.point_velocity(r_1), n) * s;
+                if v >= target || k == 0.0 { return; }
+
+                let j = (target - v) / k * s;
+                b1.apply_impulse(n * -j, r_1);
+                b2.apply_impulse(n * j, r_2);
+            }
+            JointKind::Distance | JointKind::Pulley | JointKind::Gear | JointKind::Mouse => {}
+        }
+    }
+
+    /// Motor & limit corrections of a revolute joint's relative rotation
+    fn solve_angular_extensions(&mut self, b1: &mut BodyMut, b2: &mut BodyMut, h: Real) {
+        let w = b1.inv_inertia() + b2.inv_inertia();
+        if w == 0.0 { return; }
+
+        if let Some(motor) = self.motor {
+            let turned = (b2.rotation - b2.prev_rotation) - (b1.rotation - b1.prev_rotation);
+            let d_lambda = self.motor_lambda(&motor, turned, w, h);
+            b1.apply_angular_correction(-d_lambda);
+            b2.apply_angular_correction(d_lambda);
+        }
+
+        if let Some(limits) = self.limits {
+            let v = b2.angular_velocity - b1.angular_velocity;
+            let d_lambda = self.limit_lambda(&limits, self.angle(b1, b2), v, w);
+            b1.apply_angular_correction(-d_lambda);
+            b2.apply_angular_correction(d_lambda);
+        }
+    }
+
+    /// XPBD multiplier update for a positional constraint with error c along n; honours compliance, and springs if asked
+    #[allow(clippy::too_many_arguments)]
+    fn positional(&mut self, b1: &mut BodyMut, b2: &mut BodyMut, r: (Vector2<Real>, Vector2<Real>), n: Vector2<Real>, c: Real, h: Real, spring: bool) -> Real {
+        let w = b1.generalised_inv_mass(r.0, n) + b2.generalised_inv_mass(r.1, n);
+        if w == 0.0 { return 0.0; }
+
+        let (alpha, gamma) = if spring && self.frequency > 0.0 {
+            // Stiffness & damping from the frequency & damping ratio of the effective mass
+            let omega = 2.0 * PI * self.frequency;
+            (w / (omega * omega * h * h), 2.0 * self.damping_ratio / (omega * h))
+        } else {
+            (self.compliance / (h * h), 0.0)
+        };
+
+        let moved = Vector2::dot(b2.point_displacement(r.1) - b1.point_displacement(r.0), n);
+        let d_lambda = (-c - alpha * self.lambda - gamma * moved) / ((1.0 + gamma) * w + alpha);
+        self.lambda += d_lambda;
+        d_lambda
+    }
+
+    /// XPBD multiplier for a motor which has `moved` over the substep, along a constraint with inverse mass w
+    fn motor_lambda(&mut self, motor: &Motor, moved: Real, w: Real, h: Real) -> Real {
+        let max = motor.max_force * h * h;
+        let lambda = (self.lambda_motor + (motor.speed * h - moved) / w).clamp(-max, max);
+        let d_lambda = lambda - self.lambda_motor;
+        self.lambda_motor = lambda;
+        d_lambda
+    }
+
+    /// XPBD multiplier pushing a 1D quantity x back within the limits; v is its speed, used later for restitution
+    fn limit_lambda(&mut self, limits: &Limits, x: Real, v: Real, w: Real) -> Real {
+        let c = if x < limits.lower {
+            x - limits.lower
+        } else if x > limits.upper {
+            x - limits.upper
+        } else {
+            return 0.0;
+        };
+
+        self.limit_v = v;
+        self.lambda_limit = -c / w;
+        self.lambda_limit
+    }
+
+    /// Impulse for a (possibly spring-like) constraint with error c along n; k is the rigid effective inverse mass
+    #[allow(clippy::too_many_arguments)]
+    fn soft_impulse(&self, b1: &impl BodyView, b2: &impl BodyView, r: (Vector2<Real>, Vector2<Real>), n: Vector2<Real>, c: Real, k: Real, dt: Real) -> Real {
+        if k == 0.0 { return 0.0; }
+        let v = Vector2::dot(b2.point_velocity(r.1) - b1.point_velocity(r.0), n);
+
+        let (gamma, beta) = if self.frequency > 0.0 {
+            // Soft constraint; spring & damper coefficients from the frequency & damping ratio of the effective mass
+            let m = 1.0 / k;
+            let omega = 2.0 * PI * self.frequency;
+            let (spring, damper) = (m * omega * omega, 2.0 * m * self.damping_ratio * omega);
+            let gamma = 1.0 / (dt * (damper + dt * spring));
+            (gamma, dt * spring * gamma)
+        } else {
+            (self.compliance / (dt * dt), BAUMGARTE / dt)
+        };
+
+        -(v + c * beta) / (k + gamma)
+    }
+
+    /// Gear solve, at the velocity (impulse pipeline) or position (XPBD) level. The coupled joints are borrowed
+    /// one at a time, so they may share bodies.
+    fn solve_gear(&mut self, bodies: &mut BodyArena, dt: Real, position: bool) {
+        let Some([j1, j2]) = self.coupled.clone() else { return; };
+        let (x_1, v_1, k_1) = j1.borrow().coordinate(bodies);
+        let (x_2, v_2, k_2) = j2.borrow().coordinate(bodies);
+
+        let k = k_1 + self.ratio * self.ratio * k_2;
+        if k == 0.0 { return; }
+        let c = x_1 + self.ratio * x_2 - self.length;
+
+        let j = if position {
+            let alpha = self.compliance / (dt * dt);
+            -(c + alpha * self.lambda) / (k + alpha)
+        } else {
+            -(v_1 + self.ratio * v_2 + c * BAUMGARTE / dt) / (k + self.compliance / (dt * dt))
+        };
+        self.lambda += j;
+
+        let force = if position { self.lambda.abs() / (dt * dt) } else { j.abs() / dt };
+        if force > self.break_force {
+            self.broken = true;
+            return;
+        }
+
+        j1.borrow().apply_coordinate(bodies, j, position);
+        j2.borrow().apply_coordinate(bodies, self.ratio * j, position);
+    }
+
+    /// Impulse pulling a mouse joint's anchor towards its target, on both axes at once
+    fn solve_mouse_velocity(&mut self, bodies: &mut BodyArena, dt: Real) {
+        let Some(mut body) = bodies.get_mut(self.bodies[0]) else { return; };
+        let (m, i) = (body.inv_mass(), body.inv_inertia());
+        if m == 0.0 { return; }
+        body.angular_velocity *= (-MOUSE_SPIN_DAMPING * dt).exp();
+
+        let p = body.world_point(self.anchors[0]);
+        let r = p - body.center();
+        let (gamma, beta) = if self.frequency > 0.0 {
+            // Spring & damper coefficients from the frequency & damping ratio of the body's mass
+            let omega = 2.0 * PI * self.frequency;
+            let (spring, damper) = (omega * omega / m, 2.0 * self.damping_ratio * omega / m);
+            let gamma = 1.0 / (dt * (damper + dt * spring));
+            (gamma, dt * spring * gamma)
+        } else {
+            (self.compliance / (dt * dt), BAUMGARTE / dt)
+        };
+
+        // Effective mass matrix of the point constraint, softened by the spring
+        let k_11 = m + i * r.y * r.y + gamma;
+        let k_12 = -i * r.x * r.y;
+        let k_22 = m + i * r.x * r.x + gamma;
+        let k = Mat22::new(v2!(k_11, k_12), v2!(k_12, k_22));
+
+        let b = (body.point_velocity(r) + (p - self.ground[0]) * beta) * -1.0;
+        let Some(impulse) = k.solve(b) else { return; };
+        if self.breaks(impulse.mag(), dt) { return; }
+
+        let max = MOUSE_ACCELERATION / m * dt;
+        let impulse = if impulse.mag() > max { impulse.norm() * max } else { impulse };
+        body.apply_impulse(impulse, r);
+    }
+
+    /// XPBD correction pulling a mouse joint's anchor towards its target, on both axes at once
+    fn solve_mouse_position(&mut self, bodies: &mut BodyArena, h: Real) {
+        let Some(mut body) = bodies.get_mut(self.bodies[0]) else { return; };
+        let m = body.inv_mass();
+        if m == 0.0 { return; }
+        let spin = body.rotation - body.prev_rotation;
+        body.rotation -= spin * (1.0 - (-MOUSE_SPIN_DAMPING * h).exp());
+
+        let p = body.world_point(self.anchors[0]);
+        let r = p - body.center();
+        let d = p - self.ground[0];
+
+        // Each axis is a spring of its own, so that motion around the target is damped too. Joints are solved once
+        // per substep, so the multipliers start from 0
+        let axis = |n: Vector2<Real>, c: Real| {
+            let w = body.generalised_inv_mass(r, n);
+            if w == 0.0 { return 0.0; }
+
+            let (alpha, gamma) = if self.frequency > 0.0 {
+                let omega = 2.0 * PI * self.frequency;
+                (w / (omega * omega * h * h), 2.0 * self.damping_ratio / (omega * h))
+            } else {
+                (self.compliance / (h * h), 0.0)
+            };
+            let moved = Vector2::dot(body.point_displacement(r), n);
+            (-c - gamma * moved) / ((1.0 + gamma) * w + alpha)
+        };
+        let lambda = v2!(axis(v2!(1.0, 0.0), d.x), axis(v2!(0.0, 1.0), d.y));
+
+        let max = MOUSE_ACCELERATION / m * h * h;
+        let lambda = if lambda.mag() > max { lambda.norm() * max } else { lambda };
+        self.lambda = lambda.mag();
+        if self.lambda / (h * h) > self.break_force {
+            self.broken = true;
+            return;
+        }
+
+        body.apply_correction(lambda, r);
+    }
+
+    /// Position, velocity & effective inverse mass of the joint's free coordinate;
+    /// the relative angle of revolute joints, the translation of prismatic joints, or the length of distance joints
+    pub fn coordinate(&self, bodies: &BodyArena) -> (Real, Real, Real) {
+        let (b1, b2) = (bodies.body(self.bodies[0]), bodies.body(self.bodies[1]));
+
+        match self.coordinate_frame(&b1, &b2) {
+            Some((n, r_1, r_2, x)) => (
+                x,
+                Vector2::dot(b2.point_velocity(r_2) - b1.point_velocity(r_1), n),
+                b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n),
+            ),
+            None if self.kind == JointKind::Revolute => (
+                self.angle(&b1, &b2),
+                b2.angular_velocity - b1.angular_velocity,
+                b1.inv_inertia() + b2.inv_inertia(),
+            ),
+            None => (0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Apply an impulse (or positional correction) to the joint's free coordinate; see `coordinate`
+    fn apply_coordinate(&self, bodies: &mut BodyArena, amount: Real, position: bool) {
+        let Some((mut b1, mut b2)) = bodies.pair_mut(self.bodies[0], self.bodies[1]) else { return; };
+
+        match (self.coordinate_frame(&b1, &b2), position) {
+            (Some((n, r_1, r_2, _)), false) => {
+                b1.apply_impulse(n * -amount, r_1);
+                b2.apply_impulse(n * amount, r_2);
+            }
+            (Some((n, r_1, r_2, _)), true) => {
+                b1.apply_correction(n * -amount, r_1);
+                b2.apply_correction(n * amount, r_2);
+            }
+            (None, false) if self.kind == JointKind::Revolute => {
+                b1.apply_angular_impulse(-amount);
+                b2.apply_angular_impulse(amount);
+            }
+            (None, true) if self.kind == JointKind::Revolute => {
+                b1.apply_angular_correction(-amount);
+                b2.apply_angular_correction(amount);
+            }
+            _ => {}
+        }
+    }
+
+    /// Direction, anchor arms & value of a linear coordinate; None for angular (or coupling) joints
+    #[allow(clippy::type_complexity)]
+    fn coordinate_frame(&self, b1: &impl BodyView, b2: &impl BodyView) -> Option<(Vector2<Real>, Vector2<Real>, Vector2<Real>, Real)> {
+        let p_1 = b1.world_point(self.anchors[0]);
+        let p_2 = b2.world_point(self.anchors[1]);
+        let (r_1, r_2) = self.arms(b1, b2, p_1, p_2);
+        let d = p_2 - p_1;
+
+        match self.kind {
+            JointKind::Prismatic => {
+                let n = Rot::new(b1.motion().rotation).rotate(self.axis);
+                Some((n, r_1, r_2, Vector2::dot(d, n)))
+            }
+            JointKind::Distance if d.mag() >= LENGTH_TOLERANCE => Some((d.norm(), r_1, r_2, d.mag())),
+            _ => None,
+        }
+    }
+
+    /// Rope directions (from each ground point towards its anchor) & the length error of a pulley
+    fn pulley_frame(&self, p_1: Vector2<Crd>, p_2: Vector2<Crd>) -> Option<(Vector2<Real>, Vector2<Real>, Real)> {
+        let (d_1, d_2) = (p_1 - self.ground[0], p_2 - self.ground[1]);
+        if d_1.mag() < LENGTH_TOLERANCE || d_2.mag() < LENGTH_TOLERANCE { return None; }
+
+        Some((d_1.norm(), d_2.norm(), d_1.mag() + self.ratio * d_2.mag() - self.length))
+    }
+
+    /// Breaks the joint if the impulse over dt exceeds the break force
+    fn breaks(&mut self, impulse: Real, dt: Real) -> bool {
+        if impulse.abs() / dt > self.break_force {
+            self.broken = true;
+        }
+        self.broken
+    }
+
+    /// Anchor offsets from each body's center of mass. The first body of a prismatic joint is
+    /// acted on where the second anchor currently lies along its axis.
+    fn arms(&self, b1: &impl BodyView, b2: &impl BodyView, p_1: Vector2<Crd>, p_2: Vector2<Crd>) -> (Vector2<Real>, Vector2<Real>) {
+        match self.kind {
+            JointKind::Prismatic => (p_2 - b1.center(), p_2 - b2.center()),
+            _ => (p_1 - b1.center(), p_2 - b2.center()),
+        }
+    }
+
+    /// Relative rotation of the bodies, measured from the reference angle
+    fn angle(&self, b1: &impl BodyView, b2: &impl BodyView) -> Real {
+        b2.motion().rotation - b1.motion().rotation - self.reference_angle
+    }
+
+    /// Global positions of both anchors
+    pub fn world_anchors(&self, bodies: &BodyArena) -> [Vector2<Crd>; 2] {
+        [
+            bodies.body(self.bodies[0]).world_point(self.anchors[0]),
+            bodies.body(self.bodies[1]).world_point(self.anchors[1]),
+        ]
+    }
+    /// Whether the joint & both of its bodies take part in the simulation
+    pub fn is_active(&self, bodies: &BodyArena) -> bool {
+        self.enabled && self.bodies.iter().all(|&b| bodies.get(b).is_some_and(|body| body.enabled))
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_length(mut self, length: Real) -> Self {
+        self.length = length;
+        self
+    }
+    pub fn set_compliance(mut self, compliance: Real) -> Self {
+        self.compliance = compliance;
+        self
+    }
+    pub fn set_break_force(mut self, break_force: Real) -> Self {
+        self.break_force = break_force;
+        self
+    }
+    pub fn set_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub fn set_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+    /// Sets the prismatic slide axis, given in global space
+    pub fn set_axis(mut self, axis: Vector2<Real>) -> Self {
+        self.axis = Rot::new(-self.base_rotation).rotate(axis.norm());
+        self
+    }
+    pub fn set_motor(mut self, speed: Real, max_force: Real) -> Self {
+        self.motor = Some(Motor { speed, max_force });
+        self
+    }
+    pub fn set_limits(mut self, lower: Real, upper: Real, restitution: Real) -> Self {
+        self.limits = Some(Limits { lower, upper, restitution });
+        self
+    }
+    /// Makes a distance joint, or the axis of a prismatic joint, a damped spring
+    pub fn set_spring(mut self, frequency: Real, damping_ratio: Real) -> Self {
+        self.frequency = frequency;
+        self.damping_ratio = damping_ratio;
+        self
+    }
+    /// Moves a mouse joint's target, in global space
+    pub fn set_target(&mut self, target: Vector2<Crd>) {
+        self.ground[0] = target;
+    }
+    /// Changes the target speed of the motor at runtime; does nothing for joints without a motor
+    pub fn set_motor_speed(&mut self, speed: Real) {
+        if let Some(motor) = self.motor.as_mut() {
+            motor.speed = speed;
+        }
+    }
+}
+
+/// Impulse keeping a 1D quantity x, moving at v, within the limits; k is the effective inverse mass along it
+fn limit_impulse(limits: &Limits, x: Real, v: Real, k: Real, dt: Real) -> Real {
+    // s flips the upper limit onto the lower one, so only pushes in the positive direction need handling
+    let (c, s) = if x <= limits.lower {
+        (x - limits.lower, 1.0)
+    } else if x >= limits.upper {
+        (limits.upper - x, -1.0)
+    } else {
+        return 0.0;
+    };
+
+    let v = v * s;
+    let e = if v.abs() <= RESTING_VELOCITY { 0.0 } else { limits.restitution };
+    let target = (-e * v).max(-c * BAUMGARTE / dt);
+
+    ((target - v) / k).max(0.0) * s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::engine::{Engine, Solver};
+    use crate::app::objects::Body;
+    use crate::app::ssm::SystemStateManager;
+    use crate::common::{BodyForm, Materials, Shared};
+    use crate::rect;
+
+    const DT: Real = 1.0 / 60.0;
+
+    /// An engine using the given solver, & a world holding a frozen base, out of the way of everything else
+    fn world(solver: Solver) -> (SystemStateManager, Engine, BodyHandle) {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        let mut engine = Engine::new(shared);
+        engine.set_solver(solver);
+
+        let mut ssm = SystemStateManager::new();
+        let base = ssm.add_body(rect!(v2!(0.0, 0.0), 0.2, 0.2).set_frozen(true));
+        (ssm, engine, base)
+    }
+
+    #[test]
+    fn motor_reaches_its_target_speed() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            // A wheel pinned at its center, driven at 2 rad/s
+            let (mut ssm, mut engine, base) = world(solver);
+            let wheel = ssm.add_body(rect!(v2!(9.5, 5.5), 1.0, 1.0));
+            let axle = Joint::new(JointKind::Revolute, ssm.bodies(), base, wheel, v2!(10.0, 6.0), v2!(10.0, 6.0)).set_motor(2.0, 100.0);
+            let axle = ssm.add_joint(axle);
+
+            for _ in 0..60 {
+                engine.step(&mut ssm, DT);
+            }
+            let (_, speed, _) = axle.borrow().coordinate(ssm.bodies());
+            assert!((speed - 2.0).abs() < 0.01, "{solver:?} motor turning at {speed} rad/s");
+            assert!((ssm.bodies().body(wheel).center() - v2!(10.0, 6.0)).mag() < 0.01);
+        }
+    }
+
+    #[test]
+    fn limit_holds_at_its_bound() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            // An arm pinned at one end, level, swinging down under gravity until the upper limit stops it
+            let (mut ssm, mut engine, base) = world(solver);
+            let arm = ssm.add_body(rect!(v2!(8.0, 5.9), 2.0, 0.2));
+            let pivot = Joint::new(JointKind::Revolute, ssm.bodies(), base, arm, v2!(8.0, 6.0), v2!(8.0, 6.0)).set_limits(-0.5, 0.5, 0.0);
+            let pivot = ssm.add_joint(pivot);
+
+            let mut angle = 0.0;
+            for _ in 0..180 {
+                engine.step(&mut ssm, DT);
+                angle = pivot.borrow().coordinate(ssm.bodies()).0;
+                assert!(angle < 0.5 + 0.02, "{solver:?} arm swung past the limit, to {angle} rad");
+            }
+            assert!((angle - 0.5).abs() < 0.02, "{solver:?} arm rests at {angle} rad");
+        }
+    }
+
+    #[test]
+    fn broken_joints_are_reported_once() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            // Two weights hanging from rods; one able to bear ten times its weight, the other a tenth of it
+            let (mut ssm, mut engine, base) = world(solver);
+            let (kept, dropped) = (ssm.add_body(rect!(v2!(4.0, 4.0), 0.5, 0.5)), ssm.add_body(rect!(v2!(12.0, 4.0), 0.5, 0.5)));
+            let weight = 9.81 / ssm.bodies().body(kept).inv_mass();
+
+            let holds = Joint::new(JointKind::Distance, ssm.bodies(), base, kept, v2!(4.25, 2.0), v2!(4.25, 4.0)).set_break_force(weight * 10.0);
+            let snaps = Joint::new(JointKind::Distance, ssm.bodies(), base, dropped, v2!(12.25, 2.0), v2!(12.25, 4.0)).set_break_force(weight * 0.1);
+            let (holds, snaps) = (ssm.add_joint(holds), ssm.add_joint(snaps));
+
+            let mut breaks = 0;
+            for _ in 0..60 {
+                engine.step(&mut ssm, DT);
+                for joint_ref in engine.broken_joints() {
+                    assert!(Rc::ptr_eq(joint_ref, &snaps), "{solver:?} broke the strong joint");
+                    breaks += 1;
+                }
+            }
+            assert_eq!(breaks, 1, "{solver:?}");
+            assert!(snaps.borrow().broken && !holds.borrow().broken);
+            assert!(ssm.bodies().body(dropped).center().y > 8.0, "{solver:?} weight didn't fall");
+        }
+    }
+
+    #[test]
+    fn pulley_keeps_its_total_length() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            // Equal weights, the second on a rope twice as expensive; the first drops, hoisting the second half as far
+            let (mut ssm, mut engine, _) = world(solver);
+            let (b1, b2) = (ssm.add_body(rect!(v2!(4.0, 5.0), 0.5, 0.5)), ssm.add_body(rect!(v2!(12.0, 5.0), 0.5, 0.5)));
+            let pulley = Joint::pulley(ssm.bodies(), b1, b2, v2!(4.25, 5.0), v2!(12.25, 5.0), v2!(4.25, 2.0), v2!(12.25, 2.0), 2.0);
+            let pulley = ssm.add_joint(pulley);
+
+            let lengths = |ssm: &SystemStateManager| {
+                let [p_1, p_2] = pulley.borrow().world_anchors(ssm.bodies());
+                ((p_1 - v2!(4.25, 2.0)).mag(), (p_2 - v2!(12.25, 2.0)).mag())
+            };
+            for _ in 0..60 {
+                engine.step(&mut ssm, DT);
+                let (len_1, len_2) = lengths(&ssm);
+                assert!((len_1 + 2.0 * len_2 - 9.0).abs() < 0.01, "{solver:?} pulley ropes total {}", len_1 + 2.0 * len_2);
+            }
+            assert!(lengths(&ssm).0 > 4.0, "{solver:?} pulley didn't turn");
+        }
+    }
+
+    #[test]
+    fn gear_keeps_its_total_angle() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            // Two wheels on axles, the first driven by a motor; the second turns half as fast, the other way
+            let (mut ssm, mut engine, base) = world(solver);
+            let (w1, w2) = (ssm.add_body(rect!(v2!(5.5, 5.5), 1.0, 1.0)), ssm.add_body(rect!(v2!(13.5, 5.5), 1.0, 1.0)));
+            let axle_1 = Joint::new(JointKind::Revolute, ssm.bodies(), base, w1, v2!(6.0, 6.0), v2!(6.0, 6.0)).set_motor(2.0, 100.0);
+            let axle_2 = Joint::new(JointKind::Revolute, ssm.bodies(), base, w2, v2!(14.0, 6.0), v2!(14.0, 6.0));
+            let (axle_1, axle_2) = (ssm.add_joint(axle_1), ssm.add_joint(axle_2));
+            let gear = Joint::gear(ssm.bodies(), axle_1.clone(), axle_2.clone(), 2.0);
+            ssm.add_joint(gear);
+
+            let angles = |ssm: &SystemStateManager| (axle_1.borrow().coordinate(ssm.bodies()).0, axle_2.borrow().coordinate(ssm.bodies()).0);
+            for _ in 0..60 {
+                engine.step(&mut ssm, DT);
+                let (angle_1, angle_2) = angles(&ssm);
+                assert!((angle_1 + 2.0 * angle_2).abs() < 0.01, "{solver:?} gear angles total {}", angle_1 + 2.0 * angle_2);
+            }
+            assert!(angles(&ssm).0 > 1.0, "{solver:?} gear didn't turn");
+        }
+    }
+}
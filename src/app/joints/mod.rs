@@ -0,0 +1,2 @@
+mod joint;
+pub use joint::{Joint, JointKind, Limits, Motor};
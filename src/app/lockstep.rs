@@ -0,0 +1,266 @@
+/*
+    lockstep.rs
+    ----------------------------------------
+    Description:
+    * Provides a deterministic rigid body world running on Fixed (Q32.32); built with the `fixed` feature
+    * The engine isn't generic over its scalar, so this is a separate, smaller world; polygons under gravity, with
+      the engine's own SAT test, contact points & impulse resolver (collision::contact, generic over Number). Joints,
+      XPBD, force fields, the broad phase grid & the rest of the engine are float-only
+    * Bodies & materials are built from fixed-point values alone, so that every build & machine (f32 or f64,
+      debug or release) reaches the same state from the same scene
+    * Run with `cargo run --features fixed -- --lockstep [steps]`
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::app::collision::contact::{self, Contactable};
+use crate::app::fixed::{cross, dot, Fixed};
+use crate::app::replay::Fnv;
+use crate::common::Vector2;
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+pub const DEFAULT_STEPS: u64 = 600;
+const ITERATIONS: i32 = 10;
+
+/* ------------------- STRUCTURES ------------------- */
+/// Fixed-point counterpart of a Material.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedMaterial {
+    pub density: Fixed, // Kilograms per square metre
+    pub e: Fixed,
+    pub ks: Fixed,
+    pub kd: Fixed,
+}
+
+#[derive(Debug, Clone)]
+pub struct FixedBody {
+    pub position: Vector2<Fixed>, // Center of mass, which the body rotates about
+    pub rotation: Fixed,
+    pub velocity: Vector2<Fixed>,
+    pub angular_velocity: Fixed,
+    pub vertices: Vec<Vector2<Fixed>>, // About the center of mass
+    pub inv_mass: Fixed,
+    pub inv_inertia: Fixed,
+    pub material: FixedMaterial,
+    pub frozen: bool,
+    radius: Fixed, // Furthest vertex from the center of mass; for the broad phase
+}
+
+pub struct LockstepWorld {
+    bodies: Vec<FixedBody>,
+    gravity: Vector2<Fixed>, // m/s²
+    steps: u64,
+}
+
+struct Contact {
+    bodies: [usize; 2],
+    normal: Vector2<Fixed>,
+    overlap: Fixed,
+    points: Vec<Vector2<Fixed>>,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl FixedMaterial {
+    // The engine's Materials, as exact ratios rather than through floats
+    pub const ROCK: FixedMaterial = FixedMaterial { density: Fixed::ratio(1, 2), e: Fixed::ratio(3, 10), ks: Fixed::ratio(12, 100), kd: Fixed::ratio(6, 100) };
+    pub const METAL: FixedMaterial = FixedMaterial { density: Fixed::ratio(7, 10), e: Fixed::ratio(15, 100), ks: Fixed::ratio(15, 100), kd: Fixed::ratio(8, 100) };
+    pub const BOUNCY: FixedMaterial = FixedMaterial { density: Fixed::ratio(1, 5), e: Fixed::ratio(6, 5), ks: Fixed::ratio(8, 100), kd: Fixed::ratio(1, 100) };
+    pub const RUBBER: FixedMaterial = FixedMaterial { density: Fixed::ratio(2, 5), e: Fixed::ratio(1, 5), ks: Fixed::ratio(9, 10), kd: Fixed::ratio(7, 10) };
+}
+
+impl FixedBody {
+    /// Body of the given shape, with its vertices about the center of mass at the given position
+    pub fn new(position: Vector2<Fixed>, vertices: Vec<Vector2<Fixed>>, material: FixedMaterial) -> FixedBody {
+        // Area & moment of inertia about the center, summed over the triangles fanning out from it
+        let (mut area, mut inertia) = (Fixed::ZERO, Fixed::ZERO);
+        for (i, &a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            let area_s = cross(a, b) * Fixed::HALF;
+            area += area_s;
+            inertia += area_s * (dot(a, a) + dot(a, b) + dot(b, b)) / Fixed::from_int(6);
+        }
+        let (mass, inertia) = (material.density * area.abs(), material.density * inertia.abs());
+        let radius = vertices.iter().map(|v| v.length()).max().unwrap_or(Fixed::ZERO);
+
+        FixedBody {
+            position,
+            rotation: Fixed::ZERO,
+            velocity: v2!(Fixed::ZERO, Fixed::ZERO),
+            angular_velocity: Fixed::ZERO,
+            vertices,
+            inv_mass: if mass.is_zero() { Fixed::ZERO } else { Fixed::ONE / mass },
+            inv_inertia: if inertia.is_zero() { Fixed::ZERO } else { Fixed::ONE / inertia },
+            material,
+            frozen: false,
+            radius,
+        }
+    }
+    /// Regular polygon centered on the given position, with its first vertex along +x
+    pub fn polygon(center: Vector2<Fixed>, radius: Fixed, sides: u32, material: FixedMaterial) -> FixedBody {
+        let angle = Fixed::TAU / Fixed::from_int(sides as i32);
+        let vertices = (0..sides).map(|i| v2!(radius, Fixed::ZERO).rotate(angle * Fixed::from_int(i as i32))).collect();
+        FixedBody::new(center, vertices, material)
+    }
+    /// Rectangle centered on the given position
+    pub fn rect(center: Vector2<Fixed>, width: Fixed, height: Fixed, material: FixedMaterial) -> FixedBody {
+        let (w, h) = (width * Fixed::HALF, height * Fixed::HALF);
+        FixedBody::new(center, vec![v2!(-w, -h), v2!(-w, h), v2!(w, h), v2!(w, -h)], material)
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    /// Freezes the body in place; it no longer moves, & has infinite mass in collisions
+    pub fn set_frozen(mut self) -> Self {
+        self.frozen = true;
+        self.inv_mass = Fixed::ZERO;
+        self.inv_inertia = Fixed::ZERO;
+        self
+    }
+    pub fn set_rotation(mut self, rotation: Fixed) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Vertices in global space
+    fn globalise(&self) -> Vec<Vector2<Fixed>> {
+        let (sin, cos) = (self.rotation.sin(), self.rotation.cos());
+        self.vertices.iter().map(|v| self.position + v2!(v.x * cos - v.y * sin, v.x * sin + v.y * cos)).collect()
+    }
+}
+
+impl Contactable<Fixed> for FixedBody {
+    fn center(&self) -> Vector2<Fixed> {
+        self.position
+    }
+    fn inv_mass(&self) -> Fixed {
+        self.inv_mass
+    }
+    fn inv_inertia(&self) -> Fixed {
+        self.inv_inertia
+    }
+    fn coefficients(&self) -> (Fixed, Fixed, Fixed) {
+        (self.material.e, self.material.ks, self.material.kd)
+    }
+    fn velocities(&self) -> (Vector2<Fixed>, Fixed) {
+        (self.velocity, self.angular_velocity)
+    }
+    fn motion_mut(&mut self) -> (&mut Vector2<Fixed>, &mut Vector2<Fixed>, &mut Fixed) {
+        (&mut self.position, &mut self.velocity, &mut self.angular_velocity)
+    }
+}
+
+impl LockstepWorld {
+    pub fn new(gravity: Vector2<Fixed>) -> Self {
+        Self { bodies: Vec::new(), gravity, steps: 0 }
+    }
+
+    pub fn add_body(&mut self, body: FixedBody) {
+        self.bodies.push(body);
+    }
+
+    /// Advances the world by dt, in substeps, as the engine does
+    pub fn step(&mut self, dt: Fixed) {
+        let dt = dt / Fixed::from_int(ITERATIONS);
+        let gravity = self.gravity * dt;
+
+        for _ in 0..ITERATIONS {
+            for body in self.bodies.iter_mut().filter(|b| !b.frozen) {
+                body.velocity = body.velocity + gravity;
+                body.position = body.position + body.velocity * dt;
+                body.rotation += body.angular_velocity * dt;
+            }
+
+            for contact in self.detect() {
+                self.resolve(&contact);
+            }
+        }
+
+        self.steps += 1;
+    }
+
+    /// Broad phase on bounding circles, then SAT on each pair that passes; pairs are visited in index order
+    fn detect(&self) -> Vec<Contact> {
+        let vertices: Vec<Vec<Vector2<Fixed>>> = self.bodies.iter().map(|b| b.globalise()).collect();
+        let normals: Vec<Vec<Vector2<Fixed>>> = vertices.iter().map(|v| contact::normals(v)).collect();
+        let mut contacts = Vec::new();
+
+        for i in 0..self.bodies.len() {
+            for j in i + 1..self.bodies.len() {
+                let (b1, b2) = (&self.bodies[i], &self.bodies[j]);
+                if b1.frozen && b2.frozen { continue; }
+
+                let d = b2.position - b1.position;
+                let r = b1.radius + b2.radius;
+                if dot(d, d) >= r * r { continue; }
+
+                if let Some((normal, overlap)) = contact::sat(&vertices[i], &normals[i], &vertices[j], &normals[j]) {
+                    let points = contact::find_contacts(&vertices[i], &vertices[j]);
+                    contacts.push(Contact { bodies: [i, j], normal, overlap, points });
+                }
+            }
+        }
+
+        contacts
+    }
+
+    fn resolve(&mut self, c: &Contact) {
+        let [i, j] = c.bodies;
+        let (head, tail) = self.bodies.split_at_mut(j);
+        contact::resolve(&mut head[i], &mut tail[0], c.normal, c.overlap, &c.points);
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn bodies(&self) -> &Vec<FixedBody> {
+        &self.bodies
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Hash of the world's state, from the raw bits of every body's motion
+    pub fn hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+
+        hash.add(self.steps);
+        for body in &self.bodies {
+            for x in [body.position.x, body.position.y, body.velocity.x, body.velocity.y, body.rotation, body.angular_velocity] {
+                hash.add(x.raw() as u64);
+            }
+        }
+
+        hash.finish()
+    }
+}
+
+/// Steps a stack of boxes & pentagons on a static floor; returns the world's hash at the end
+pub fn run(steps: u64) -> u64 {
+    let mut world = LockstepWorld::new(v2!(Fixed::ZERO, Fixed::ratio(981, 100)));
+    let (rock, one) = (FixedMaterial::ROCK, Fixed::ONE);
+
+    world.add_body(FixedBody::rect(v2!(Fixed::from_int(10), Fixed::ratio(118, 10)), Fixed::from_int(20), Fixed::ratio(2, 5), rock).set_frozen());
+    for row in 0..6 {
+        for column in 0..5 {
+            // Centimetres
+            let center = v2!(Fixed::ratio(650 + column * 180 + row * 14, 100), Fixed::ratio(1050 - row * 140, 100));
+            world.add_body(if (row + column) % 3 == 0 { FixedBody::polygon(center, Fixed::HALF, 5, rock) } else { FixedBody::rect(center, one, one, rock) });
+        }
+    }
+    world.add_body(FixedBody::rect(v2!(Fixed::ratio(13, 5), Fixed::ratio(43, 10)), Fixed::ratio(6, 5), Fixed::ratio(3, 5), rock).set_rotation(Fixed::ratio(3, 10)));
+
+    let dt = Fixed::ratio(1, 60);
+    for _ in 0..steps {
+        world.step(dt);
+    }
+
+    world.hash()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_the_same_everywhere() {
+        // Recorded from this scene; every build, on every machine, must reach the same state
+        assert_eq!(run(DEFAULT_STEPS), 0x146e_7bfa_9265_2699);
+    }
+}
@@ -6,48 +6,103 @@
     * Provides the App struct itself, and manages all internal components of the physics engine
       (rendering, computation, etc.)
     * Manages the 'main loop'
+    * Handles the mouse; left-drag grabs the body under the cursor with a mouse joint (or pans over empty space),
+      right-click spawns the selected shape & material, & middle-click removes the body under the cursor
  */
 /* --------------------- IMPORTS -------------------- */
 // Modules
 pub mod engine;
+pub mod integrator;
 pub mod video;
+pub mod camera;
 pub mod objects;
+pub mod arena;
 pub mod collision;
 pub mod ssm;
+pub mod joints;
+pub mod replay;
+pub mod scene;
+pub mod snapshot;
+pub mod parallel;
+pub mod xpbd;
+pub mod bench;
+#[cfg(feature = "fixed")]
+pub mod fixed;
+#[cfg(feature = "fixed")]
+pub mod lockstep;
 
 // Crates
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::thread;
 use std::time::Duration;
 
 use sdl2::event::Event;
 use sdl2::gfx::primitives::DrawRenderer;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::Sdl;
 use sdl2::video::Window;
 
-use ssm::SystemStateManager;
+use ssm::{Group, SystemStateManager};
 
-use crate::app::engine::Engine;
-use crate::app::objects::Body;
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::camera::Camera;
+use crate::app::collision::CollisionDetector;
+use crate::app::engine::{Engine, Solver, WorldBounds};
+use crate::app::integrator::{Integrator, TForceField};
+use crate::app::joints::Joint;
+use crate::app::objects::{Body, RopeBuilder, SoftBody, VehicleBuilder};
+use crate::app::replay::{Input, Recorder, Recording, ReplayError, Replayer};
+use crate::app::scene::{BodyDesc, Scene, SceneError};
+use crate::app::snapshot::Snapshot;
 use crate::app::video::Video;
-use crate::common::{TSharedRef, Vector2, BodyForm, Materials, Colors};
-use crate::{rect, v2};
+use crate::common::{TCharacterRef, TDespawnListener, TJointListener, TJointRef, TParticlesRef, TRopeRef, TSharedRef, TSoftBodyRef, TVehicleRef, Crd, Disp, Vector2, Vector2M, BodyForm, Material, Materials, Colors, Real};
+use crate::{poly, rect, v2};
 
 /* -------------------- VARIABLES ------------------- */
+const ZOOM_STEP: Real = 1.1; // Zoom factor per notch of the mouse wheel
 
+// Selected with keys 1-4 & 5-8
+const SPAWN_SHAPES: [SpawnShape; 4] = [
+    SpawnShape::Rect { width: 0.6, height: 0.6 },
+    SpawnShape::Polygon { radius: 0.4, sides: 3 },
+    SpawnShape::Polygon { radius: 0.4, sides: 5 },
+    SpawnShape::Rect { width: 1.6, height: 0.3 },
+];
+const SPAWN_MATERIALS: [Material; 4] = [Materials::ROCK, Materials::METAL, Materials::BOUNCY, Materials::RUBBER];
 
 /* ------------------- STRUCTURES ------------------- */
 pub struct App {
     shared: TSharedRef,
 
     fps: u64,
-    delta: f64,
+    delta: Real,
 
     sdl2_ctx: Sdl,
     video: Video,
     engine: Engine,
     system_state_manager: SystemStateManager,
+    bounds: Vec<BodyHandle>,      // Window boundaries; not part of saved scenes
+    vehicle: Option<TVehicleRef>, // Vehicle driven by the arrow keys
+
+    // Record & replay
+    recorder: Option<(Recorder, PathBuf)>, // Written to the path on quit
+    replayer: Option<Replayer>,
+
+    // Mouse
+    dragged: Option<TJointRef>, // Mouse joint of the body being dragged
+    spawn_shape: SpawnShape,    // Spawned by right-clicking
+    spawn_material: Material,
+}
+
+/// Shape spawned at the cursor by right-clicking; sizes in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpawnShape {
+    Rect { width: Real, height: Real },
+    Polygon { radius: Real, sides: u32 },
 }
 
 /* -------------------- FUNCTIONS ------------------- */
@@ -59,41 +114,57 @@ impl App {
         let engine = Engine::new(video.shared.clone());
 
         let fps = 60;
-        let delta = 1.0 / fps as f64;
+        let delta = 1.0 / fps as Real;
 
         let mut ssm = SystemStateManager::new();
-        // Initialize window boundaries
+        let bounds = Self::add_bounds(&mut ssm, video.world_size());
+
+        App {
+            shared: video.shared.clone(),
+            
+            fps,
+            delta,
+
+            sdl2_ctx,
+            video,
+            engine,
+            system_state_manager: ssm,
+            bounds,
+            vehicle: None,
+
+            // Record & replay
+            recorder: None,
+            replayer: None,
+
+            // Mouse
+            dragged: None,
+            spawn_shape: SPAWN_SHAPES[0],
+            spawn_material: SPAWN_MATERIALS[0],
+        }
+    }
+
+    /// Initialize window boundaries, around the given metres of world
+    fn add_bounds(ssm: &mut SystemStateManager, size: Vector2<Crd>) -> Vec<BodyHandle> {
+        let (width, height) = (size.x, size.y);
         let bodies: Vec<Body> = vec![
-            rect!(v2!(-8, 0), 10, height)
+            rect!(v2!(-0.16, 0.0), 0.2, height)
                 .set_frozen(true)
                 .set_collision_group(-1)
                 .set_ignore_groups(vec![-1]),
-            rect!(v2!(width as i32 - 2 , 0), 10, height)
+            rect!(v2!(width - 0.04, 0.0), 0.2, height)
                 .set_frozen(true)
                 .set_collision_group(-1)
                 .set_ignore_groups(vec![-1]),
-            rect!(v2!(0, -8), width, 10)
+            rect!(v2!(0.0, -0.16), width, 0.2)
                 .set_frozen(true)
                 .set_collision_group(-1)
                 .set_ignore_groups(vec![-1]),
-            rect!(v2!(0, height as i32 - 2), width, 10)
+            rect!(v2!(0.0, height - 0.04), width, 0.2)
                 .set_frozen(true)
                 .set_collision_group(-1)
                 .set_ignore_groups(vec![-1]),
         ];
-        ssm.add_bodies(bodies);
-
-        App {
-            shared: video.shared.clone(),
-            
-            fps,
-            delta,
-
-            sdl2_ctx,
-            video,
-            engine,
-            system_state_manager: ssm,
-        }
+        ssm.add_bodies(bodies)
     }
 
     pub fn start(&mut self) {
@@ -106,37 +177,114 @@ impl App {
         let mut event_pump = self.sdl2_ctx.event_pump().unwrap();
         let mut stepped: bool = false;
 
-        let mut window_size = self.shared.borrow_mut().window_size;
+        let mut window_size = self.window().size();
+        let mut mouse: Vector2<Disp> = v2!(0, 0);
 
         'main_loop: loop {
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. } |
                     Event::KeyDown { keycode: Some(Keycode::Escape), .. } => { break 'main_loop; }
+                    Event::KeyDown { keycode: Some(key), .. } => { self.select(key); }
+
+                    // Camera; the wheel zooms about the cursor, & dragging empty space pans
+                    Event::MouseMotion { x, y, xrel, yrel, mousestate, .. } => {
+                        mouse = v2!(x, y);
+                        if mousestate.left() && self.dragged.is_none() { self.video.camera.pan(v2!(xrel, yrel)); }
+                    }
+                    Event::MouseWheel { y, .. } => { self.video.camera.zoom_at(mouse, ZOOM_STEP.powi(y)); }
+
+                    // Bodies; grab, spawn & remove
+                    Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                        mouse = v2!(x, y);
+                        match mouse_btn {
+                            MouseButton::Left => self.grab(mouse),
+                            MouseButton::Right => self.spawn_at(mouse),
+                            MouseButton::Middle => self.remove_at(mouse),
+                            _ => {}
+                        }
+                    }
+                    Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => { self.release(); }
                     _ => {}
                 }
             }
 
+            // Drive the vehicle; right/left throttle forwards/backwards, down brakes. Replays drive it themselves
+            if let (Some(vehicle_ref), None) = (&self.vehicle, &self.replayer) {
+                let keys = event_pump.keyboard_state();
+                let mut vehicle = vehicle_ref.borrow_mut();
+                let drive = (vehicle.throttle, vehicle.brake);
+
+                let forwards = keys.is_scancode_pressed(Scancode::Right) as i32 as Real;
+                let backwards = keys.is_scancode_pressed(Scancode::Left) as i32 as Real;
+                vehicle.set_throttle(forwards - backwards);
+                vehicle.set_brake(keys.is_scancode_pressed(Scancode::Down));
+
+                // Record changes in drive as the axles' new motors
+                if let Some((recorder, _)) = &mut self.recorder {
+                    if drive != (vehicle.throttle, vehicle.brake) {
+                        for axle in &vehicle.axles {
+                            // Axles removed with their wheels no longer drive anything
+                            let Some(joint) = self.system_state_manager.joints().iter().position(|j| Rc::ptr_eq(j, axle)) else { continue; };
+                            recorder.record(Input::Motor { joint, motor: axle.borrow().motor });
+                        }
+                    }
+                }
+            }
+
+            // The dragged body is pulled towards the cursor, which moves with the camera too
+            self.drag_to(mouse);
+
         // Update window size
-        let cur_window_size = v2!(self.window().size().0, self.window().size().1);
+        let cur_window_size = self.window().size();
         if window_size != cur_window_size {
             window_size = cur_window_size;
-            self.shared.borrow_mut().window_size = cur_window_size;
+            self.shared.borrow_mut().world_size = self.video.world_size();
+            self.video.camera.set_viewport(v2!(cur_window_size.0, cur_window_size.1));
         }
 
             stepped = true;
 
             // Update physics
-            self.engine.step(self.system_state_manager.bodies(), self.delta);
+            if let Some(replayer) = &mut self.replayer {
+                if let Err(e) = replayer.step(&mut self.system_state_manager, &mut self.engine) {
+                    eprintln!("replay stopped; {e}");
+                    self.replayer = None;
+                } else if replayer.finished() {
+                    match replayer.divergence() {
+                        Some(divergence) => eprintln!("{divergence}"),
+                        None => println!("replay matched the recording for all {} steps", self.engine.steps()),
+                    }
+                    self.replayer = None;
+                }
+            } else if let Some((recorder, _)) = &mut self.recorder {
+                recorder.step(&mut self.system_state_manager, &mut self.engine);
+            } else {
+                self.engine.step(&mut self.system_state_manager, self.delta);
+            }
 
-            self.video.pre_draw();
+            self.video.camera.update(self.delta, self.system_state_manager.bodies());
+            self.video.pre_draw(self.system_state_manager.bodies());
 
             // Draw objects in world collection
-            for body_ref in self.system_state_manager.bodies() {
-                // println!("i={:?}, s={:?}", body_ref.borrow().inertia.clone(), body_ref.borrow().sides.clone());
+            for (_, body) in self.system_state_manager.bodies().iter() {
+                // println!("i={:?}, s={:?}", body.inertia, body.sides);
+                if !body.visible { continue; }
+
+                self.video.draw_body(&body);
+            }
+            for soft_ref in self.system_state_manager.soft_bodies() {
+                self.video.draw_soft_body(soft_ref);
+            }
+            for rope_ref in self.system_state_manager.ropes() {
+                self.video.draw_rope(rope_ref);
+            }
+            for joint_ref in self.system_state_manager.joints() {
+                if !joint_ref.borrow().visible { continue; }
 
-                self.video.draw_body(body_ref);
+                self.video.draw_joint(joint_ref, self.system_state_manager.bodies());
             }
+            self.video.draw_particles(self.system_state_manager.particles());
 
             // Apply changes
             self.video.canvas.set_draw_color(Colors::BG); // Background color
@@ -150,18 +298,330 @@ impl App {
             thread::sleep(Duration::from_millis(1000/self.fps));
             // thread::sleep(Duration::from_millis(1000));
         }
+
+        if let Some((recorder, path)) = &self.recorder {
+            if let Err(e) = recorder.recording().write(path) {
+                eprintln!("{}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Replaces the world with the scene in the given file; the world is left untouched if the scene is invalid
+    pub fn load_scene(&mut self, path: impl AsRef<Path>) -> Result<Vec<BodyHandle>, SceneError> {
+        let bodies = self.build_world(&Scene::read(path)?)?;
+        self.vehicle = None;
+        Ok(bodies)
+    }
+
+    /// Replaces the world with the window boundaries & the given scene; the world is left untouched if it is invalid
+    fn build_world(&mut self, scene: &Scene) -> Result<Vec<BodyHandle>, SceneError> {
+        let size = self.shared.borrow().world_size;
+
+        let mut ssm = SystemStateManager::new();
+        let bounds = Self::add_bounds(&mut ssm, size);
+        let bodies = scene.build(&mut ssm, &mut self.engine)?;
+
+        self.system_state_manager = ssm;
+        self.bounds = bounds;
+        self.dragged = None;
+        Ok(bodies)
+    }
+
+    /// Saves the world's bodies, joints & engine settings to the given file
+    pub fn save_scene(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        Scene::capture(&self.system_state_manager, &self.engine, &self.bounds)?.write(path)
+    }
+
+    /// Saves the group's bodies & joints, and the engine settings, to the given file; loading it puts them back
+    /// under the same paths
+    pub fn save_group(&self, group: &Group, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        Scene::capture_group(&self.system_state_manager, &self.engine, group)?.write(path)
+    }
+
+    /// Captures the state of every object & the simulation clock
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::capture(&self.system_state_manager, &self.engine)
+    }
+
+    /// Brings the world back to the state it was in when the snapshot was taken
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        snapshot.restore(&mut self.system_state_manager, &mut self.engine);
+    }
+
+    /// Starts recording the session, written to the given file on quit. The world is first rebuilt from its own scene,
+    /// so that replays start from exactly the same state.
+    pub fn record(&mut self, path: impl AsRef<Path>, seed: u64) -> Result<(), SceneError> {
+        let scene = Scene::capture(&self.system_state_manager, &self.engine, &self.bounds)?;
+        let old = self.system_state_manager.clone();
+        self.build_world(&scene)?;
+
+        // Keep driving the same vehicle, now made of the rebuilt bodies & joints
+        if let Some(vehicle_ref) = &self.vehicle {
+            vehicle_ref.borrow_mut().remap(&old, &self.system_state_manager);
+        }
+
+        let size = self.shared.borrow().world_size;
+        let recorder = Recorder::new(scene, seed, self.delta, size, &mut self.engine);
+        self.recorder = Some((recorder, path.as_ref().to_path_buf()));
+        self.replayer = None;
+        Ok(())
+    }
+
+    /// Replaces the world with the recording's starting scene, and replays its inputs. The state is checked against
+    /// the recording every step; the first divergence, if any, is reported once the replay ends.
+    pub fn replay(&mut self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let recording = Recording::read(path)?;
+
+        let size = self.shared.borrow().world_size;
+        if recording.world_size != size {
+            return Err(ReplayError::WorldSize { expected: recording.world_size, found: size });
+        }
+
+        self.build_world(&recording.scene).map_err(ReplayError::Scene)?;
+        self.vehicle = None;
+        self.recorder = None;
+        self.replayer = Some(Replayer::new(recording, &mut self.engine));
+        Ok(())
+    }
+
+    /// Applies an external input to the world; while recording, it is recorded too
+    pub fn apply_input(&mut self, input: Input) -> Result<(), ReplayError> {
+        match &mut self.recorder {
+            Some((recorder, _)) => recorder.apply(input, &mut self.system_state_manager, &mut self.engine),
+            None => {
+                let step = self.engine.steps();
+                input.apply(&mut self.system_state_manager, &mut self.engine, step)
+            }
+        }
+    }
+
+    /// Grabs the moving body under the pixel with a mouse joint. Replays drive the world themselves, so the mouse
+    /// only moves the camera while replaying
+    fn grab(&mut self, pixel: Vector2<Disp>) {
+        if self.replayer.is_some() { return; }
+        let point = self.video.to_world(pixel);
+        let Some(body) = CollisionDetector::point_query(point, self.bodies(), &[]) else { return; };
+        if self.bodies().body(body).frozen { return; }
+
+        if let Err(e) = self.apply_input(Input::Grab { body, point }) {
+            eprintln!("{e}");
+            return;
+        }
+        self.dragged = self.system_state_manager.joints().last().cloned();
+    }
+
+    /// Moves the dragged body's target to the world point under the pixel
+    fn drag_to(&mut self, pixel: Vector2<Disp>) {
+        let Some(joint_ref) = self.dragged.clone() else { return; };
+        // The joint goes with its body, e.g. if it despawns
+        let Some(joint) = self.joint_index(&joint_ref) else {
+            self.dragged = None;
+            return;
+        };
+
+        let target = self.video.to_world(pixel);
+        if joint_ref.borrow().ground[0] != target {
+            self.input(Input::Drag { joint, target });
+        }
+    }
+
+    /// Lets go of the dragged body
+    fn release(&mut self) {
+        let Some(joint_ref) = self.dragged.take() else { return; };
+        let Some(joint) = self.joint_index(&joint_ref) else { return; };
+        self.input(Input::Release { joint });
+    }
+
+    /// Spawns the selected shape & material, centred on the world point under the pixel
+    fn spawn_at(&mut self, pixel: Vector2<Disp>) {
+        if self.replayer.is_some() { return; }
+        let mut body = match self.spawn_shape {
+            SpawnShape::Rect { width, height } => rect!(v2!(0.0), width, height, self.spawn_material),
+            SpawnShape::Polygon { radius, sides } => poly!(v2!(0.0), radius, sides, self.spawn_material),
+        };
+        body.position = self.video.to_world(pixel) - body.origin;
+        body.prev_position = body.position;
+
+        self.input(Input::Spawn(Box::new(BodyDesc::of(body))));
+    }
+
+    /// Removes the body under the pixel; the window boundaries stay
+    fn remove_at(&mut self, pixel: Vector2<Disp>) {
+        if self.replayer.is_some() { return; }
+        let point = self.video.to_world(pixel);
+        let Some(body) = CollisionDetector::point_query(point, self.bodies(), &[]) else { return; };
+        if self.bounds.contains(&body) { return; }
+
+        self.input(Input::Remove { body });
+    }
+
+    /// Selects the spawned shape with keys 1-4, & its material with keys 5-8
+    fn select(&mut self, key: Keycode) {
+        let shapes = [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4];
+        let materials = [Keycode::Num5, Keycode::Num6, Keycode::Num7, Keycode::Num8];
+
+        if let Some(i) = shapes.iter().position(|&k| k == key) {
+            self.spawn_shape = SPAWN_SHAPES[i];
+        } else if let Some(i) = materials.iter().position(|&k| k == key) {
+            self.spawn_material = SPAWN_MATERIALS[i];
+        }
+    }
+
+    /// Applies an input made through the window; it can't be applied if the world changed under it, which is reported
+    fn input(&mut self, input: Input) {
+        if let Err(e) = self.apply_input(input) {
+            eprintln!("{e}");
+        }
     }
 
     /* --------------------- GETTERS -------------------- */
     fn window(&self) -> &Window {
         self.video.canvas.window()
     }
+    /// The group at the given path, e.g. `level1/bridge`
+    pub fn group(&self, path: &str) -> Option<&Group> {
+        self.system_state_manager.group(path)
+    }
+    /// The body at the given path, e.g. `level1/bridge/plank3`
+    pub fn body(&self, path: &str) -> Option<BodyHandle> {
+        self.system_state_manager.body(path)
+    }
+    pub fn bodies(&self) -> &BodyArena {
+        self.system_state_manager.bodies()
+    }
+    pub fn bodies_mut(&mut self) -> &mut BodyArena {
+        self.system_state_manager.bodies_mut()
+    }
+    pub fn joint(&self, path: &str) -> Option<TJointRef> {
+        self.system_state_manager.joint(path)
+    }
+    /// Index of the joint in the system, as inputs refer to joints
+    fn joint_index(&self, joint_ref: &TJointRef) -> Option<usize> {
+        self.system_state_manager.joints().iter().position(|j| Rc::ptr_eq(j, joint_ref))
+    }
+    pub fn camera(&self) -> &Camera {
+        &self.video.camera
+    }
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.video.camera
+    }
 
     /* --------------------- SETTERS -------------------- */
-    pub fn add_body(&mut self, body: Body) {
-        self.system_state_manager.add_body(body);
+    pub fn add_body(&mut self, body: Body) -> BodyHandle {
+        self.system_state_manager.add_body(body)
+    }
+    pub fn add_bodies(&mut self, bodies: Vec<Body>) -> Vec<BodyHandle> {
+        self.system_state_manager.add_bodies(bodies)
+    }
+    pub fn add_joint(&mut self, joint: Joint) -> TJointRef {
+        self.system_state_manager.add_joint(joint)
+    }
+    /// Adds the body under the given path, e.g. `level1/bridge/plank3`; missing groups along the path are created
+    pub fn add_named_body(&mut self, path: &str, body: Body) -> BodyHandle {
+        self.system_state_manager.add_named_body(path, body)
+    }
+    pub fn add_named_joint(&mut self, path: &str, joint: Joint) -> TJointRef {
+        self.system_state_manager.add_named_joint(path, joint)
+    }
+    /// Removes the body, with the joints & characters attached to it; None if the handle is stale
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Body> {
+        let body = self.system_state_manager.remove_body(handle)?;
+        self.engine.forget_body(handle);
+        Some(body)
+    }
+    /// Removes the group at the given path, with everything in or attached to it
+    pub fn remove_group(&mut self, path: &str) -> Option<Group> {
+        let group = self.system_state_manager.remove_group(path)?;
+        for handle in group.all_bodies() {
+            self.engine.forget_body(handle);
+        }
+        Some(group)
+    }
+    /// Enables or disables everything in the group at the given path; false if there is no such group
+    pub fn set_group_enabled(&mut self, path: &str, enabled: bool) -> bool {
+        self.system_state_manager.set_group_enabled(path, enabled)
+    }
+    pub fn set_group_visible(&mut self, path: &str, visible: bool) -> bool {
+        self.system_state_manager.set_group_visible(path, visible)
+    }
+    pub fn set_group_frozen(&mut self, path: &str, frozen: bool) -> bool {
+        self.system_state_manager.set_group_frozen(path, frozen)
+    }
+    pub fn translate_group(&mut self, path: &str, d: Vector2<Real>) -> bool {
+        self.system_state_manager.translate_group(path, d)
+    }
+    pub fn add_soft_body(&mut self, soft_body: SoftBody) -> TSoftBodyRef {
+        self.system_state_manager.add_soft_body(soft_body)
+    }
+    /// Adds a lightweight verlet rope, built from the given builder
+    pub fn add_rope(&mut self, builder: RopeBuilder) -> TRopeRef {
+        let rope = builder.build_verlet(self.system_state_manager.bodies());
+        self.system_state_manager.add_rope(rope)
+    }
+    /// Adds a chain of linked bodies, built from the given builder
+    pub fn add_chain(&mut self, builder: RopeBuilder) -> (Vec<BodyHandle>, Vec<TJointRef>) {
+        self.system_state_manager.add_chain(builder)
+    }
+    /// Adds a body moved by a kinematic character controller
+    pub fn add_character(&mut self, body: Body) -> TCharacterRef {
+        self.system_state_manager.add_character(body)
+    }
+    /// Spawns a vehicle from the given builder; the latest vehicle added is driven with the arrow keys
+    pub fn add_vehicle(&mut self, builder: VehicleBuilder) -> TVehicleRef {
+        let vehicle_ref: TVehicleRef = Rc::from(RefCell::from(builder.build(&mut self.system_state_manager)));
+        self.vehicle = Some(vehicle_ref.clone());
+        vehicle_ref
+    }
+    pub fn particles(&self) -> TParticlesRef {
+        self.system_state_manager.particles().clone()
+    }
+    /// Keeps the camera on the body, following it smoothly; None stops following
+    pub fn follow(&mut self, body: Option<BodyHandle>) {
+        self.video.camera.follow(body);
+    }
+    /// Sets the scale the world is drawn at; the window boundaries move to the edges of the world now in view
+    pub fn set_pixels_per_metre(&mut self, pixels_per_metre: Real) {
+        self.video.set_pixels_per_metre(pixels_per_metre);
+
+        for handle in std::mem::take(&mut self.bounds) {
+            self.remove_body(handle);
+        }
+        self.bounds = Self::add_bounds(&mut self.system_state_manager, self.video.world_size());
+    }
+    /// Sets the shape spawned by right-clicking; keys 1-4 pick from a few presets
+    pub fn set_spawn_shape(&mut self, shape: SpawnShape) {
+        self.spawn_shape = shape;
+    }
+    /// Sets the material of spawned bodies; keys 5-8 pick rock, metal, bouncy or rubber
+    pub fn set_spawn_material(&mut self, material: Material) {
+        self.spawn_material = material;
+    }
+    pub fn set_solver(&mut self, solver: Solver) {
+        self.engine.set_solver(solver);
+    }
+    pub fn set_gravity(&mut self, gravity: Vector2M<Real>) {
+        self.engine.set_gravity(gravity);
+    }
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.engine.set_integrator(integrator);
+    }
+    /// Bodies which leave the given region entirely are despawned; None keeps every body
+    pub fn set_world_bounds(&mut self, bounds: Option<WorldBounds>) {
+        self.engine.set_world_bounds(bounds);
+    }
+    /// Sets the number of worker threads the engine uses; 1 runs everything on the main thread
+    pub fn set_threads(&mut self, threads: usize) {
+        self.engine.set_threads(threads);
+    }
+    pub fn add_force_field(&mut self, field: TForceField) {
+        self.engine.add_force_field(field);
+    }
+    /// Registers a callback, invoked with every joint that breaks
+    pub fn add_break_listener(&mut self, listener: TJointListener) {
+        self.engine.add_break_listener(listener);
     }
-    pub fn add_bodies(&mut self, bodies: Vec<Body>) {
-        self.system_state_manager.add_bodies(bodies);
+    /// Registers a callback, invoked with every body that despawns
+    pub fn add_despawn_listener(&mut self, listener: TDespawnListener) {
+        self.engine.add_despawn_listener(listener);
     }
 }
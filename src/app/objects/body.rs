@@ -4,12 +4,23 @@
     Description:Description
     * Provides struct for all physical objects that are rendered in the simulation
     * Internally tracks properties (position, velocity, etc.)
+    * A body's motion is kept apart from the rest of its data (BodyData); once added to a BodyArena, its motion
+      lives in the arena's per-field arrays, and the body is reached through BodyRef & BodyMut views
+    * Geometry shared by bodies & views is provided by the BodyView trait; points & vectors are moved between a
+      body's local space & world space by its Transform, which the arena stores alongside the body's motion
+    * A body's world-space Geometry (vertices, edge normals & bounding box) is cached by the arena, & only redone
+      once the body has moved
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
-use std::f64::consts::PI;
+use crate::common::consts::PI;
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
 
-use crate::common::{AABB, BodyForm, ConvertPrimitives, Disp, Crd, Vector2, Vertex, Material, Materials};
+use serde::{Deserialize, Serialize};
+
+use crate::app::collision::contact;
+use crate::common::{AABB, BodyForm, ConvertPrimitives, Crd, Vector2, Vertex, Material, Materials, Real, Rot, Transform};
 use crate::{v2, vtx};
 
 /* -------------------- VARIABLES ------------------- */
@@ -17,68 +28,246 @@ use crate::{v2, vtx};
 
 /* ------------------- STRUCTURES ------------------- */
 /// Internal struct for defining & updating bodies (any object which has a physical presence in the simulation).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Body {
+    // Motion
+    pub position: Vector2<Crd>,
+    pub rotation: Real,
+    pub velocity: Vector2<Real>,
+    pub angular_velocity: Real,
+    pub torque: Real,
+    pub force_buffer: Vector2<Real>,
+
+    // Solver
+    pub prev_position: Vector2<Crd>, // State at the start of the current substep; used by position-based solvers
+    pub prev_rotation: Real,
+
+    pub data: BodyData,
+}
+
+/// Everything about a body besides its motion; shape, mass properties, material & flags.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BodyData {
     // Internal
     pub form: BodyForm,
-    pub position: Vector2<Crd>,
-    pub rotation: f64,
     pub origin: Vector2<Crd>,
-    pub radius: Option<f64>,
+    pub radius: Option<Real>,
 
     // BodyForm::Polygon
     pub sides: u32,
     pub vertices: Vec<Vertex>,
-    pub width: Option<u32>,
-    pub height: Option<u32>,
+    pub width: Option<Real>,
+    pub height: Option<Real>,
 
     // Physics
-    pub mass: f64, // Mass of the object, exerted at it's center of mass
+    pub mass: Real, // Mass of the object, exerted at it's center of mass
     pub material: Material,
     pub frozen: bool, // Whether the body's forces shouldn't be updated at the physics step
-    pub velocity: Vector2<f64>,
-    pub angular_velocity: f64,
-    pub inertia: f64,
-    pub torque: f64,
-    pub force_buffer: Vector2<f64>,
+    pub inertia: Real,
 
     // Meta
     pub collision_group: i32,
     pub ignore_groups: Vec<i32>,
+    pub enabled: bool, // Whether the body takes part in the simulation
+    pub visible: bool,
+    pub lifetime: Option<Real>, // Simulated seconds left before the body despawns; None lives forever
+}
+
+/// Snapshot of a body's motion.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Motion {
+    pub position: Vector2<Crd>,
+    pub rotation: Real,
+    pub velocity: Vector2<Real>,
+    pub angular_velocity: Real,
+    pub prev_position: Vector2<Crd>,
+    pub prev_rotation: Real,
+}
+
+/// How the engine treats a body; derived from its data.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Mode {
+    #[default]
+    Disabled,
+    Frozen,
+    Moving,
+}
+
+/// Shape of a body in world space.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vector2<Real>>,
+    pub normals: Vec<Vector2<Real>>, // Unit normal of the edge from each vertex to the next
+    pub aabb: AABB,
+}
+
+/// Read-only view of a body; the motion is copied out.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyRef<'a> {
+    pub position: Vector2<Crd>,
+    pub rotation: Real,
+    pub velocity: Vector2<Real>,
+    pub angular_velocity: Real,
+    pub prev_position: Vector2<Crd>,
+    pub prev_rotation: Real,
+    pub transform: Transform,
+    pub geometry: Option<&'a Geometry>, // Cached by the arena; None if the body has moved since
+    pub data: &'a BodyData,
+}
+
+/// Mutable view of a body; the motion is copied out, and written back when the view is dropped.
+#[derive(Debug)]
+pub struct BodyMut<'a> {
+    pub position: Vector2<Crd>,
+    pub rotation: Real,
+    pub velocity: Vector2<Real>,
+    pub angular_velocity: Real,
+    pub prev_position: Vector2<Crd>,
+    pub prev_rotation: Real,
+    data: DataMut<'a>,
+    target: MotionMut<'a>,
+}
+
+/// Data of a BodyMut; shared data is copied on its first write, so the write never reaches the body.
+#[derive(Debug)]
+enum DataMut<'a> {
+    Unique(&'a mut BodyData),
+    Shared(Cow<'a, BodyData>),
 }
 
+/// Where a BodyMut writes its motion back to.
+#[derive(Debug)]
+pub struct MotionMut<'a> {
+    pub position: &'a mut Vector2<Crd>,
+    pub rotation: &'a mut Real,
+    pub velocity: &'a mut Vector2<Real>,
+    pub angular_velocity: &'a mut Real,
+    pub prev_position: &'a mut Vector2<Crd>,
+    pub prev_rotation: &'a mut Real,
+    pub derived: Option<Derived<'a>>, // Kept in step with the body's data
+}
+
+/// Values derived from a body's data & motion, stored alongside its motion.
+#[derive(Debug)]
+pub struct Derived<'a> {
+    pub inv_mass: &'a mut Real,
+    pub inv_inertia: &'a mut Real,
+    pub mode: &'a mut Mode,
+    pub transform: &'a mut Transform,
+    pub stale: &'a mut bool, // Whether the cached geometry is out of date
+}
 
 /* -------------------- FUNCTIONS ------------------- */
+/// Geometry of a body, or of a view of one.
+pub trait BodyView: Deref<Target = BodyData> {
+    fn motion(&self) -> Motion;
+    /// Maps the body's local space (about its origin, the center of mass) into world space
+    fn transform(&self) -> Transform;
+
+    /// Convert a local point into world space
+    fn world_point(&self, v: Vector2<Crd>) -> Vector2<Crd> {
+        self.transform().world_point(v)
+    }
+    /// Convert a world point into local space; inverse of `world_point`
+    fn local_point(&self, v: Vector2<Crd>) -> Vector2<Crd> {
+        self.transform().local_point(v)
+    }
+    /// Rotate a local direction into world space
+    fn world_vector(&self, v: Vector2<Real>) -> Vector2<Real> {
+        self.transform().world_vector(v)
+    }
+    /// Rotate a world direction into local space
+    fn local_vector(&self, v: Vector2<Real>) -> Vector2<Real> {
+        self.transform().local_vector(v)
+    }
+
+    /// Velocity of a point offset by r from the center of mass
+    fn point_velocity(&self, r: Vector2<Real>) -> Vector2<Real> {
+        let Motion { velocity, angular_velocity, .. } = self.motion();
+        velocity + v2!(-r.y, r.x) * angular_velocity
+    }
+
+    /// How far the point offset by r from the center of mass has moved since the start of the substep
+    fn point_displacement(&self, r: Vector2<Real>) -> Vector2<Real> {
+        let Motion { position, rotation, prev_position, prev_rotation, .. } = self.motion();
+        let prev_r = Rot::new(prev_rotation - rotation).rotate(r);
+
+        (position - prev_position) + (r - prev_r)
+    }
+
+    /// Inverse mass of the body as seen along direction n at offset r from the center of mass
+    fn generalised_inv_mass(&self, r: Vector2<Real>, n: Vector2<Real>) -> Real {
+        let rn = Vector2::cross(r, n);
+        self.inv_mass() + rn * rn * self.inv_inertia()
+    }
+
+    /// The body's world-space shape; cached by the arena, or worked out if there is no up-to-date copy
+    fn geometry(&self) -> Cow<'_, Geometry> {
+        let mut geometry = Geometry::default();
+        self.write_geometry(&mut geometry);
+        Cow::Owned(geometry)
+    }
+
+    /// Works out the body's world-space shape, reusing the given geometry's buffers
+    fn write_geometry(&self, geometry: &mut Geometry) {
+        let transform = self.transform();
+        let sides = self.sides as usize;
+
+        geometry.vertices.clear();
+        geometry.vertices.extend(self.vertices[..sides].iter().map(|v| transform.world_point(v.to_vec2())));
+
+        geometry.normals.clear();
+        for i in 0..sides {
+            let edge = geometry.vertices[if i + 1 == sides { 0 } else { i + 1 }] - geometry.vertices[i];
+            geometry.normals.push(v2!(-edge.y, edge.x).norm());
+        }
+
+        geometry.aabb = match (self.form, self.radius) {
+            (BodyForm::Circle, Some(r)) => {
+                // About the geometric center; differs from the origin if the center of mass was moved
+                let c = self.vertices.iter().fold(v2!(0.0), |c, v| c + v.to_vec2()) / self.vertices.len().max(1) as Crd;
+                AABB::from_circle(transform.world_point(c), r)
+            }
+            _ => AABB::from_points(&geometry.vertices),
+        };
+    }
+
+    fn center(&self) -> Vector2<Real> {
+        // Bodies rotate about their origin, which is also their center of mass
+        self.motion().position + self.origin
+    }
+}
+
 impl Body {
-    /// Constructor for the Body struct.
+    /// Constructor for the Body struct. Lengths are in metres.
     pub fn new(
-        form: BodyForm, position: Vector2<Disp>, radius: Option<f64>, // Internal properties
-        sides: u32, width: Option<u32>, height: Option<u32>,          // Polygonal properties
-        material: Material,                                           // Physics properties
+        form: BodyForm, position: Vector2<Crd>, radius: Option<Real>, // Internal properties
+        sides: u32, width: Option<Real>, height: Option<Real>,       // Polygonal properties
+        material: Material,                                          // Physics properties
     ) -> Self {
         let origin: Vector2<Crd>;
         let vertices: Vec<Vertex>;
-        let inertia: f64;
-        let mass: f64;
+        let inertia: Real;
+        let mass: Real;
 
         if sides == 4 && width.is_some() && height.is_some() { // If the body is a rect-like
-            let width = width.unwrap_or(1)   as Crd;
-            let height = height.unwrap_or(1) as Crd;
+            let width = width.unwrap_or(1.0);
+            let height = height.unwrap_or(1.0);
 
-            // mass = material.density * width * height;
-            mass = material.density * 1f64;
-            origin = v2!(0).to();
+            mass = material.density * width * height;
+            origin = v2!(width / 2.0, height / 2.0);
             inertia = (1.0 / 12.0) * mass * (width * width + height * height);
 
-            // Manually define initial vertex positions
+            // Manually define initial vertex positions, about the center so that rect-likes rotate about it
+            let (w, h) = (width / 2.0, height / 2.0);
             vertices = vec![
-                vtx!(0, 0.0, 0.0     ),
-                vtx!(1, 0.0, height  ),
-                vtx!(2, width, height),
-                vtx!(3, width, 0.0   ),
+                vtx!(0, -w, -h),
+                vtx!(1, -w, h ),
+                vtx!(2, w, h  ),
+                vtx!(3, w, -h ),
             ];
         } else { // Standard polygon formation
-            let radius = radius.unwrap_or(1f64);
+            let radius = radius.unwrap_or(1.0);
 
             origin = Vector2::from(radius).to();
             vertices = Body::calculate_vertices(sides, radius);
@@ -86,68 +275,56 @@ impl Body {
             inertia = Self::calculate_inertia(vertices.clone(), mass);
         }
 
-        println!("mass={mass}");
-
         Body {
-            // Internal
-            form,
-            position: position.to(),
+            // Motion
+            position,
             rotation: 0.0,
-            origin,
-            radius,
-
-            // BodyForm::Polygon
-            sides,
-            vertices,
-            width,
-            height,
-
-            // Physics
-            mass,
-            material,
-            frozen: false,
             velocity: v2!(0.0),
             angular_velocity: 0.0,
-            inertia,
             torque: 0.0,
             force_buffer: v2!(0.0),
 
-            // Meta
-            collision_group: 0,
-            ignore_groups: vec![],
+            // Solver
+            prev_position: position,
+            prev_rotation: 0.0,
+
+            data: BodyData {
+                // Internal
+                form,
+                origin,
+                radius,
+
+                // BodyForm::Polygon
+                sides,
+                vertices,
+                width,
+                height,
+
+                // Physics
+                mass,
+                material,
+                frozen: false,
+                inertia,
+
+                // Meta
+                collision_group: 0,
+                ignore_groups: vec![],
+                enabled: true,
+                visible: true,
+                lifetime: None,
+            },
         }
     }
 
-    /// Physics update for the body. Called every frame.
-    pub fn update(&mut self, dt: f64) {
-        if self.frozen { return; }
-
-        self.velocity = self.velocity + self.force_buffer * dt;
-        self.angular_velocity = self.angular_velocity + self.torque * self.inv_inertia() * dt;
-
-        self.position = self.position + self.velocity * dt;
-        self.rotation = self.rotation + self.angular_velocity * dt;
-
-        // println!("rotation={}", self.rotation);
-        self.force_buffer = v2!(0.0);
-        self.torque = 0.0;
-    }
-
-    /// Evaluates whether the given Body object is a rect-like.
-    /// Checks if it has 4 sides, and has its width & height properties defined.
-    pub fn is_rect(&self) -> bool {
-        self.form == BodyForm::Polygon && self.sides == 4 && self.width.is_some() && self.height.is_some()
-    }
-
     /// Internal method for calculating the initial vertex position of a polygon.
-    fn calculate_vertices(sides: u32, radius: f64) -> Vec<Vertex> {
-        let a = (2f64 * PI) / sides as f64;
+    fn calculate_vertices(sides: u32, radius: Real) -> Vec<Vertex> {
+        let a = (2.0 * PI) / sides as Real;
         let mut vertices = Vec::new();
 
         // Optimization for when number of sides is even
         if sides % 2 == 0 {
             for i in 1..=(sides - 2) / 2 {
-                let j = i as f64;
+                let j = i as Real;
                 let x = (radius * (a * j).cos()) as Crd;
                 let y = (radius * (a * j).sin()) as Crd;
 
@@ -161,7 +338,7 @@ impl Body {
         // Optimization for when number of sides is odd
         else {
             for i in 1..=(sides - 1) / 2 {
-                let j = i as f64;
+                let j = i as Real;
                 let x = radius * (a * j).cos() as Crd;
                 let y = radius * (a * j).sin() as Crd;
 
@@ -176,132 +353,376 @@ impl Body {
         vertices
     }
 
-    /// Internal method for computing the area of a polygon
-    fn calculate_area(vertices: Vec<Vertex>) -> f64 {
-        let x: Vec<f64> = vertices.iter().map(|vtx| vtx.to_vec2().to().x).collect();
-        let y: Vec<f64> = vertices.iter().map(|vtx| vtx.to_vec2().to().y).collect();
+    /// Internal method for computing the area of a polygon (shoelace formula)
+    fn calculate_area(vertices: Vec<Vertex>) -> Real {
+        let mut area: Real = 0.0;
 
-        let mut area = 0f64;
-
-        for i in (0..vertices.len()).step_by(2) {
-            area += x[(i + 1) % x.len()] * (y[(i + 2) % y.len()] - y[i % y.len()]) + y[(i + 1) % y.len()] * (x[i % x.len()] - x[(i + 2) % x.len()]);
+        let mut prev: usize = vertices.len() - 1;
+        for i in 0..vertices.len() {
+            area += Vector2::cross(vertices[prev].to_vec2(), vertices[i].to_vec2());
+            prev = i;
         }
 
-        // area.abs() / 2f64
-        1f64
+        area.abs() / 2.0
     }
 
-    /// Internal method used for calculating the moment of inertia (mmoi) of a non-rect polygon
-    fn calculate_inertia(vertices: Vec<Vertex>, mass: f64) -> f64 {
-        let mut center: Vector2<f64> = v2!(0.0);
-        let mut area: f64 = 0.0;
-        let mut mmoi: f64 = 0.0;
+    /// Internal method used for calculating the moment of inertia (mmoi) of a non-rect polygon, about its centroid
+    fn calculate_inertia(vertices: Vec<Vertex>, mass: Real) -> Real {
+        let mut center: Vector2<Real> = v2!(0.0);
+        let mut area: Real = 0.0;
+        let mut mmoi: Real = 0.0;
 
+        // Sum over the triangles fanning out from the local origin; signed, so the winding cancels out
         let mut prev: usize = vertices.len() - 1;
         for i in 0..vertices.len() {
             let a = vertices[prev].to_vec2();
             let b = vertices[i].to_vec2();
 
-            let center_s = (a + b) / 3.0;
             let area_s = Vector2::cross(a, b) / 2.0;
+            let center_s = (a + b) / 3.0;
             let mmoi_s = area_s * (Vector2::dot(a, a) + Vector2::dot(a, b) + Vector2::dot(b, b)) / 6.0;
 
-            center = (center * area + center_s + v2!(area_s)) / (area + area_s);
+            center = center + center_s * area_s;
             area = area + area_s;
             mmoi = mmoi + mmoi_s;
 
             prev = i;
         }
+        if area == 0.0 { return 0.0; }
+        center = center / area;
 
-        let density: f64 = mass / area;
-        mmoi = mmoi * density;
-        mmoi = mmoi - Vector2::dot(center, center);
-
-        mmoi
+        // Scale to the mass, then move from the local origin to the centroid (parallel axis theorem)
+        let density: Real = mass / area;
+        mmoi * density - mass * Vector2::dot(center, center)
     }
 
-    /// Convert local Vector2 (vector about the object's origin) into global space
-    pub fn globalise(&self, v: Vector2<Crd>) -> Vector2<Crd> {
-        let rotated = v2!(
-            v.x * self.rotation.cos() - v.y * self.rotation.sin(),
-            v.x * self.rotation.sin() + v.y * self.rotation.cos()
-        );
+    /// Splits the body into its motion & the rest of its data
+    pub fn view(&self) -> BodyRef<'_> {
+        let Motion { position, rotation, velocity, angular_velocity, prev_position, prev_rotation } = self.motion();
+        BodyRef { position, rotation, velocity, angular_velocity, prev_position, prev_rotation, transform: self.transform(), geometry: None, data: &self.data }
+    }
+    pub fn view_mut(&mut self) -> BodyMut<'_> {
+        let target = MotionMut {
+            position: &mut self.position,
+            rotation: &mut self.rotation,
+            velocity: &mut self.velocity,
+            angular_velocity: &mut self.angular_velocity,
+            prev_position: &mut self.prev_position,
+            prev_rotation: &mut self.prev_rotation,
+            derived: None,
+        };
+        BodyMut::new(target, &mut self.data)
+    }
 
-        self.origin + self.position + rotated.to()
+    /* --------------------- GETTERS -------------------- */
+    pub fn ident(&self) -> () {
+        println!("- sides={:?}\n- w={:?}", self.sides, self.width.unwrap_or(0.0));
     }
 
-    /// Returns the axis-aligned bounding box of the object.
-    pub fn aabb(&self) -> AABB {
-        let points;
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_frozen(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+    pub fn set_collision_group(mut self, group: i32) -> Self {
+        self.collision_group = group;
+        self
+    }
+    pub fn set_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub fn set_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+    pub fn set_ignore_groups(mut self, groups: Vec<i32>) -> Self {
+        self.ignore_groups = groups;
+        self
+    }
+    /// Despawns the body after the given number of simulated seconds
+    pub fn set_lifetime(mut self, seconds: Real) -> Self {
+        self.lifetime = Some(seconds);
+        self
+    }
+    /// Places the body so that its center is at the given global position
+    pub fn set_center(mut self, center: Vector2<Crd>) -> Self {
+        self.position = center - self.origin;
+        self.prev_position = self.position;
+        self
+    }
+    pub fn set_rotation(mut self, rotation: Real) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    /// Moves the center of mass (which the body also rotates about) by a local offset, leaving the shape in place
+    pub fn set_center_of_mass(mut self, offset: Vector2<Crd>) -> Self {
+        for vertex in self.vertices.iter_mut() {
+            vertex.x -= offset.x;
+            vertex.y -= offset.y;
+        }
+        self.origin = self.origin + offset;
+        self.inertia += self.mass * Vector2::dot(offset, offset); // Parallel axis theorem
+        self
+    }
+}
 
-        if self.radius.is_some() {
-            let r = self.radius.unwrap() as Crd;
+impl BodyData {
+    /// Evaluates whether the given Body object is a rect-like.
+    /// Checks if it has 4 sides, and has its width & height properties defined.
+    pub fn is_rect(&self) -> bool {
+        self.form == BodyForm::Polygon && self.sides == 4 && self.width.is_some() && self.height.is_some()
+    }
 
-            points = vec![v2!(-r, -r), v2!(r, -r), v2!(r, r), v2!(-r, r)]
-                .iter().map(|&v2| self.globalise(v2)).collect();
-        } else {
-            let w = self.width.unwrap() as Crd;
-            let h = self.height.unwrap() as Crd;
+    pub fn mode(&self) -> Mode {
+        if !self.enabled { Mode::Disabled } else if self.frozen { Mode::Frozen } else { Mode::Moving }
+    }
+    pub fn inv_mass(&self) -> Real {
+        if self.frozen || self.mass == 0.0 { 0.0 } else { 1.0 / self.mass }
+    }
+    pub fn inv_inertia(&self) -> Real {
+        if self.frozen || self.inertia == 0.0 { 0.0 } else { 1.0 / self.inertia }
+    }
+}
 
-            points = vec![v2!(0.0, 0.0), v2!(0.0, h), v2!(w, h), v2!(w, 0.0)]
-                .iter().map(|&v2| self.globalise(v2)).collect();
+impl<'a> BodyMut<'a> {
+    pub fn new(target: MotionMut<'a>, data: &'a mut BodyData) -> Self {
+        BodyMut::with_data(target, DataMut::Unique(data))
+    }
+    /// View of a body that others may be reading; its motion is written back, but changes to its data are dropped
+    pub fn shared(target: MotionMut<'a>, data: &'a BodyData) -> Self {
+        BodyMut::with_data(target, DataMut::Shared(Cow::Borrowed(data)))
+    }
+    fn with_data(target: MotionMut<'a>, data: DataMut<'a>) -> Self {
+        BodyMut {
+            position: *target.position,
+            rotation: *target.rotation,
+            velocity: *target.velocity,
+            angular_velocity: *target.angular_velocity,
+            prev_position: *target.prev_position,
+            prev_rotation: *target.prev_rotation,
+            data,
+            target,
         }
+    }
 
-        AABB {
-            points
-        }
+    pub fn view(&self) -> BodyRef<'_> {
+        let Motion { position, rotation, velocity, angular_velocity, prev_position, prev_rotation } = self.motion();
+        BodyRef { position, rotation, velocity, angular_velocity, prev_position, prev_rotation, transform: self.transform(), geometry: None, data: self }
+    }
+    /// Shorter-lived view of the same body; its changes reach this view when it is dropped
+    pub fn reborrow(&mut self) -> BodyMut<'_> {
+        let target = MotionMut {
+            position: &mut self.position,
+            rotation: &mut self.rotation,
+            velocity: &mut self.velocity,
+            angular_velocity: &mut self.angular_velocity,
+            prev_position: &mut self.prev_position,
+            prev_rotation: &mut self.prev_rotation,
+            derived: None,
+        };
+        BodyMut::with_data(target, self.data.reborrow())
     }
 
-    pub fn axes(&self) -> Vec<Vector2<f64>> {
-        let mut axes = Vec::new();
+    /// Apply an impulse at offset r from the center of mass, changing linear & angular velocity
+    pub fn apply_impulse(&mut self, impulse: Vector2<Real>, r: Vector2<Real>) {
+        self.velocity = self.velocity + impulse * self.inv_mass();
+        self.angular_velocity += Vector2::cross(r, impulse) * self.inv_inertia();
+    }
 
-        for i in 0..self.sides as usize {
-            let p1 = self.globalise(self.vertices[i].to_vec2());
-            let p2 = self.globalise(self.vertices[if i + 1 == self.sides as usize { 0 } else { i + 1 }].to_vec2());
+    /// Apply a positional correction at offset r from the center of mass, changing position & rotation
+    pub fn apply_correction(&mut self, correction: Vector2<Real>, r: Vector2<Real>) {
+        self.position = self.position + correction * self.inv_mass();
+        self.rotation += Vector2::cross(r, correction) * self.inv_inertia();
+    }
 
-            let edge = p2 - p1;
-            let normal = v2!(-edge.y, edge.x);
+    /// Apply an angular impulse, changing angular velocity only
+    pub fn apply_angular_impulse(&mut self, impulse: Real) {
+        self.angular_velocity += impulse * self.inv_inertia();
+    }
 
-            // println!("Axis={:?}", normal);
-            // println!("Norm={:?}", normal.norm());
+    /// Apply an angular positional correction, changing rotation only
+    pub fn apply_angular_correction(&mut self, correction: Real) {
+        self.rotation += correction * self.inv_inertia();
+    }
+}
 
-            axes.push(normal);
+impl DataMut<'_> {
+    fn reborrow(&mut self) -> DataMut<'_> {
+        match self {
+            DataMut::Unique(data) => DataMut::Unique(data),
+            DataMut::Shared(Cow::Owned(data)) => DataMut::Unique(data),
+            DataMut::Shared(Cow::Borrowed(data)) => DataMut::Shared(Cow::Borrowed(data)),
         }
+    }
+}
 
-        axes
+impl MotionMut<'_> {
+    /// Shorter-lived target on the same motion
+    pub fn reborrow(&mut self) -> MotionMut<'_> {
+        MotionMut {
+            position: &mut *self.position,
+            rotation: &mut *self.rotation,
+            velocity: &mut *self.velocity,
+            angular_velocity: &mut *self.angular_velocity,
+            prev_position: &mut *self.prev_position,
+            prev_rotation: &mut *self.prev_rotation,
+            derived: self.derived.as_mut().map(|d| Derived {
+                inv_mass: &mut *d.inv_mass,
+                inv_inertia: &mut *d.inv_inertia,
+                mode: &mut *d.mode,
+                transform: &mut *d.transform,
+                stale: &mut *d.stale,
+            }),
+        }
     }
+}
 
-    /* --------------------- GETTERS -------------------- */
-    pub fn ident(&self) -> () {
-        println!("- sides={:?}\n- w={:?}", self.sides, self.width.unwrap_or(0));
+impl Drop for BodyMut<'_> {
+    fn drop(&mut self) {
+        // The body is only marked stale if it has moved; its transform & geometry are redone once it next is refreshed
+        let p = self.origin + self.position;
+        let moved = *self.target.rotation != self.rotation || self.target.derived.as_ref().is_some_and(|d| d.transform.p != p);
+
+        let target = &mut self.target;
+        *target.position = self.position;
+        *target.rotation = self.rotation;
+        *target.velocity = self.velocity;
+        *target.angular_velocity = self.angular_velocity;
+        *target.prev_position = self.prev_position;
+        *target.prev_rotation = self.prev_rotation;
+
+        if let Some(derived) = target.derived.as_mut() {
+            *derived.inv_mass = self.data.inv_mass();
+            *derived.inv_inertia = self.data.inv_inertia();
+            *derived.mode = self.data.mode();
+            if moved {
+                *derived.stale = true;
+            }
+        }
     }
-    pub fn center(&self) -> Vector2<f64> {
-        let m: Vector2<f64> = if self.is_rect() { v2!(self.width.unwrap_or(1) / 2, self.height.unwrap_or(1) / 2).to() } else { v2!(self.radius.unwrap_or(1.0)) };
-        return self.position + m
+}
+
+impl BodyView for Body {
+    fn motion(&self) -> Motion {
+        Motion {
+            position: self.position,
+            rotation: self.rotation,
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+            prev_position: self.prev_position,
+            prev_rotation: self.prev_rotation,
+        }
     }
-    pub fn inv_mass(&self) -> f64 {
-        return if self.frozen || self.mass == 0.0 { 0.0 } else { 1.0 / self.mass }
+    fn transform(&self) -> Transform {
+        Transform::new(self.origin + self.position, self.rotation)
     }
-    pub fn inv_inertia(&self) -> f64 {
-        return if self.frozen || self.inertia == 0.0 { 0.0 } else { 1.0 / self.inertia }
+}
+impl BodyView for BodyRef<'_> {
+    fn motion(&self) -> Motion {
+        Motion {
+            position: self.position,
+            rotation: self.rotation,
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+            prev_position: self.prev_position,
+            prev_rotation: self.prev_rotation,
+        }
+    }
+    fn transform(&self) -> Transform {
+        self.transform
+    }
+    fn geometry(&self) -> Cow<'_, Geometry> {
+        match self.geometry {
+            Some(geometry) => Cow::Borrowed(geometry),
+            None => {
+                let mut geometry = Geometry::default();
+                self.write_geometry(&mut geometry);
+                Cow::Owned(geometry)
+            }
+        }
+    }
+}
+impl BodyView for BodyMut<'_> {
+    fn motion(&self) -> Motion {
+        Motion {
+            position: self.position,
+            rotation: self.rotation,
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+            prev_position: self.prev_position,
+            prev_rotation: self.prev_rotation,
+        }
+    }
+    /// From the view's own motion, which may have changed since it was taken
+    fn transform(&self) -> Transform {
+        Transform::new(self.origin + self.position, self.rotation)
+    }
+}
+impl contact::Contactable<Real> for BodyMut<'_> {
+    fn center(&self) -> Vector2<Real> {
+        BodyView::center(self)
     }
+    fn inv_mass(&self) -> Real {
+        BodyData::inv_mass(self)
+    }
+    fn inv_inertia(&self) -> Real {
+        BodyData::inv_inertia(self)
+    }
+    fn coefficients(&self) -> (Real, Real, Real) {
+        (self.material.e, self.material.ks, self.material.kd)
+    }
+    fn velocities(&self) -> (Vector2<Real>, Real) {
+        (self.velocity, self.angular_velocity)
+    }
+    fn motion_mut(&mut self) -> (&mut Vector2<Crd>, &mut Vector2<Real>, &mut Real) {
+        (&mut self.position, &mut self.velocity, &mut self.angular_velocity)
+    }
+}
 
-    /* --------------------- SETTERS -------------------- */
-    pub fn set_frozen(mut self, frozen: bool) -> Self {
-        self.frozen = frozen;
-        self
+impl Deref for Body {
+    type Target = BodyData;
+    fn deref(&self) -> &BodyData {
+        &self.data
     }
-    pub fn set_collision_group(mut self, group: i32) -> Self {
-        self.collision_group = group;
-        self
+}
+impl DerefMut for Body {
+    fn deref_mut(&mut self) -> &mut BodyData {
+        &mut self.data
     }
-    pub fn set_ignore_groups(mut self, groups: Vec<i32>) -> Self {
-        self.ignore_groups = groups;
-        self
+}
+impl Deref for BodyRef<'_> {
+    type Target = BodyData;
+    fn deref(&self) -> &BodyData {
+        self.data
     }
-    pub fn set_rotation(mut self, rotation: f64) -> Self {
-        self.rotation = rotation;
-        self
+}
+impl Deref for BodyMut<'_> {
+    type Target = BodyData;
+    fn deref(&self) -> &BodyData {
+        &self.data
+    }
+}
+impl DerefMut for BodyMut<'_> {
+    fn deref_mut(&mut self) -> &mut BodyData {
+        &mut self.data
+    }
+}
+impl Deref for DataMut<'_> {
+    type Target = BodyData;
+    fn deref(&self) -> &BodyData {
+        match self {
+            DataMut::Unique(data) => data,
+            DataMut::Shared(data) => data,
+        }
+    }
+}
+impl DerefMut for DataMut<'_> {
+    fn deref_mut(&mut self) -> &mut BodyData {
+        match self {
+            DataMut::Unique(data) => data,
+            DataMut::Shared(data) => data.to_mut(),
+        }
     }
 }
 
@@ -310,11 +731,11 @@ impl Body {
 macro_rules! poly {
     // Generate polygon made off rock
     ($pos:expr, $radius:expr, $sides:expr) => {
-        Body::new(BodyForm::Polygon, $pos, Some($radius as f64), $sides, None, None, Materials::ROCK)
+        Body::new(BodyForm::Polygon, $crate::common::ConvertPrimitives::to($pos), Some($radius as $crate::common::Real), $sides, None, None, Materials::ROCK)
     };
     // Generate polygon with custom material
     ($pos:expr, $radius:expr, $sides:expr, $material:expr) => {
-        Body::new(BodyForm::Polygon, $pos, Some($radius as f64), $sides, None, None, $material)
+        Body::new(BodyForm::Polygon, $crate::common::ConvertPrimitives::to($pos), Some($radius as $crate::common::Real), $sides, None, None, $material)
     };
 }
 
@@ -322,10 +743,27 @@ macro_rules! poly {
 macro_rules! rect {
     // Generate a rect-like made off rock
     ($pos:expr, $width:expr, $height:expr) => {
-        Body::new(BodyForm::Polygon, $pos, None, 4, Some($width), Some($height), Materials::ROCK)
+        Body::new(BodyForm::Polygon, $crate::common::ConvertPrimitives::to($pos), None, 4, Some($width as $crate::common::Real), Some($height as $crate::common::Real), Materials::ROCK)
     };
     // Generate a rect-like with a custom material
     ($pos:expr, $width:expr, $height:expr, $material:expr) => {
-        Body::new(BodyForm::Polygon, $pos, None, 4, Some($width), Some($height), $material)
+        Body::new(BodyForm::Polygon, $crate::common::ConvertPrimitives::to($pos), None, 4, Some($width as $crate::common::Real), Some($height as $crate::common::Real), $material)
     };
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poly;
+
+    #[test]
+    fn polygon_mass_follows_its_area() {
+        // Regular hexagon of circumradius 2 m; area 3√3/2 r², & inertia m r²/6 (1 + 2cos²(π/n)) about its center
+        let (r, density) = (2.0, Materials::ROCK.density);
+        let hexagon = poly!(v2!(0.0), r, 6);
+        let area = 1.5 * (3.0 as Real).sqrt() * r * r;
+        let inertia = hexagon.mass * r * r / 6.0 * (1.0 + 2.0 * (PI / 6.0).cos().powi(2));
+
+        assert!((hexagon.mass - density * area).abs() < 1e-3);
+        assert!((hexagon.inertia - inertia).abs() < 1e-3);
+    }
+}
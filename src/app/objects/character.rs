@@ -0,0 +1,386 @@
+/*
+    character.rs
+    ----------------------------------------
+    Description:
+    * Provides a kinematic character controller for platformers
+    * The character's body is frozen; it is moved by shape casts against the world, sliding along whatever it hits,
+      so it never tips over, bounces, or slides down walkable slopes
+    * Supports slope limits, stepping up low obstacles, ground detection, moving platforms, coyote time & jump buffering
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::collision::CollisionDetector;
+use crate::app::objects::BodyView;
+use crate::common::{CastResult, Vector2, LENGTH_TOLERANCE, Real};
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+const SKIN: Real = 0.01;     // Gap kept between the character & the surfaces it touches
+const MAX_SLIDES: u32 = 4;  // Surfaces the character may slide along per update
+
+/* ------------------- STRUCTURES ------------------- */
+/// Internal struct for kinematic characters.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterController {
+    pub body: BodyHandle,
+    pub velocity: Vector2<Real>,
+
+    pub max_slope: Real,    // Steepest walkable slope, in radians
+    pub step_height: Real,  // Tallest obstacle the character steps onto
+    pub jump_speed: Real,
+    pub coyote_time: Real,  // Seconds after leaving the ground during which a jump is still allowed
+    pub jump_buffer: Real,  // Seconds before landing during which a jump press is remembered
+
+    // State
+    pub grounded: bool,
+    pub ground_normal: Vector2<Real>,
+    pub ground: Option<BodyHandle>,
+    pub contacts: Vec<CastResult>, // Surfaces resolved during the last update
+    ground_anchor: Vector2<Real>,   // Ground body's center at the last update; its change carries the character
+    since_grounded: Real,
+    since_jump: Real,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl CharacterController {
+    /// Constructor for the CharacterController struct. The body is frozen, as it is moved kinematically.
+    pub fn new(bodies: &mut BodyArena, body: BodyHandle) -> Self {
+        bodies.body_mut(body).frozen = true;
+
+        CharacterController {
+            body,
+            velocity: v2!(0.0),
+
+            max_slope: Real::to_radians(50.0),
+            step_height: 0.16,
+            jump_speed: 6.0,
+            coyote_time: 0.1,
+            jump_buffer: 0.1,
+
+            // State
+            grounded: false,
+            ground_normal: v2!(0.0),
+            ground: None,
+            contacts: Vec::new(),
+            ground_anchor: v2!(0.0),
+            since_grounded: Real::INFINITY,
+            since_jump: Real::INFINITY,
+        }
+    }
+
+    /// Request a jump; it happens now if the character is (or just was) grounded, or as soon as it lands
+    pub fn jump(&mut self) {
+        self.since_jump = 0.0;
+    }
+
+    /// Move the character by its velocity. Called every step, with the gravity velocity change for that step.
+    pub fn update(&mut self, dt: Real, bodies: &mut BodyArena, gravity: Vector2<Real>) {
+        let up = if gravity.mag() > 0.0 { gravity.norm() * -1.0 } else { v2!(0.0, -1.0) };
+        let start = bodies.body(self.body).position;
+        self.contacts.clear();
+
+        // Moving platforms carry the character along
+        let carry = match self.ground.and_then(|ground| bodies.get(ground)) {
+            Some(ground) => ground.center() - self.ground_anchor,
+            None => v2!(0.0),
+        };
+
+        // Gravity, & jumps
+        self.since_jump += dt;
+        if self.grounded {
+            self.velocity = self.velocity - up * Vector2::dot(self.velocity, up).min(0.0);
+        } else {
+            self.velocity = self.velocity + gravity;
+        }
+        if self.since_jump <= self.jump_buffer && self.since_grounded <= self.coyote_time {
+            self.velocity = self.velocity - up * Vector2::dot(self.velocity, up) + up * self.jump_speed;
+            self.grounded = false;
+            self.since_grounded = Real::INFINITY;
+            self.since_jump = Real::INFINITY;
+        }
+
+        // Move & slide
+        let mut remaining = self.velocity * dt + carry;
+        for _ in 0..MAX_SLIDES {
+            if remaining.mag() < LENGTH_TOLERANCE { break; }
+
+            let Some(hit) = self.cast(v2!(0.0), remaining, bodies) else {
+                self.translate(remaining, bodies);
+                break;
+            };
+
+            if hit.depth > 0.0 {
+                // Started inside the surface; push out of it first
+                self.translate(hit.normal * (hit.depth + SKIN), bodies);
+            } else {
+                self.translate(remaining * hit.t + hit.normal * SKIN, bodies);
+                remaining = remaining * (1.0 - hit.t);
+            }
+
+            let mut n = hit.normal;
+            if Vector2::dot(n, up) >= self.max_slope.cos() {
+                // Landing on walkable ground stops the fall outright, rather than turning it into a slide down the slope
+                remaining = remaining - up * Vector2::dot(remaining, up).min(0.0);
+                self.velocity = self.velocity - up * Vector2::dot(self.velocity, up).min(0.0);
+            } else {
+                if self.grounded && self.step_up(remaining, up, bodies) {
+                    self.contacts.push(hit);
+                    break;
+                }
+
+                // Steep slopes block like walls, rather than being climbed
+                let flat = n - up * Vector2::dot(n, up);
+                if Vector2::dot(n, up) > 0.0 && flat.mag() > LENGTH_TOLERANCE {
+                    n = flat.norm();
+                }
+            }
+
+            remaining = remaining - n * Vector2::dot(remaining, n).min(0.0);
+            self.velocity = self.velocity - n * Vector2::dot(self.velocity, n).min(0.0);
+            self.contacts.push(hit);
+        }
+
+        // Ground detection; grounded characters also snap down slopes & steps instead of leaving the ground
+        let probe = if self.grounded && Vector2::dot(self.velocity, up) <= 0.0 { self.step_height } else { SKIN * 2.0 };
+        let was_grounded = self.grounded;
+        self.grounded = false;
+        self.ground = None;
+
+        if let Some(hit) = self.cast(v2!(0.0), up * -probe, bodies) {
+            if Vector2::dot(hit.normal, up) >= self.max_slope.cos() {
+                self.translate(up * -(probe * hit.t - SKIN).max(0.0), bodies);
+
+                self.grounded = true;
+                self.ground_normal = hit.normal;
+                self.ground_anchor = bodies.body(hit.body).center();
+                self.ground = Some(hit.body);
+                if !was_grounded {
+                    self.contacts.push(hit);
+                }
+            }
+        }
+
+        self.since_grounded = if self.grounded { 0.0 } else { self.since_grounded + dt };
+
+        // Expose the kinematic velocity, so that dynamic bodies are pushed along
+        let mut body = bodies.body_mut(self.body);
+        body.velocity = (body.position - start) / dt;
+    }
+
+    /// Try to climb onto an obstacle no taller than `step_height`; moves the character & returns true on success
+    fn step_up(&mut self, forward: Vector2<Real>, up: Vector2<Real>, bodies: &mut BodyArena) -> bool {
+        let mut forward = forward - up * Vector2::dot(forward, up);
+        if forward.mag() < SKIN * 2.0 {
+            // Hit the obstacle at the very end of the move; probe just far enough to get onto it
+            let heading = self.velocity - up * Vector2::dot(self.velocity, up);
+            if heading.mag() < LENGTH_TOLERANCE { return false; }
+            forward = heading.norm() * (SKIN * 2.0);
+        }
+
+        let rise = match self.cast(v2!(0.0), up * self.step_height, bodies) {
+            Some(hit) => up * (self.step_height * hit.t - SKIN).max(0.0),
+            None => up * self.step_height,
+        };
+        let advance = match self.cast(rise, forward, bodies) {
+            Some(hit) => forward * hit.t,
+            None => forward,
+        };
+        if advance.mag() < LENGTH_TOLERANCE { return false; }
+
+        // Land on a walkable surface
+        let Some(hit) = self.cast(rise + advance, rise * -1.0 - up * SKIN, bodies) else { return false; };
+        if hit.depth > 0.0 || Vector2::dot(hit.normal, up) < self.max_slope.cos() { return false; }
+
+        let fall = (rise * -1.0 - up * SKIN) * hit.t + up * SKIN;
+        self.translate(rise + advance + fall, bodies);
+        true
+    }
+
+    /// Shape cast of the character's body, offset from its current position
+    fn cast(&self, offset: Vector2<Real>, delta: Vector2<Real>, bodies: &BodyArena) -> Option<CastResult> {
+        let body = bodies.body(self.body);
+        let shape: Vec<Vector2<Real>> = body.geometry().vertices.iter().map(|&v| v + offset).collect();
+
+        CollisionDetector::shape_cast(&shape, delta, bodies, &body.ignore_groups, Some(self.body))
+    }
+
+    fn translate(&self, d: Vector2<Real>, bodies: &mut BodyArena) {
+        let mut body = bodies.body_mut(self.body);
+        body.position = body.position + d;
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    /// Whether a jump requested now would happen; i.e. the character is grounded, or within coyote time
+    pub fn can_jump(&self) -> bool {
+        self.since_grounded <= self.coyote_time
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_max_slope(mut self, max_slope: Real) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+    pub fn set_step_height(mut self, step_height: Real) -> Self {
+        self.step_height = step_height;
+        self
+    }
+    pub fn set_jump(mut self, jump_speed: Real, coyote_time: Real, jump_buffer: Real) -> Self {
+        self.jump_speed = jump_speed;
+        self.coyote_time = coyote_time;
+        self.jump_buffer = jump_buffer;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::engine::Engine;
+    use crate::app::objects::Body;
+    use crate::app::ssm::SystemStateManager;
+    use crate::common::{BodyForm, Materials, Shared, TCharacterRef, Vector2M};
+    use crate::rect;
+
+    const DT: Real = 1.0 / 60.0;
+
+    /// An engine, & a world holding a floor whose top is at y = 10, with a character standing on it at the given x
+    fn world(x: Real) -> (SystemStateManager, Engine, TCharacterRef) {
+        let engine = Engine::new(Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        })));
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(0.0, 10.0), 20.0, 1.0).set_frozen(true));
+        let character = ssm.add_character(rect!(v2!(x, 8.99), 0.5, 1.0));
+        (ssm, engine, character)
+    }
+
+    fn run(ssm: &mut SystemStateManager, engine: &mut Engine, steps: usize) {
+        for _ in 0..steps {
+            engine.step(ssm, DT);
+        }
+    }
+
+    /// Left & bottom edges of the character's body
+    fn feet(ssm: &SystemStateManager, character: &TCharacterRef) -> Vector2<Real> {
+        let aabb = ssm.bodies().body(character.borrow().body).geometry().aabb;
+        v2!(aabb.min.x, aabb.max.y)
+    }
+
+    #[test]
+    fn slopes_past_the_limit_slide() {
+        // Gravity tilted rather than the floor, making it a slope of the same angle
+        for (degrees, walkable) in [(30.0, true), (60.0, false)] {
+            let (mut ssm, mut engine, character) = world(9.0);
+            let angle = Real::to_radians(degrees);
+            engine.set_gravity(v2!(angle.sin(), angle.cos(), 9.81));
+            run(&mut ssm, &mut engine, 60);
+
+            let slid = feet(&ssm, &character).x - 9.0;
+            assert_eq!(character.borrow().grounded, walkable, "at {degrees}°");
+            if walkable {
+                assert!(slid.abs() < 0.01, "slid {slid} m down a walkable {degrees}° slope");
+            } else {
+                assert!(slid > 0.5, "slid only {slid} m down a steep {degrees}° slope");
+            }
+        }
+    }
+
+    #[test]
+    fn low_obstacles_are_stepped_onto() {
+        // A kerb below the step height, & a wall above it, each a metre ahead
+        for (height, climbed) in [(0.1, true), (0.4, false)] {
+            let (mut ssm, mut engine, character) = world(5.0);
+            ssm.add_body(rect!(v2!(6.5, 10.0 - height), 2.0, height).set_frozen(true));
+            character.borrow_mut().velocity = v2!(2.0, 0.0);
+            run(&mut ssm, &mut engine, 60);
+
+            let feet = feet(&ssm, &character);
+            if climbed {
+                assert!(feet.x > 6.5 && (feet.y - (10.0 - height)).abs() < 0.02, "{height} m kerb; feet at {feet:?}");
+                assert!(character.borrow().grounded);
+            } else {
+                // Stopped with its right side, half a metre from its feet, against the wall
+                assert!((feet.x + 0.5 - 6.5).abs() < 0.02 && (feet.y - 10.0).abs() < 0.02, "{height} m wall; feet at {feet:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn jumps_are_allowed_just_after_leaving_the_ground() {
+        // Walking off the end of a ledge at 3 m/s; a jump still works for the coyote time, 0.1 s
+        let steps_off = |jump_at: usize| {
+            let (mut ssm, mut engine, character) = world(19.0);
+            character.borrow_mut().velocity = v2!(3.0, 0.0);
+            let mut off = None;
+            for step in 0..60 {
+                engine.step(&mut ssm, DT);
+                let grounded = character.borrow().grounded;
+                if !grounded && off.is_none() { off = Some(step); }
+                if off.is_some_and(|off| step == off + jump_at) {
+                    character.borrow_mut().jump();
+                    engine.step(&mut ssm, DT);
+                    return character.borrow().velocity.y;
+                }
+            }
+            panic!("the character never left the ledge");
+        };
+
+        assert!(steps_off(3) < 0.0, "no jump 0.05 s after leaving the ground");
+        assert!(steps_off(12) > 0.0, "jumped 0.2 s after leaving the ground");
+    }
+
+    #[test]
+    fn jumps_pressed_just_before_landing_happen_on_landing() {
+        // Dropped from half a metre onto the floor
+        let dropped = || {
+            let (mut ssm, engine, character) = world(9.0);
+            ssm.bodies_mut().body_mut(character.borrow().body).position.y -= 0.5;
+            (ssm, engine, character)
+        };
+        let (mut ssm, mut engine, character) = dropped();
+        let landing = (0..120).find(|_| {
+            engine.step(&mut ssm, DT);
+            character.borrow().grounded
+        }).expect("the character never landed");
+
+        // The jump pressed some steps before landing, then stepped once past it; the buffer is 0.1 s
+        let jumped = |before: usize| {
+            let (mut ssm, mut engine, character) = dropped();
+            for step in 0..=landing + 1 {
+                if step + before == landing { character.borrow_mut().jump(); }
+                engine.step(&mut ssm, DT);
+            }
+            let velocity = character.borrow().velocity;
+            velocity.y < 0.0
+        };
+        assert!(jumped(3), "jump pressed 0.05 s before landing was dropped");
+        assert!(!jumped(12), "jump pressed 0.2 s before landing was kept");
+    }
+
+    #[test]
+    fn moving_platforms_carry_the_character() {
+        let (mut ssm, mut engine, character) = world(9.0);
+        let platform = ssm.add_body(rect!(v2!(8.0, 7.0), 3.0, 0.5).set_frozen(true));
+        ssm.bodies_mut().body_mut(character.borrow().body).position.y -= 3.5;
+        run(&mut ssm, &mut engine, 30);
+        assert_eq!(character.borrow().ground, Some(platform));
+
+        // The platform slides 1 m sideways over a second; the character rides along
+        let start = feet(&ssm, &character);
+        for _ in 0..60 {
+            ssm.bodies_mut().body_mut(platform).position.x += 1.0 / 60.0;
+            engine.step(&mut ssm, DT);
+        }
+        let moved = feet(&ssm, &character) - start;
+        assert!((moved.x - 1.0).abs() < 0.05 && moved.y.abs() < 0.01, "carried by {moved:?}");
+    }
+}
@@ -1,2 +1,12 @@
 mod body;
-pub use body::Body;
\ No newline at end of file
+mod character;
+mod particles;
+mod rope;
+mod soft_body;
+mod vehicle;
+pub use body::{Body, BodyData, BodyMut, BodyRef, BodyView, Derived, Geometry, Mode, Motion, MotionMut};
+pub use character::CharacterController;
+pub use particles::{Particle, ParticleSystem};
+pub use rope::{Rope, RopeAnchor, RopeBuilder};
+pub use soft_body::{SoftBody, PointMass, Spring};
+pub use vehicle::{Vehicle, VehicleBuilder};
\ No newline at end of file
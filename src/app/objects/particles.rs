@@ -0,0 +1,229 @@
+/*
+    particles.rs
+    ----------------------------------------
+    Description:
+    * Provides a lightweight particle subsystem for debris, sparks, sand, etc.
+    * Particles are point masses with a radius, lifetime & colour; they have no vertices, axes or rotation
+    * Particles collide one-way against rigid bodies (via the broad-phase grid), and optionally against each other
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::collections::VecDeque;
+
+use crate::common::consts::PI;
+
+use rand::Rng;
+use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{with_rng, Crd, Vector2, Real};
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Particle {
+    pub position: Vector2<Crd>,
+    pub velocity: Vector2<Real>,
+    pub radius: Real,
+    pub lifetime: Real, // Remaining lifetime in seconds; Real::INFINITY for particles which never expire
+    #[serde(with = "ColorDef")]
+    pub color: Color,
+}
+
+/// Mirror of sdl2's Color, which doesn't implement serde's traits
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Color")]
+struct ColorDef {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+}
+
+/// Internal struct for managing every particle in the simulation.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ParticleSystem {
+    pub particles: VecDeque<Particle>, // Oldest first
+    pub restitution: Real,
+    pub friction: Real,         // Fraction of tangential velocity lost on contact
+    pub self_collision: bool,  // Whether particles collide with each other
+    pub max_particles: usize,  // Oldest particles are discarded past this limit
+    pub ignore_groups: Vec<i32>,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Particle {
+    pub fn new(position: Vector2<Crd>, velocity: Vector2<Real>, radius: Real, lifetime: Real, color: Color) -> Self {
+        Particle { position, velocity, radius, lifetime, color }
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        ParticleSystem {
+            particles: VecDeque::new(),
+            restitution: 0.3,
+            friction: 0.1,
+            self_collision: false,
+            max_particles: 10000,
+            ignore_groups: vec![],
+        }
+    }
+
+    /// Adds a particle; at max_particles, the oldest are discarded to make room
+    pub fn spawn(&mut self, particle: Particle) {
+        if self.max_particles == 0 { return; }
+        while self.particles.len() >= self.max_particles {
+            self.particles.pop_front();
+        }
+        self.particles.push_back(particle);
+    }
+
+    /// Spawn `count` particles at a point, flying out in random directions at up to `speed`
+    pub fn burst(&mut self, position: Vector2<Crd>, count: usize, speed: Real, radius: Real, lifetime: Real, color: Color) {
+        for _ in 0..count {
+            let (a, s) = with_rng(|rng| (rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.2..1.0)));
+            let v = v2!(a.cos(), a.sin()) * (speed * s);
+            self.spawn(Particle::new(position, v, radius, lifetime, color));
+        }
+    }
+
+    /// Integrate every particle, and discard expired ones. Called every substep.
+    pub fn update(&mut self, dt: Real, gravity: Vector2<Real>) {
+        for particle in self.particles.iter_mut() {
+            particle.velocity = particle.velocity + gravity;
+            particle.position = particle.position + particle.velocity * dt;
+            particle.lifetime -= dt;
+        }
+
+        self.particles.retain(|p| p.lifetime > 0.0);
+    }
+
+    /// Resolve particle-particle overlaps using a sorted spatial grid; particles are treated as equal masses
+    pub fn collide_self(&mut self) {
+        if !self.self_collision || self.particles.is_empty() { return; }
+
+        let cell = 2.0 * self.particles.iter().map(|p| p.radius).fold(0.0, Real::max);
+        if cell == 0.0 { return; }
+
+        // Cells are keyed row-major, so the 3 horizontally adjacent cells of a row form one contiguous range
+        let key = |p: Vector2<Real>, dx: i64, dy: i64| {
+            (((p.y / cell).floor() as i64 + dy) << 32) + ((p.x / cell).floor() as i64 + dx)
+        };
+        let mut cells: Vec<(i64, usize)> = self.particles.iter().enumerate()
+            .map(|(i, p)| (key(p.position, 0, 0), i))
+            .collect();
+        cells.sort_unstable();
+
+        for a in 0..self.particles.len() {
+            for dy in -1..=1 {
+                let position = self.particles[a].position;
+                let from = cells.partition_point(|&(k, _)| k < key(position, -1, dy));
+                let to = cells.partition_point(|&(k, _)| k <= key(position, 1, dy));
+
+                for &(_, b) in &cells[from..to] {
+                    if b <= a { continue; }
+
+                    let (p_a, p_b) = (self.particles[a], self.particles[b]);
+                    let d = p_b.position - p_a.position;
+                    let overlap = p_a.radius + p_b.radius - d.mag();
+                    if overlap <= 0.0 || d.mag() == 0.0 { continue; }
+
+                    let n = d.norm();
+                    let v_n = Vector2::dot(p_b.velocity - p_a.velocity, n);
+
+                    self.particles[a].position = p_a.position - n * (overlap * 0.5);
+                    self.particles[b].position = p_b.position + n * (overlap * 0.5);
+
+                    // Exchange the approaching part of the normal velocity
+                    if v_n < 0.0 {
+                        let j = n * (v_n * (1.0 + self.restitution) * 0.5);
+                        self.particles[a].velocity = p_a.velocity + j;
+                        self.particles[b].velocity = p_b.velocity - j;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::seed_rng;
+
+    fn particle(x: Real, velocity: Vector2<Real>, lifetime: Real) -> Particle {
+        Particle::new(v2!(x, 0.0), velocity, 0.1, lifetime, Color::WHITE)
+    }
+
+    #[test]
+    fn spawning_at_the_cap_drops_the_oldest() {
+        let mut system = ParticleSystem { max_particles: 3, ..ParticleSystem::new() };
+        for i in 0..5 {
+            system.spawn(particle(i as Real, v2!(0.0, 0.0), 1.0));
+        }
+        let xs: Vec<Real> = system.particles.iter().map(|p| p.position.x).collect();
+        assert_eq!(xs, vec![2.0, 3.0, 4.0]);
+
+        // Lowering the cap drops as many as it takes
+        system.max_particles = 1;
+        system.spawn(particle(5.0, v2!(0.0, 0.0), 1.0));
+        assert_eq!(system.particles.iter().map(|p| p.position.x).collect::<Vec<Real>>(), vec![5.0]);
+    }
+
+    #[test]
+    fn particles_fall_until_they_expire() {
+        let mut system = ParticleSystem::new();
+        system.spawn(particle(0.0, v2!(1.0, 0.0), 0.045));
+        system.spawn(particle(1.0, v2!(0.0, 0.0), Real::INFINITY));
+
+        let (dt, gravity) = (0.01, v2!(0.0, 0.0981));
+        for _ in 0..4 {
+            system.update(dt, gravity);
+        }
+        assert_eq!(system.particles.len(), 2);
+        let spark = system.particles[0];
+        assert!((spark.position.x - 0.04).abs() < 1e-6 && (spark.velocity.y - 4.0 * 0.0981).abs() < 1e-6);
+
+        system.update(dt, gravity);
+        assert_eq!(system.particles.len(), 1);
+        assert_eq!(system.particles[0].position.x, 1.0);
+    }
+
+    #[test]
+    fn overlapping_particles_are_pushed_apart() {
+        let mut system = ParticleSystem { self_collision: true, restitution: 0.5, ..ParticleSystem::new() };
+        system.spawn(particle(0.0, v2!(1.0, 0.0), 1.0));
+        system.spawn(particle(0.15, v2!(-1.0, 0.0), 1.0));
+        system.spawn(particle(5.0, v2!(0.0, 0.0), 1.0));
+        system.collide_self();
+
+        // Each moved by half the overlap, & bounced with half their approach speed; the distant one is untouched
+        let [a, b, c] = [system.particles[0], system.particles[1], system.particles[2]];
+        assert!(((b.position - a.position).mag() - 0.2).abs() < 1e-6);
+        assert!((a.velocity.x + 0.5).abs() < 1e-6 && (b.velocity.x - 0.5).abs() < 1e-6);
+        assert_eq!((c.position, c.velocity), (v2!(5.0, 0.0), v2!(0.0, 0.0)));
+    }
+
+    #[test]
+    fn bursts_scatter_within_their_speed() {
+        seed_rng(3);
+        let mut system = ParticleSystem::new();
+        system.burst(v2!(1.0, 2.0), 50, 4.0, 0.05, 1.0, Color::WHITE);
+
+        assert_eq!(system.particles.len(), 50);
+        for p in &system.particles {
+            assert_eq!(p.position, v2!(1.0, 2.0));
+            assert!((0.8 - 1e-6..=4.0).contains(&p.velocity.mag()), "flying at {}", p.velocity.mag());
+        }
+    }
+}
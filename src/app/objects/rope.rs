@@ -0,0 +1,399 @@
+/*
+    rope.rs
+    ----------------------------------------
+    Description:
+    * Provides ropes & chains between two (optional) anchor bodies
+    * Chain mode builds N small rect-like bodies linked by revolute joints; heavy, but fully rigid-body
+    * Verlet mode builds a lightweight rope of point masses held together by distance constraints
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::joints::{Joint, JointKind};
+use crate::app::objects::{Body, BodyView};
+use crate::app::ssm::SystemStateManager;
+use crate::common::{BodyForm, Crd, Material, Materials, TJointRef, Vector2, Real};
+use crate::rect;
+
+/* -------------------- VARIABLES ------------------- */
+const ROPE_GROUP: i32 = -2;                 // Default collision group of rope segments; segments ignore each other
+const MAX_CHAIN_COMPLIANCE: Real = 0.001;    // Joint compliance of a chain with a stiffness of 0
+const ITERATIONS: u32 = 4;                  // Constraint iterations per substep for verlet ropes
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct RopeAnchor {
+    pub body: BodyHandle,
+    pub local: Vector2<Crd>, // Anchor in the body's local space
+}
+
+/// Internal struct for verlet ropes.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Rope {
+    pub points: Vec<Vector2<Crd>>,
+    pub prev_points: Vec<Vector2<Crd>>, // Positions at the previous substep; velocity is implicit
+    pub broken: Vec<bool>,              // Per segment
+    pub anchors: [Option<RopeAnchor>; 2],
+
+    pub point_mass: Real,
+    pub segment_length: Real,
+    pub stiffness: Real,   // Fraction of the stretch corrected per iteration, 0..=1
+    pub break_force: Real, // Tension past which a segment snaps
+    pub thickness: Real,   // Drawn width; also the collision diameter of each point
+
+    // Meta
+    pub ignore_groups: Vec<i32>,
+}
+
+/// Builder for ropes & chains running from `start` to `end`.
+pub struct RopeBuilder {
+    start: Vector2<Crd>,
+    end: Vector2<Crd>,
+    anchors: [Option<BodyHandle>; 2],
+
+    segment_length: Real,
+    stiffness: Real,
+    break_force: Real,
+    thickness: Real,
+    material: Material,
+    collision_group: i32,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl RopeBuilder {
+    pub fn new(start: Vector2<Crd>, end: Vector2<Crd>) -> Self {
+        RopeBuilder {
+            start,
+            end,
+            anchors: [None, None],
+
+            segment_length: 0.4,
+            stiffness: 1.0,
+            break_force: Real::INFINITY,
+            thickness: 0.08,
+            material: Materials::ROCK,
+            collision_group: ROPE_GROUP,
+        }
+    }
+
+    /// Number of segments & their (evenly spread) positions along the rope
+    fn layout(&self) -> Vec<Vector2<Crd>> {
+        let length = (self.end - self.start).mag();
+        let segments = (length / self.segment_length).round().max(1.0) as usize;
+
+        (0..=segments)
+            .map(|i| self.start + (self.end - self.start) * (i as Real / segments as Real))
+            .collect()
+    }
+
+    /// Build a chain of rect-like links joined by revolute joints, and add it to the system.
+    pub fn build_chain(self, ssm: &mut SystemStateManager) -> (Vec<BodyHandle>, Vec<TJointRef>) {
+        let points = self.layout();
+        let compliance = (1.0 - self.stiffness.clamp(0.0, 1.0)) * MAX_CHAIN_COMPLIANCE;
+
+        let mut links: Vec<BodyHandle> = Vec::new();
+        let mut joints: Vec<TJointRef> = Vec::new();
+
+        for i in 0..points.len() - 1 {
+            let d = points[i + 1] - points[i];
+            let link = rect!(points[i], d.mag(), self.thickness, self.material)
+                .set_center((points[i] + points[i + 1]) / 2.0)
+                .set_rotation(d.y.atan2(d.x))
+                .set_collision_group(self.collision_group)
+                .set_ignore_groups(vec![self.collision_group]);
+            links.push(ssm.add_body(link));
+        }
+
+        // Link neighbours, then tie the ends to the anchors
+        let mut pairs: Vec<(BodyHandle, BodyHandle, Vector2<Crd>)> = Vec::new();
+        for i in 0..links.len() - 1 {
+            pairs.push((links[i], links[i + 1], points[i + 1]));
+        }
+        if let Some(anchor) = self.anchors[0] {
+            pairs.push((anchor, links[0], self.start));
+        }
+        if let Some(anchor) = self.anchors[1] {
+            pairs.push((links[links.len() - 1], anchor, self.end));
+        }
+
+        for (b1, b2, pivot) in pairs {
+            let joint = Joint::new(JointKind::Revolute, ssm.bodies(), b1, b2, pivot, pivot)
+                .set_compliance(compliance)
+                .set_break_force(self.break_force);
+            joints.push(ssm.add_joint(joint));
+        }
+
+        (links, joints)
+    }
+
+    /// Build a lightweight verlet rope of point masses. Mass is the density over the rope's length & thickness, spread
+    /// evenly over the points.
+    pub fn build_verlet(self, bodies: &BodyArena) -> Rope {
+        let points = self.layout();
+        let segments = points.len() - 1;
+        let anchor = |body: Option<BodyHandle>, point: Vector2<Crd>| body.map(|b| RopeAnchor {
+            body: b,
+            local: bodies.body(b).local_point(point),
+        });
+
+        Rope {
+            prev_points: points.clone(),
+            broken: vec![false; segments],
+            anchors: [anchor(self.anchors[0], self.start), anchor(self.anchors[1], self.end)],
+
+            point_mass: self.material.density * (self.end - self.start).mag() * self.thickness / points.len() as Real,
+            segment_length: (self.end - self.start).mag() / segments as Real,
+            stiffness: self.stiffness.clamp(0.0, 1.0),
+            break_force: self.break_force,
+            thickness: self.thickness,
+
+            // Meta
+            ignore_groups: vec![self.collision_group],
+
+            points,
+        }
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_anchors(mut self, start: Option<BodyHandle>, end: Option<BodyHandle>) -> Self {
+        self.anchors = [start, end];
+        self
+    }
+    pub fn set_segment_length(mut self, segment_length: Real) -> Self {
+        self.segment_length = segment_length;
+        self
+    }
+    pub fn set_stiffness(mut self, stiffness: Real) -> Self {
+        self.stiffness = stiffness;
+        self
+    }
+    pub fn set_break_force(mut self, break_force: Real) -> Self {
+        self.break_force = break_force;
+        self
+    }
+    pub fn set_thickness(mut self, thickness: Real) -> Self {
+        self.thickness = thickness;
+        self
+    }
+    pub fn set_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+    pub fn set_collision_group(mut self, group: i32) -> Self {
+        self.collision_group = group;
+        self
+    }
+}
+
+impl Rope {
+    /// Verlet integration & constraint solve. Called every substep, with the gravity velocity change for that substep.
+    pub fn update(&mut self, dt: Real, gravity: Vector2<Real>, bodies: &mut BodyArena) {
+        for i in 0..self.points.len() {
+            let velocity = self.points[i] - self.prev_points[i];
+            self.prev_points[i] = self.points[i];
+            self.points[i] = self.points[i] + velocity + gravity * dt;
+        }
+
+        let weights = self.end_weights(bodies);
+        for _ in 0..ITERATIONS {
+            self.solve_anchors(dt, bodies);
+            self.solve_segments(dt, weights);
+        }
+    }
+
+    /// Weights of the rope's ends in the segment solve, relative to a free point; an anchored end moves with its body,
+    /// so it carries the body's inverse mass, & none if the body is frozen
+    fn end_weights(&self, bodies: &BodyArena) -> [Real; 2] {
+        self.anchors.each_ref().map(|anchor| match anchor.as_ref().and_then(|anchor| bodies.get(anchor.body)) {
+            Some(body) => body.inv_mass() * self.point_mass,
+            None => 1.0,
+        })
+    }
+
+    /// Pull the rope ends onto their anchors; the anchor bodies are pulled back by the rope's tension
+    fn solve_anchors(&mut self, dt: Real, bodies: &mut BodyArena) {
+        let last = self.points.len() - 1;
+
+        for (end, index) in [(0, 0), (1, last)] {
+            let Some(anchor) = &self.anchors[end] else { continue; };
+            let Some(mut body) = bodies.get_mut(anchor.body) else { continue; };
+            let target = body.world_point(anchor.local);
+            let d = target - self.points[index];
+
+            // Share the correction by inverse mass
+            let r = target - body.center();
+            let w_point = 1.0 / self.point_mass;
+            let w_body = body.generalised_inv_mass(r, d.norm());
+            let share = w_point / (w_point + w_body);
+
+            // The body is moved the rest of the way, & keeps the velocity of that move, as the points do
+            let lambda = d * (-1.0 / (w_point + w_body));
+            self.points[index] = self.points[index] + d * share;
+            body.apply_correction(lambda, r);
+            body.apply_impulse(lambda / dt, r);
+        }
+    }
+
+    /// Distance constraints between neighbouring points; segments snap once their tension exceeds `break_force`
+    fn solve_segments(&mut self, dt: Real, ends: [Real; 2]) {
+        let last = self.points.len() - 1;
+        let weight = |i: usize| if i == 0 { ends[0] } else if i == last { ends[1] } else { 1.0 };
+
+        for i in 0..self.broken.len() {
+            if self.broken[i] { continue; }
+
+            let d = self.points[i + 1] - self.points[i];
+            if d.mag() == 0.0 { continue; }
+
+            let stretch = d.mag() - self.segment_length;
+            if self.point_mass * stretch.abs() / (dt * dt) > self.break_force {
+                self.broken[i] = true;
+                continue;
+            }
+
+            let (w_a, w_b) = (weight(i), weight(i + 1));
+            if w_a + w_b == 0.0 { continue; }
+
+            let correction = d.norm() * (stretch * self.stiffness / (w_a + w_b));
+            self.points[i] = self.points[i] + correction * w_a;
+            self.points[i + 1] = self.points[i + 1] - correction * w_b;
+        }
+    }
+
+    /// Whether point i is an end of the rope tied to the given body; such points don't collide with it
+    pub fn anchored_to(&self, i: usize, body: BodyHandle) -> bool {
+        let end = if i == 0 { &self.anchors[0] } else if i == self.points.len() - 1 { &self.anchors[1] } else { &None };
+        end.as_ref().is_some_and(|anchor| anchor.body == body)
+    }
+
+    /// Current velocity of a point over the last substep
+    pub fn velocity(&self, i: usize, dt: Real) -> Vector2<Real> {
+        (self.points[i] - self.prev_points[i]) / dt
+    }
+
+    /// Catmull-Rom spline through the points of each unbroken run of the rope
+    pub fn polylines(&self, subdivisions: usize) -> Vec<Vec<Vector2<Crd>>> {
+        let mut runs: Vec<Vec<Vector2<Crd>>> = vec![vec![self.points[0]]];
+        for i in 0..self.broken.len() {
+            if self.broken[i] {
+                runs.push(vec![]);
+            }
+            runs.last_mut().unwrap().push(self.points[i + 1]);
+        }
+
+        runs.iter().filter(|run| run.len() > 1).map(|run| {
+            let mut line = vec![run[0]];
+
+            for i in 0..run.len() - 1 {
+                let p_0 = run[i.saturating_sub(1)];
+                let (p_1, p_2) = (run[i], run[i + 1]);
+                let p_3 = run[(i + 2).min(run.len() - 1)];
+
+                for s in 1..=subdivisions {
+                    let t = s as Real / subdivisions as Real;
+                    let (t2, t3) = (t * t, t * t * t);
+
+                    line.push(
+                        (p_1 * 2.0
+                            + (p_2 - p_0) * t
+                            + (p_0 * 2.0 - p_1 * 5.0 + p_2 * 4.0 - p_3) * t2
+                            + (p_1 * 3.0 - p_0 - p_2 * 3.0 + p_3) * t3) * 0.5
+                    );
+                }
+            }
+
+            line
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::engine::Engine;
+    use crate::common::Shared;
+    use crate::v2;
+
+    /// An engine, & a world holding a frozen ceiling whose underside is at y = 1
+    fn world() -> (SystemStateManager, Engine, BodyHandle) {
+        let engine = Engine::new(Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        })));
+        let mut ssm = SystemStateManager::new();
+        let ceiling = ssm.add_body(rect!(v2!(0.0, 0.0), 20.0, 1.0).set_frozen(true));
+        (ssm, engine, ceiling)
+    }
+
+    fn run(ssm: &mut SystemStateManager, engine: &mut Engine, seconds: Real) {
+        for _ in 0..(seconds * 60.0) as usize {
+            engine.step(ssm, 1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn verlet_segments_keep_their_length() {
+        // A rope tied to the ceiling, held out sideways, swings down & hangs below its knot
+        let (mut ssm, mut engine, ceiling) = world();
+        let rope = RopeBuilder::new(v2!(5.0, 1.0), v2!(9.0, 1.0)).set_anchors(Some(ceiling), None).build_verlet(ssm.bodies());
+        assert_eq!((rope.points.len(), rope.segment_length), (11, 0.4));
+        let rope = ssm.add_rope(rope);
+        run(&mut ssm, &mut engine, 3.0);
+
+        let rope = rope.borrow();
+        assert!((rope.points[0] - v2!(5.0, 1.0)).mag() < 1e-4);
+        for pair in rope.points.windows(2) {
+            let length = (pair[1] - pair[0]).mag();
+            assert!((length - 0.4).abs() < 0.02, "segment stretched to {length} m");
+        }
+        let end = rope.points[10];
+        assert!(end.y > 3.0 && (end - v2!(5.0, 1.0)).mag() < 4.0 + 0.02 * 10.0, "end of the rope at {end:?}");
+        assert!(!rope.broken.contains(&true));
+    }
+
+    #[test]
+    fn segments_snap_past_their_break_force() {
+        // Two ropes holding up the same weight; only the weaker one gives way
+        let (mut ssm, mut engine, ceiling) = world();
+        let mut ropes = Vec::new();
+        for (x, break_force) in [(4.0, Real::INFINITY), (12.0, 0.05)] {
+            let weight = ssm.add_body(rect!(v2!(x - 0.25, 3.0), 0.5, 0.5));
+            let rope = RopeBuilder::new(v2!(x, 1.0), v2!(x, 3.0))
+                .set_anchors(Some(ceiling), Some(weight))
+                .set_break_force(break_force)
+                .build_verlet(ssm.bodies());
+            ropes.push((weight, ssm.add_rope(rope)));
+        }
+        run(&mut ssm, &mut engine, 1.0);
+
+        let [(held, strong), (dropped, weak)] = [ropes[0].clone(), ropes[1].clone()];
+        assert!(!strong.borrow().broken.contains(&true));
+        // Snapped once, at the knot, where the tension is greatest; the rest of the rope falls with the weight
+        assert_eq!(weak.borrow().broken, vec![true, false, false, false, false]);
+        assert_eq!(weak.borrow().polylines(1).len(), 1);
+        assert!(ssm.bodies().body(held).center().y < 3.5 && ssm.bodies().body(dropped).center().y > 6.0);
+    }
+
+    #[test]
+    fn anchors_bear_the_tension() {
+        // A weight hanging from a rope doesn't fall, & comes to rest with the rope taut
+        let (mut ssm, mut engine, ceiling) = world();
+        let weight = ssm.add_body(rect!(v2!(9.75, 3.0), 0.5, 0.5));
+        let rope = RopeBuilder::new(v2!(10.0, 1.0), v2!(10.0, 3.0)).set_anchors(Some(ceiling), Some(weight)).build_verlet(ssm.bodies());
+        let rope = ssm.add_rope(rope);
+        run(&mut ssm, &mut engine, 3.0);
+
+        let body = ssm.bodies().body(weight);
+        let knot = body.world_point(rope.borrow().anchors[1].as_ref().unwrap().local);
+        assert!((knot - v2!(10.0, 3.0)).mag() < 0.1, "weight hanging at {knot:?}");
+        assert!(body.velocity.mag() < 0.1, "weight moving at {:?}", body.velocity);
+        assert!((*rope.borrow().points.last().unwrap() - knot).mag() < 0.05);
+    }
+}
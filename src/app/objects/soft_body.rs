@@ -0,0 +1,336 @@
+/*
+    soft_body.rs
+    ----------------------------------------
+    Description:
+    * Provides struct for deformable bodies made off point masses connected by damped springs
+    * Shape is maintained by shape-matching (pulling points towards their best-fit rest pose), or by an internal
+      pressure term acting on the hull
+    * Hull edges collide with rigid bodies as segments, via the CollisionDetector
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::common::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Crd, Material, Vector2, AABB, Real};
+use crate::v2;
+
+/* -------------------- VARIABLES ------------------- */
+const SPRING_STIFFNESS: Real = 10000.0; // Default spring stiffness, per unit of point mass
+const SPRING_DAMPING: Real = 50.0;      // Default spring damping, per unit of point mass
+const SHAPE_DAMPING_RATIO: Real = 0.3;  // Damping ratio of the shape-matching pull
+
+/* ------------------- STRUCTURES ------------------- */
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PointMass {
+    pub position: Vector2<Crd>,
+    pub velocity: Vector2<Real>,
+    pub mass: Real,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Spring {
+    pub points: [usize; 2],
+    pub length: Real,    // Rest length
+    pub stiffness: Real, // Hooke's constant
+    pub damping: Real,   // Damping coefficient along the spring
+}
+
+/// Internal struct for deformable bodies.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct SoftBody {
+    pub points: Vec<PointMass>,
+    pub springs: Vec<Spring>,
+    pub hull: Vec<usize>, // Indices of the points forming the outline, in winding order
+    pub material: Material,
+
+    // Shape keeping
+    pub rest_shape: Vec<Vector2<Real>>, // Point offsets from the centroid in the rest pose
+    pub shape_stiffness: Real,          // Shape-matching stiffness; 0 disables shape matching
+    pub pressure: Real,                 // Internal pressure constant (nRT); 0 disables pressure
+
+    // Meta
+    pub collision_group: i32,
+    pub ignore_groups: Vec<i32>,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl SoftBody {
+    /// Constructor for the SoftBody struct. Mass is the density over the area enclosed by the hull, spread evenly
+    /// over the points. Panics if the hull encloses no area, as its points would have no mass.
+    pub fn new(points: Vec<Vector2<Crd>>, springs: Vec<[usize; 2]>, hull: Vec<usize>, material: Material) -> Self {
+        assert!(hull.len() >= 3, "soft body hull needs at least 3 points, got {}", hull.len());
+        let area: Real = hull.iter().zip(hull.iter().cycle().skip(1))
+            .map(|(&a, &b)| Vector2::cross(points[a], points[b]))
+            .sum::<Real>().abs() * 0.5;
+        assert!(area > 0.0, "soft body hull encloses no area");
+        let point_mass = material.density * area / points.len() as Real;
+
+        let springs = springs.iter().map(|&[a, b]| Spring {
+            points: [a, b],
+            length: (points[b] - points[a]).mag(),
+            stiffness: SPRING_STIFFNESS * point_mass,
+            damping: SPRING_DAMPING * point_mass,
+        }).collect();
+
+        let centroid = points.iter().fold(v2!(0.0), |c, &p| c + p) / points.len() as Real;
+        let rest_shape = points.iter().map(|&p| p - centroid).collect();
+
+        SoftBody {
+            points: points.iter().map(|&position| PointMass { position, velocity: v2!(0.0), mass: point_mass }).collect(),
+            springs,
+            hull,
+            material,
+
+            // Shape keeping
+            rest_shape,
+            shape_stiffness: 0.0,
+            pressure: 0.0,
+
+            // Meta
+            collision_group: 0,
+            ignore_groups: vec![],
+        }
+    }
+
+    /// Ring of points, with springs to each neighbour and to every second point for bending resistance.
+    /// Suited for jelly blobs (with shape matching) and balloons (with pressure). Panics below 3 segments.
+    pub fn ring(center: Vector2<Crd>, radius: Real, segments: usize, material: Material) -> Self {
+        assert!(segments >= 3, "soft body ring needs at least 3 segments, got {segments}");
+        let a = (2.0 * PI) / segments as Real;
+        let points = (0..segments)
+            .map(|i| center + v2!((a * i as Real).cos(), (a * i as Real).sin()) * radius)
+            .collect();
+
+        let mut springs = Vec::new();
+        for i in 0..segments {
+            springs.push([i, (i + 1) % segments]);
+            springs.push([i, (i + 2) % segments]);
+        }
+
+        Self::new(points, springs, (0..segments).collect(), material)
+    }
+
+    /// Two concentric rings braced by radial & diagonal springs; the outer ring forms the hull. Panics below 3 segments.
+    pub fn tyre(center: Vector2<Crd>, outer: Real, inner: Real, segments: usize, material: Material) -> Self {
+        assert!(segments >= 3, "soft body tyre needs at least 3 segments, got {segments}");
+        let a = (2.0 * PI) / segments as Real;
+        let mut points = Vec::new();
+        for radius in [outer, inner] {
+            for i in 0..segments {
+                points.push(center + v2!((a * i as Real).cos(), (a * i as Real).sin()) * radius);
+            }
+        }
+
+        let mut springs = Vec::new();
+        for i in 0..segments {
+            let j = (i + 1) % segments;
+
+            springs.push([i, j]);                       // Outer ring
+            springs.push([segments + i, segments + j]); // Inner ring
+            springs.push([i, segments + i]);            // Radial
+            springs.push([i, segments + j]);            // Diagonals
+            springs.push([j, segments + i]);
+        }
+
+        Self::new(points, springs, (0..segments).collect(), material)
+    }
+
+    /// Physics update for the soft body. Called every substep, with the gravity velocity change for that substep.
+    pub fn update(&mut self, dt: Real, gravity: Vector2<Real>) {
+        let mut forces: Vec<Vector2<Real>> = vec![v2!(0.0); self.points.len()];
+
+        // Damped springs
+        for spring in &self.springs {
+            let [a, b] = spring.points;
+            let d = self.points[b].position - self.points[a].position;
+            if d.mag() == 0.0 { continue; }
+
+            let n = d.norm();
+            let rel_v = Vector2::dot(self.points[b].velocity - self.points[a].velocity, n);
+            let f = n * (spring.stiffness * (d.mag() - spring.length) + spring.damping * rel_v);
+
+            forces[a] = forces[a] + f;
+            forces[b] = forces[b] - f;
+        }
+
+        // Shape matching; damped relative to the body's mean velocity so that rigid motion is left untouched
+        if self.shape_stiffness > 0.0 {
+            let total_mass: Real = self.points.iter().map(|p| p.mass).sum();
+            let mean_v = self.points.iter().fold(v2!(0.0), |v, p| v + p.velocity * p.mass) / total_mass;
+            let damping = 2.0 * SHAPE_DAMPING_RATIO * self.shape_stiffness.sqrt();
+
+            for (i, goal) in self.shape_goals().iter().enumerate() {
+                let point = self.points[i];
+                let pull = (*goal - point.position) * self.shape_stiffness - (point.velocity - mean_v) * damping;
+                forces[i] = forces[i] + pull * point.mass;
+            }
+        }
+
+        // Internal pressure; pushes each hull edge outwards proportionally to its length over the enclosed area
+        if self.pressure > 0.0 {
+            let area = self.area();
+            if area.abs() > 0.0 {
+                for i in 0..self.hull.len() {
+                    let (a, b) = (self.hull[i], self.hull[(i + 1) % self.hull.len()]);
+                    let edge = self.points[b].position - self.points[a].position;
+                    // Outward normal, scaled by the edge length; sign follows the winding of the hull
+                    let normal = v2!(edge.y, -edge.x) * area.signum();
+                    let f = normal * (self.pressure / area.abs() * 0.5);
+
+                    forces[a] = forces[a] + f;
+                    forces[b] = forces[b] + f;
+                }
+            }
+        }
+
+        // Semi-implicit Euler
+        for (point, force) in self.points.iter_mut().zip(forces) {
+            point.velocity = point.velocity + gravity + force / point.mass * dt;
+            point.position = point.position + point.velocity * dt;
+        }
+    }
+
+    /// Best-fit rest pose for the current point positions; a rotated & translated copy of `rest_shape`
+    fn shape_goals(&self) -> Vec<Vector2<Real>> {
+        let total_mass: Real = self.points.iter().map(|p| p.mass).sum();
+        let centroid = self.points.iter().fold(v2!(0.0), |c, p| c + p.position * p.mass) / total_mass;
+
+        // Optimal rotation of the rest shape onto the current shape
+        let (mut cross, mut dot) = (0.0, 0.0);
+        for (point, &rest) in self.points.iter().zip(&self.rest_shape) {
+            let offset = point.position - centroid;
+            cross += Vector2::cross(rest, offset) * point.mass;
+            dot += Vector2::dot(rest, offset) * point.mass;
+        }
+        let (sin, cos) = cross.atan2(dot).sin_cos();
+
+        self.rest_shape.iter()
+            .map(|&q| centroid + v2!(q.x * cos - q.y * sin, q.x * sin + q.y * cos))
+            .collect()
+    }
+
+    /// Signed area enclosed by the hull (shoelace formula)
+    pub fn area(&self) -> Real {
+        let mut area = 0.0;
+        for i in 0..self.hull.len() {
+            let a = self.points[self.hull[i]].position;
+            let b = self.points[self.hull[(i + 1) % self.hull.len()]].position;
+            area += Vector2::cross(a, b);
+        }
+
+        area * 0.5
+    }
+
+    /// Global positions of the hull points, in winding order
+    pub fn hull_points(&self) -> Vec<Vector2<Crd>> {
+        self.hull.iter().map(|&i| self.points[i].position).collect()
+    }
+
+    /// Returns the axis-aligned bounding box of the hull.
+    pub fn aabb(&self) -> AABB {
+        AABB::from_points(&self.hull_points())
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_shape_stiffness(mut self, stiffness: Real) -> Self {
+        self.shape_stiffness = stiffness;
+        self
+    }
+    pub fn set_pressure(mut self, pressure: Real) -> Self {
+        self.pressure = pressure;
+        self
+    }
+    /// Scales the stiffness & damping of every spring
+    pub fn set_spring_scale(mut self, scale: Real) -> Self {
+        for spring in self.springs.iter_mut() {
+            spring.stiffness *= scale;
+            spring.damping *= scale;
+        }
+        self
+    }
+    pub fn set_collision_group(mut self, group: i32) -> Self {
+        self.collision_group = group;
+        self
+    }
+    pub fn set_ignore_groups(mut self, groups: Vec<i32>) -> Self {
+        self.ignore_groups = groups;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Materials;
+
+    const DT: Real = 1.0 / 600.0; // A substep
+
+    /// Squashes the body to the given fraction of its height, about its centroid
+    fn squash(body: &mut SoftBody, fraction: Real) {
+        let centroid = body.points.iter().fold(v2!(0.0), |c, p| c + p.position) / body.points.len() as Real;
+        for point in body.points.iter_mut() {
+            point.position.y = centroid.y + (point.position.y - centroid.y) * fraction;
+        }
+    }
+
+    fn settle(body: &mut SoftBody, seconds: Real) {
+        for _ in 0..(seconds / DT) as usize {
+            body.update(DT, v2!(0.0));
+        }
+    }
+
+    #[test]
+    fn mass_follows_the_hull_area() {
+        // A unit square of four points, with the density of rock
+        let points = vec![v2!(0.0, 0.0), v2!(1.0, 0.0), v2!(1.0, 1.0), v2!(0.0, 1.0)];
+        let square = SoftBody::new(points, vec![[0, 1], [1, 2], [2, 3], [3, 0], [0, 2]], vec![0, 1, 2, 3], Materials::ROCK);
+        assert!(square.points.iter().all(|p| (p.mass - Materials::ROCK.density / 4.0).abs() < 1e-6));
+        assert!((square.area().abs() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 segments")]
+    fn rings_need_segments() {
+        SoftBody::ring(v2!(0.0, 0.0), 1.0, 0, Materials::ROCK);
+    }
+
+    #[test]
+    #[should_panic(expected = "encloses no area")]
+    fn hulls_need_an_area() {
+        SoftBody::new(vec![v2!(0.0, 0.0), v2!(1.0, 0.0), v2!(2.0, 0.0)], vec![], vec![0, 1, 2], Materials::ROCK);
+    }
+
+    #[test]
+    fn shape_matching_restores_a_squashed_blob() {
+        // Without springs, only the shape-matching pull brings the points back
+        let mut blob = SoftBody::ring(v2!(5.0, 5.0), 1.0, 16, Materials::ROCK).set_spring_scale(0.0).set_shape_stiffness(400.0);
+        let rest = blob.area();
+        squash(&mut blob, 0.5);
+        assert!(blob.area() < rest * 0.6);
+
+        settle(&mut blob, 2.0);
+        for (point, goal) in blob.points.iter().zip(blob.shape_goals()) {
+            assert!((point.position - goal).mag() < 0.01, "{:?} is away from {goal:?}", point.position);
+        }
+        assert!((blob.area() - rest).abs() < rest * 0.01);
+    }
+
+    #[test]
+    fn pressurised_ring_keeps_its_area() {
+        // Soft springs, held out by the pressure; a squashed ring swells back to its area within a second
+        let mut balloon = SoftBody::ring(v2!(5.0, 5.0), 1.0, 16, Materials::ROCK).set_spring_scale(0.05).set_pressure(20.0);
+        settle(&mut balloon, 2.0);
+        let inflated = balloon.area();
+
+        squash(&mut balloon, 0.6);
+        assert!(balloon.area() < inflated * 0.7);
+        // Lightly damped, it wobbles about its area for a while after
+        let swelled = (0..(1.0 / DT) as usize).map(|_| {
+            balloon.update(DT, v2!(0.0));
+            balloon.area()
+        }).fold(0.0, Real::max);
+        assert!(swelled > inflated * 0.98, "area {swelled} after squashing, {inflated} before");
+    }
+}
@@ -0,0 +1,304 @@
+/*
+    vehicle.rs
+    ----------------------------------------
+    Description:
+    * Provides a ready-made car; a chassis body with wheels on wheel joints
+    * Each wheel joint is a prismatic suspension spring to a hub, plus a revolute drive joint from hub to wheel
+    * Hubs are squares as wide as the wheel's radius, hidden inside it; a hub much lighter than its wheel would soak up
+      the drive & brake torques rather than pass them on to the chassis
+    * Wheels are driven by the drive joints' motors, from throttle & brake input
+    * Wheels aren't true circles; the narrow phase only handles polygons, so they are regular polygons of WHEEL_SIDES
+      sides, which roll with a slight bump as each vertex passes under them
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::rc::Rc;
+
+use crate::app::arena::BodyHandle;
+use crate::app::joints::{Joint, JointKind, Motor};
+use crate::app::objects::Body;
+use crate::app::ssm::SystemStateManager;
+use crate::common::{BodyForm, Crd, Material, Materials, TJointRef, Vector2, Real};
+use crate::{poly, rect, v2};
+
+/* -------------------- VARIABLES ------------------- */
+const VEHICLE_GROUP: i32 = -3; // Default collision group of vehicle parts; parts ignore each other
+const WHEEL_SIDES: u32 = 16;   // Sides of the polygons standing in for round wheels
+
+/* ------------------- STRUCTURES ------------------- */
+/// Internal struct for a spawned vehicle; steers the motors of its drive joints.
+#[derive(PartialEq, Debug)]
+pub struct Vehicle {
+    pub chassis: BodyHandle,
+    pub wheels: Vec<BodyHandle>,
+    pub hubs: Vec<BodyHandle>,
+    pub suspension: Vec<TJointRef>, // Prismatic chassis-hub joints
+    pub axles: Vec<TJointRef>,      // Revolute hub-wheel joints
+    pub driven: Vec<bool>,          // Per wheel
+
+    pub max_speed: Real,    // Wheel speed at full throttle, rad/s
+    pub torque: Real,       // Maximum drive torque per driven wheel, N m
+    pub brake_torque: Real, // Maximum braking torque per wheel, N m
+
+    // Input
+    pub throttle: Real, // -1..=1; negative reverses
+    pub brake: bool,
+}
+
+/// Builder for vehicles centered on `position`.
+pub struct VehicleBuilder {
+    position: Vector2<Crd>,
+    size: Vector2<Real>,
+    wheel_radius: Real,
+    wheels: Vec<(Vector2<Crd>, bool)>, // Mount points relative to the chassis center, and whether they are driven
+    center_of_mass: Vector2<Crd>,       // Offset from the chassis center
+
+    frequency: Real,
+    damping_ratio: Real,
+    travel: Real, // Suspension travel either side of the rest position
+
+    max_speed: Real,
+    torque: Real,
+    brake_torque: Real,
+
+    material: Material,
+    wheel_material: Material,
+    collision_group: i32,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl VehicleBuilder {
+    pub fn new(position: Vector2<Crd>) -> Self {
+        VehicleBuilder {
+            position,
+            size: v2!(2.4, 0.6),
+            wheel_radius: 0.36,
+            wheels: vec![(v2!(-0.84, 0.4), true), (v2!(0.84, 0.4), true)],
+            center_of_mass: v2!(0.0),
+
+            frequency: 4.0,
+            damping_ratio: 0.7,
+            travel: 0.16,
+
+            max_speed: 20.0,
+            torque: 1.5,
+            brake_torque: 3.0,
+
+            material: Materials::METAL,
+            wheel_material: Materials::RUBBER,
+            collision_group: VEHICLE_GROUP,
+        }
+    }
+
+    /// Spawn the chassis, wheels & joints into the system.
+    pub fn build(self, ssm: &mut SystemStateManager) -> Vehicle {
+        let group = self.collision_group;
+        let part = |body: Body| body.set_collision_group(group).set_ignore_groups(vec![group]);
+
+        let chassis = ssm.add_body(part(
+            rect!(self.position, self.size.x, self.size.y, self.material)
+                .set_center(self.position)
+                .set_center_of_mass(self.center_of_mass)
+        ));
+
+        let mut vehicle = Vehicle {
+            chassis,
+            wheels: Vec::new(),
+            hubs: Vec::new(),
+            suspension: Vec::new(),
+            axles: Vec::new(),
+            driven: Vec::new(),
+
+            max_speed: self.max_speed,
+            torque: self.torque,
+            brake_torque: self.brake_torque,
+
+            // Input
+            throttle: 0.0,
+            brake: false,
+        };
+
+        for &(mount, driven) in &self.wheels {
+            let mount = self.position + mount;
+
+            let hub = ssm.add_body(part(
+                rect!(mount, self.wheel_radius, self.wheel_radius, self.wheel_material).set_center(mount)
+            ));
+            let wheel = ssm.add_body(part(
+                poly!(mount, self.wheel_radius, WHEEL_SIDES, self.wheel_material).set_center(mount)
+            ));
+
+            let suspension = Joint::new(JointKind::Prismatic, ssm.bodies(), chassis, hub, mount, mount)
+                .set_axis(v2!(0.0, 1.0))
+                .set_spring(self.frequency, self.damping_ratio)
+                .set_limits(-self.travel, self.travel, 0.0);
+            let axle = Joint::new(JointKind::Revolute, ssm.bodies(), hub, wheel, mount, mount);
+
+            vehicle.suspension.push(ssm.add_joint(suspension));
+            vehicle.axles.push(ssm.add_joint(axle));
+            vehicle.hubs.push(hub);
+            vehicle.wheels.push(wheel);
+            vehicle.driven.push(driven);
+        }
+
+        vehicle
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_size(mut self, width: Real, height: Real) -> Self {
+        self.size = v2!(width, height);
+        self
+    }
+    pub fn set_wheel_radius(mut self, radius: Real) -> Self {
+        self.wheel_radius = radius;
+        self
+    }
+    /// Replaces the wheels; each is a mount point relative to the chassis center, and whether it is driven
+    pub fn set_wheels(mut self, wheels: Vec<(Vector2<Crd>, bool)>) -> Self {
+        self.wheels = wheels;
+        self
+    }
+    /// Offset of the chassis' center of mass from its center; lower it to make the vehicle harder to flip
+    pub fn set_center_of_mass(mut self, offset: Vector2<Crd>) -> Self {
+        self.center_of_mass = offset;
+        self
+    }
+    pub fn set_suspension(mut self, frequency: Real, damping_ratio: Real, travel: Real) -> Self {
+        self.frequency = frequency;
+        self.damping_ratio = damping_ratio;
+        self.travel = travel;
+        self
+    }
+    pub fn set_motor(mut self, max_speed: Real, torque: Real) -> Self {
+        self.max_speed = max_speed;
+        self.torque = torque;
+        self
+    }
+    pub fn set_brake_torque(mut self, brake_torque: Real) -> Self {
+        self.brake_torque = brake_torque;
+        self
+    }
+    pub fn set_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+    pub fn set_wheel_material(mut self, material: Material) -> Self {
+        self.wheel_material = material;
+        self
+    }
+    pub fn set_collision_group(mut self, group: i32) -> Self {
+        self.collision_group = group;
+        self
+    }
+}
+
+impl Vehicle {
+    /// Update the drive motors from the current input. Braking locks every wheel; otherwise driven wheels are
+    /// motored towards the throttled speed, and the rest roll freely.
+    fn update_motors(&self) {
+        for (axle, &driven) in self.axles.iter().zip(&self.driven) {
+            axle.borrow_mut().motor = if self.brake {
+                Some(Motor { speed: 0.0, max_force: self.brake_torque })
+            } else if driven && self.throttle != 0.0 {
+                Some(Motor { speed: self.throttle * self.max_speed, max_force: self.torque })
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Swap the vehicle's bodies & joints for those at the same positions in another system; e.g. a rebuilt copy
+    pub fn remap(&mut self, from: &SystemStateManager, to: &SystemStateManager) {
+        let body = |&b: &BodyHandle| {
+            let i = from.bodies().iter().position(|(o, _)| o == b).expect("vehicle body not in system");
+            to.bodies().handles()[i]
+        };
+        let joint = |j: &TJointRef| {
+            let i = from.joints().iter().position(|o| Rc::ptr_eq(o, j)).expect("vehicle joint not in system");
+            to.joints()[i].clone()
+        };
+
+        self.chassis = body(&self.chassis);
+        self.wheels = self.wheels.iter().map(body).collect();
+        self.hubs = self.hubs.iter().map(body).collect();
+        self.suspension = self.suspension.iter().map(joint).collect();
+        self.axles = self.axles.iter().map(joint).collect();
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    pub fn set_throttle(&mut self, throttle: Real) {
+        self.throttle = throttle.clamp(-1.0, 1.0);
+        self.update_motors();
+    }
+    pub fn set_brake(&mut self, brake: bool) {
+        self.brake = brake;
+        self.update_motors();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::app::engine::{Engine, Solver};
+    use crate::app::objects::BodyView;
+    use crate::common::Shared;
+
+    const DT: Real = 1.0 / 60.0;
+
+    /// An engine, & a world holding a long floor whose top is at y = 10, with a car parked on it
+    fn world() -> (SystemStateManager, Engine, Vehicle) {
+        let engine = Engine::new(Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        })));
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(-20.0, 10.0), 80.0, 1.0).set_frozen(true));
+        let vehicle = VehicleBuilder::new(v2!(5.0, 9.2)).build(&mut ssm);
+        (ssm, engine, vehicle)
+    }
+
+    fn run(ssm: &mut SystemStateManager, engine: &mut Engine, seconds: Real) {
+        for _ in 0..(seconds / DT) as usize {
+            engine.step(ssm, DT);
+        }
+    }
+
+    #[test]
+    fn throttle_drives_and_brake_stops_the_wheels() {
+        for solver in [Solver::Impulse, Solver::XPBD] {
+            let (mut ssm, mut engine, mut vehicle) = world();
+            engine.set_solver(solver);
+            run(&mut ssm, &mut engine, 0.5);
+            let parked = ssm.bodies().body(vehicle.chassis).center();
+
+            // Full throttle turns the driven wheels forwards, carrying the car along
+            vehicle.set_throttle(1.0);
+            run(&mut ssm, &mut engine, 1.5);
+            let chassis = ssm.bodies().body(vehicle.chassis);
+            assert!(chassis.center().x - parked.x > 1.0, "{solver:?} car only got to {:?}", chassis.center());
+            assert!(chassis.velocity.x > 1.0 && chassis.rotation.abs() < 0.3);
+            for &wheel in &vehicle.wheels {
+                assert!(ssm.bodies().body(wheel).angular_velocity > 1.0);
+            }
+
+            // Braking locks the wheels until the car stops
+            vehicle.set_throttle(0.0);
+            vehicle.set_brake(true);
+            run(&mut ssm, &mut engine, 3.0);
+            assert!(ssm.bodies().body(vehicle.chassis).velocity.mag() < 0.05, "{solver:?} car still rolling");
+            for &wheel in &vehicle.wheels {
+                assert!(ssm.bodies().body(wheel).angular_velocity.abs() < 0.1);
+            }
+
+            // Reversing drives it back
+            vehicle.set_brake(false);
+            vehicle.set_throttle(-1.0);
+            run(&mut ssm, &mut engine, 1.0);
+            assert!(ssm.bodies().body(vehicle.chassis).velocity.x < -0.5);
+        }
+    }
+}
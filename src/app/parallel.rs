@@ -0,0 +1,57 @@
+/*
+    parallel.rs
+    ----------------------------------------
+    Description:
+    * Provides data-parallel helpers, run on scoped worker threads
+    * Work is split into contiguous chunks & results are gathered in order, so the outcome never depends on the
+      number of threads
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::num::NonZeroUsize;
+use std::thread;
+
+/* -------------------- FUNCTIONS ------------------- */
+/// Threads available to the process; 1 if unknown
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Maps every item on up to `threads` threads, giving each at least `min_chunk` items; results are in item order
+pub fn map<T, R, F>(items: &[T], threads: usize, min_chunk: usize, f: F) -> Vec<R>
+where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+    let chunk = chunk_size(items.len(), threads, min_chunk);
+    if chunk >= items.len() {
+        return items.iter().map(f).collect();
+    }
+
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items.chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("worker thread panicked")).collect()
+    })
+}
+
+/// Runs f on every item on up to `threads` threads, giving each at least `min_chunk` items
+pub fn for_each_mut<T, F>(items: &mut [T], threads: usize, min_chunk: usize, f: F)
+where T: Send, F: Fn(&mut T) + Sync {
+    let chunk = chunk_size(items.len(), threads, min_chunk);
+    if chunk >= items.len() {
+        items.iter_mut().for_each(f);
+        return;
+    }
+
+    let f = &f;
+    thread::scope(|scope| {
+        for chunk in items.chunks_mut(chunk) {
+            scope.spawn(move || chunk.iter_mut().for_each(f));
+        }
+    });
+}
+
+/// Items per thread
+fn chunk_size(len: usize, threads: usize, min_chunk: usize) -> usize {
+    len.div_ceil(threads.max(1)).max(min_chunk).max(1)
+}
@@ -0,0 +1,454 @@
+/*
+    replay.rs
+    ----------------------------------------
+    Description:
+    * Provides deterministic recording & replay of simulation runs
+    * A recording holds the starting scene, the random seed, and every external input stamped with the step it was
+      applied before. Replaying it from the same scene & seed reproduces the run bit for bit
+    * A hash of the simulated state is recorded after every step, so that a replay can report the first step
+      at which it diverged
+    * Recordings are stored as a binary log (bincode)
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::BodyHandle;
+use crate::app::engine::Engine;
+use crate::app::joints::{Joint, Motor};
+use crate::app::scene::{BodyDesc, Scene, SceneError, SCENE_VERSION};
+use crate::app::ssm::SystemStateManager;
+use crate::common::{seed_rng, Crd, Vector2, Real};
+
+/* -------------------- VARIABLES ------------------- */
+pub const RECORDING_VERSION: u32 = 2; // 2: world size in metres, rather than the window size
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/* ------------------- STRUCTURES ------------------- */
+/// An external action on the world. Bodies are given by their handle; joints & characters by their index in the system.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Impulse at an offset from the body's center of mass
+    Impulse { body: BodyHandle, impulse: Vector2<Real>, offset: Vector2<Real> },
+    /// Replaces a joint's motor; e.g. a vehicle's throttle & brake
+    Motor { joint: usize, motor: Option<Motor> },
+    /// Sets a character's velocity
+    Walk { character: usize, velocity: Vector2<Real> },
+    Jump { character: usize },
+    Spawn(Box<BodyDesc>),
+    /// Removes the body, with the joints & characters attached to it
+    Remove { body: BodyHandle },
+    /// Grabs the body at a global point with a mouse joint, added after the system's other joints
+    Grab { body: BodyHandle, point: Vector2<Crd> },
+    /// Moves a mouse joint's target
+    Drag { joint: usize, target: Vector2<Crd> },
+    /// Removes a mouse joint, letting go of its body
+    Release { joint: usize },
+}
+
+/// A recorded run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub version: u32,
+    pub seed: u64,
+    pub dt: Real,
+    pub world_size: Vector2<Crd>, // The broad phase grid spans the world in view; replays need the same size
+    pub scene: Scene,
+    pub inputs: Vec<(u64, Input)>, // Each stamped with the step it was applied before
+    pub hashes: Vec<u64>,          // State hash after each step
+}
+
+/// Internal struct which steps the simulation while recording it.
+pub struct Recorder {
+    recording: Recording,
+}
+
+/// Internal struct which steps the simulation through a recording.
+pub struct Replayer {
+    recording: Recording,
+    step: usize,
+    input: usize, // Next input to apply
+    divergence: Option<Divergence>,
+}
+
+/// The first step at which a replay's state differed from the recording's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub step: u64,
+    pub expected: u64,
+    pub found: u64,
+}
+
+/// FNV-1a hasher; unlike std's hashers, guaranteed to give the same hash on every run & build
+pub(crate) struct Fnv(u64);
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Format(bincode::Error),
+    Version(u32),
+    Scene(SceneError),
+    /// An input refers to something that doesn't exist
+    Input { step: u64, message: String },
+    WorldSize { expected: Vector2<Crd>, found: Vector2<Crd> },
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Input {
+    pub fn apply(&self, ssm: &mut SystemStateManager, engine: &mut Engine, step: u64) -> Result<(), ReplayError> {
+        let missing = |what: &str, index: usize| ReplayError::Input { step, message: format!("no {what} with index {index}") };
+
+        match self {
+            Input::Impulse { body, impulse, offset } => {
+                let mut body = ssm.bodies_mut().get_mut(*body)
+                    .ok_or_else(|| ReplayError::Input { step, message: format!("no body with handle {body:?}") })?;
+                body.apply_impulse(*impulse, *offset);
+            }
+            Input::Motor { joint, motor } => {
+                let joint_ref = ssm.joints().get(*joint).ok_or_else(|| missing("joint", *joint))?;
+                joint_ref.borrow_mut().motor = *motor;
+            }
+            Input::Walk { character, velocity } => {
+                let character_ref = ssm.characters().get(*character).ok_or_else(|| missing("character", *character))?;
+                character_ref.borrow_mut().velocity = *velocity;
+            }
+            Input::Jump { character } => {
+                let character_ref = ssm.characters().get(*character).ok_or_else(|| missing("character", *character))?;
+                character_ref.borrow_mut().jump();
+            }
+            Input::Spawn(desc) => {
+                let scene = Scene { version: SCENE_VERSION, engine: Default::default(), bodies: vec![(**desc).clone()], joints: vec![] };
+                scene.build(ssm, engine).map_err(ReplayError::Scene)?;
+            }
+            Input::Remove { body } => {
+                ssm.remove_body(*body).ok_or_else(|| ReplayError::Input { step, message: format!("no body with handle {body:?}") })?;
+                engine.forget_body(*body);
+            }
+            Input::Grab { body, point } => {
+                if !ssm.bodies().contains(*body) {
+                    return Err(ReplayError::Input { step, message: format!("no body with handle {body:?}") });
+                }
+                ssm.add_joint(Joint::mouse(ssm.bodies(), *body, *point));
+            }
+            Input::Drag { joint, target } => {
+                let joint_ref = ssm.joints().get(*joint).ok_or_else(|| missing("joint", *joint))?;
+                joint_ref.borrow_mut().set_target(*target);
+            }
+            Input::Release { joint } => {
+                let joint_ref = ssm.joints().get(*joint).ok_or_else(|| missing("joint", *joint))?.clone();
+                ssm.remove_joint(&joint_ref);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Recording {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let bytes = fs::read(path)?;
+
+        // The version leads the log; check it before the rest, whose layout may differ between versions
+        let version: u32 = bincode::deserialize(&bytes)?;
+        if version != RECORDING_VERSION {
+            return Err(ReplayError::Version(version));
+        }
+
+        Ok(bincode::deserialize(&bytes)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        fs::write(path, bincode::serialize(self)?)?;
+        Ok(())
+    }
+}
+
+impl Recorder {
+    /// Start recording a world freshly built from the scene. Reseeds the random number generator,
+    /// and restarts the engine's clock.
+    pub fn new(scene: Scene, seed: u64, dt: Real, world_size: Vector2<Crd>, engine: &mut Engine) -> Self {
+        seed_rng(seed);
+        engine.set_clock(0.0, 0);
+
+        Recorder {
+            recording: Recording {
+                version: RECORDING_VERSION,
+                seed,
+                dt,
+                world_size,
+                scene,
+                inputs: Vec::new(),
+                hashes: Vec::new(),
+            },
+        }
+    }
+
+    /// Apply an input before the next step, and record it
+    pub fn apply(&mut self, input: Input, ssm: &mut SystemStateManager, engine: &mut Engine) -> Result<(), ReplayError> {
+        input.apply(ssm, engine, self.recording.hashes.len() as u64)?;
+        self.record(input);
+        Ok(())
+    }
+
+    /// Record an input which was already applied before the next step
+    pub fn record(&mut self, input: Input) {
+        let step = self.recording.hashes.len() as u64;
+        self.recording.inputs.push((step, input));
+    }
+
+    pub fn step(&mut self, ssm: &mut SystemStateManager, engine: &mut Engine) {
+        engine.step(ssm, self.recording.dt);
+        self.recording.hashes.push(state_hash(ssm, engine));
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+}
+
+impl Replayer {
+    /// Start replaying onto a world freshly built from the recording's scene. Reseeds the random number generator,
+    /// and restarts the engine's clock.
+    pub fn new(recording: Recording, engine: &mut Engine) -> Self {
+        seed_rng(recording.seed);
+        engine.set_clock(0.0, 0);
+
+        Replayer {
+            recording,
+            step: 0,
+            input: 0,
+            divergence: None,
+        }
+    }
+
+    /// Apply the inputs recorded for the next step, then step & compare the state against the recording
+    pub fn step(&mut self, ssm: &mut SystemStateManager, engine: &mut Engine) -> Result<(), ReplayError> {
+        if self.finished() { return Ok(()); }
+        let step = self.step as u64;
+
+        while let Some((stamp, input)) = self.recording.inputs.get(self.input) {
+            if *stamp != step { break; }
+            input.apply(ssm, engine, step)?;
+            self.input += 1;
+        }
+
+        engine.step(ssm, self.recording.dt);
+
+        let expected = self.recording.hashes[self.step];
+        let found = state_hash(ssm, engine);
+        if found != expected && self.divergence.is_none() {
+            self.divergence = Some(Divergence { step, expected, found });
+        }
+
+        self.step += 1;
+        Ok(())
+    }
+
+    /// Replay every remaining step; returns the first divergence, if any
+    pub fn run(&mut self, ssm: &mut SystemStateManager, engine: &mut Engine) -> Result<Option<Divergence>, ReplayError> {
+        while !self.finished() {
+            self.step(ssm, engine)?;
+        }
+        Ok(self.divergence)
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn finished(&self) -> bool {
+        self.step >= self.recording.hashes.len()
+    }
+    pub fn divergence(&self) -> Option<Divergence> {
+        self.divergence
+    }
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+}
+
+/// Hash of the simulated state; bodies, joints, soft bodies, ropes, particles, characters & the clock
+pub fn state_hash(ssm: &SystemStateManager, engine: &Engine) -> u64 {
+    let mut hash = Fnv::new();
+
+    hash.add(engine.steps());
+    for (_, body) in ssm.bodies().iter() {
+        hash.add_v2(body.position);
+        hash.add_v2(body.velocity);
+        hash.add_real(body.rotation);
+        hash.add_real(body.angular_velocity);
+    }
+    for joint_ref in ssm.joints() {
+        hash.add(joint_ref.borrow().broken as u64);
+    }
+    for soft_ref in ssm.soft_bodies() {
+        for point in &soft_ref.borrow().points {
+            hash.add_v2(point.position);
+            hash.add_v2(point.velocity);
+        }
+    }
+    for rope_ref in ssm.ropes() {
+        for &point in &rope_ref.borrow().points {
+            hash.add_v2(point);
+        }
+    }
+    for particle in &ssm.particles().borrow().particles {
+        hash.add_v2(particle.position);
+        hash.add_v2(particle.velocity);
+    }
+    for character_ref in ssm.characters() {
+        hash.add_v2(character_ref.borrow().velocity);
+    }
+
+    hash.finish()
+}
+
+impl Fnv {
+    pub(crate) fn new() -> Fnv {
+        Fnv(FNV_OFFSET)
+    }
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+    pub(crate) fn add(&mut self, bits: u64) {
+        self.add_bytes(&bits.to_le_bytes());
+    }
+    fn add_real(&mut self, x: Real) {
+        self.add_bytes(&x.to_le_bytes());
+    }
+    fn add_v2(&mut self, v: Vector2<Real>) {
+        self.add_real(v.x);
+        self.add_real(v.y);
+    }
+    fn add_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replay diverged at step {}; state hash {:016x}, recorded {:016x}", self.step, self.found, self.expected)
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "couldn't access recording: {e}"),
+            ReplayError::Format(e) => write!(f, "malformed recording: {e}"),
+            ReplayError::Version(version) => {
+                write!(f, "unsupported recording version {version}; expected version {RECORDING_VERSION}")
+            }
+            ReplayError::Scene(e) => write!(f, "recording's scene: {e}"),
+            ReplayError::Input { step, message } => write!(f, "input at step {step}: {message}"),
+            ReplayError::WorldSize { expected, found } => write!(
+                f, "recorded with {}x{} m in view, but {}x{} m are in view", expected.x, expected.y, found.x, found.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for ReplayError {
+    fn from(e: bincode::Error) -> Self {
+        ReplayError::Format(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::objects::Body;
+    use crate::common::{BodyForm, Materials, Shared};
+    use crate::{rect, v2};
+
+    const DT: Real = 1.0 / 60.0;
+
+    fn new_engine() -> Engine {
+        Engine::new(Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        })))
+    }
+
+    /// A world freshly built from the scene, with the handles of its bodies
+    fn world(scene: &Scene) -> (SystemStateManager, Engine, Vec<BodyHandle>) {
+        let (mut ssm, mut engine) = (SystemStateManager::new(), new_engine());
+        let bodies = scene.build(&mut ssm, &mut engine).unwrap();
+        (ssm, engine, bodies)
+    }
+
+    /// Records a second of a stack of crates being knocked over, & another dropped beside it
+    fn record() -> Recording {
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(0.0, 11.0), 20.0, 1.0).set_frozen(true));
+        for i in 0..4 {
+            ssm.add_body(rect!(v2!(9.0, 10.0 - i as Real), 1.0, 1.0));
+        }
+        let scene = Scene::capture(&ssm, &new_engine(), &[]).unwrap();
+
+        let (mut ssm, mut engine, bodies) = world(&scene);
+        let mut recorder = Recorder::new(scene, 7, DT, v2!(20.0, 12.0), &mut engine);
+        for step in 0..60 {
+            if step == 5 {
+                let input = Input::Impulse { body: bodies[4], impulse: v2!(2.0, 0.0), offset: v2!(0.0, -0.5) };
+                recorder.apply(input, &mut ssm, &mut engine).unwrap();
+            }
+            if step == 20 {
+                let desc = BodyDesc::of(rect!(v2!(4.0, 2.0), 0.5, 0.5));
+                recorder.apply(Input::Spawn(Box::new(desc)), &mut ssm, &mut engine).unwrap();
+            }
+            recorder.step(&mut ssm, &mut engine);
+        }
+        recorder.recording().clone()
+    }
+
+    #[test]
+    fn recorded_runs_replay_identically() {
+        let recording = record();
+        let recording: Recording = bincode::deserialize(&bincode::serialize(&recording).unwrap()).unwrap();
+
+        let (mut ssm, mut engine, _) = world(&recording.scene);
+        let mut replayer = Replayer::new(recording.clone(), &mut engine);
+        let mut hashes = Vec::new();
+        while !replayer.finished() {
+            replayer.step(&mut ssm, &mut engine).unwrap();
+            hashes.push(state_hash(&ssm, &engine));
+        }
+
+        assert_eq!(replayer.divergence(), None);
+        assert_eq!(hashes, recording.hashes);
+    }
+
+    #[test]
+    fn tampered_inputs_report_the_first_diverging_step() {
+        let mut recording = record();
+        let (_, Input::Impulse { impulse, .. }) = &mut recording.inputs[0] else { panic!("expected the impulse first") };
+        *impulse = *impulse * 1.01;
+
+        let (mut ssm, mut engine, _) = world(&recording.scene);
+        let divergence = Replayer::new(recording.clone(), &mut engine).run(&mut ssm, &mut engine).unwrap().unwrap();
+        assert_eq!(divergence.step, 5);
+        assert_eq!(divergence.expected, recording.hashes[5]);
+        assert_ne!(divergence.found, divergence.expected);
+    }
+}
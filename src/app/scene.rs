@@ -0,0 +1,753 @@
+/*
+    scene.rs
+    ----------------------------------------
+    Description:
+    * Provides the scene file format; a versioned JSON description of the world, so that layouts can be
+      changed without recompiling
+    * A scene holds the engine settings, bodies & joints. Soft bodies, ropes, particles and force fields are
+      not part of it; vehicles are saved as their bodies & joints, and characters get a default controller
+    * Saved scenes hold the full state of each body & joint, so that loading them back is lossless.
+      Hand-written scenes can leave most fields out; see the descriptions below for their defaults
+    * Bodies & joints may be named by their path in the group hierarchy, e.g. `"name": "level1/bridge/plank3"`;
+      the groups along it are created on load. A single group can be saved on its own with `Scene::capture_group`
+    * Lengths are in metres, masses in kilograms & times in seconds
+    * Format, version 2:
+      {
+        "version": 2,
+        "engine": { "gravity": { "x": 0.0, "y": 1.0, "m": 9.81 }, "solver": "Impulse", "integrator": "RK4" },
+        "bodies": [
+          { "position": { "x": 1.0, "y": 5.0 }, "size": { "x": 10.0, "y": 1.0 }, "body_type": "Static" },
+          { "position": { "x": 1.0, "y": 0.0 }, "radius": 1.0, "sides": 5,
+            "material": { "density": 0.2, "e": 1.2, "ks": 0.08, "kd": 0.01 } }
+        ],
+        "joints": [
+          { "kind": "Revolute", "bodies": [0, 1], "anchors": [{ "x": 0.0, "y": 0.0 }, { "x": 0.0, "y": 0.0 }] }
+        ]
+      }
+    * Enum values use the Rust variant names; BodyForm, JointKind, Solver, Integrator & BodyType
+    * Optionally, the engine holds `"world_bounds": { "min": {..}, "max": {..} }`, and bodies a `"lifetime"` in seconds
+    * Errors in parsed scenes, malformed or invalid, give the line, column & path of the field at fault
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::engine::{Engine, Solver, WorldBounds};
+use crate::app::integrator::Integrator;
+use crate::app::joints::{Joint, JointKind, Limits, Motor};
+use crate::app::objects::{Body, BodyRef, BodyView};
+use crate::app::ssm::{Group, SystemStateManager};
+use crate::common::{BodyForm, Crd, Material, Materials, TJointRef, Vector2, Vector2M, Vertex, Real};
+use crate::{v2, vtx};
+
+/* -------------------- VARIABLES ------------------- */
+pub const SCENE_VERSION: u32 = 2; // 2: lengths in metres, rather than pixels
+
+/* ------------------- STRUCTURES ------------------- */
+/// A world, as stored in a scene file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    pub version: u32,
+    #[serde(default)]
+    pub engine: EngineDesc,
+    #[serde(default)]
+    pub bodies: Vec<BodyDesc>,
+    #[serde(default)]
+    pub joints: Vec<JointDesc>,
+}
+
+/// Engine settings; settings left out keep the engine's current value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EngineDesc {
+    #[serde(default)]
+    pub gravity: Option<Vector2M<Real>>,
+    #[serde(default)]
+    pub solver: Option<Solver>,
+    #[serde(default)]
+    pub integrator: Option<Integrator>,
+    #[serde(default)]
+    pub world_bounds: Option<WorldBounds>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum BodyType {
+    /// Moved by the simulation
+    #[default]
+    Dynamic,
+    /// Frozen in place
+    Static,
+    /// Moved by a character controller
+    Kinematic,
+}
+
+/// A body. Its shape is given by `size` (rect-likes), `radius` & `sides` (regular polygons), or `vertices`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BodyDesc {
+    #[serde(default)]
+    pub name: Option<String>, // Path in the group hierarchy
+    #[serde(default = "polygon")]
+    pub form: BodyForm,
+    pub position: Vector2<Crd>, // As given to `rect!` & `poly!`
+    #[serde(default)]
+    pub rotation: Real,
+    #[serde(default)]
+    pub size: Option<Vector2<Real>>,
+    #[serde(default)]
+    pub radius: Option<Real>,
+    #[serde(default)]
+    pub sides: Option<u32>,
+    #[serde(default)]
+    pub vertices: Option<Vec<Vector2<Crd>>>, // About the origin; overrides the shape's computed vertices
+    #[serde(default)]
+    pub origin: Option<Vector2<Crd>>,        // Offset of the center of mass from the position
+    #[serde(default = "rock")]
+    pub material: Material,
+    #[serde(default)]
+    pub body_type: BodyType,
+
+    // Filters
+    #[serde(default)]
+    pub collision_group: i32,
+    #[serde(default)]
+    pub ignore_groups: Vec<i32>,
+    #[serde(default = "yes")]
+    pub enabled: bool,
+    #[serde(default = "yes")]
+    pub visible: bool,
+
+    // State; mass & inertia default to the values computed from the shape & material
+    #[serde(default)]
+    pub mass: Option<Real>,
+    #[serde(default)]
+    pub inertia: Option<Real>,
+    #[serde(default = "zero")]
+    pub velocity: Vector2<Real>,
+    #[serde(default)]
+    pub angular_velocity: Real,
+    #[serde(default)]
+    pub lifetime: Option<Real>, // Simulated seconds before the body despawns; lives forever if left out
+}
+
+/// A joint between two bodies, given by their index in `bodies`. Fields left out take the value `Joint::new`
+/// (or `Joint::pulley`, `Joint::gear`) gives them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct JointDesc {
+    #[serde(default)]
+    pub name: Option<String>, // Path in the group hierarchy
+    pub kind: JointKind,
+    pub bodies: [usize; 2],
+    pub anchors: [Vector2<Crd>; 2], // In each body's local space
+    #[serde(default)]
+    pub axis: Option<Vector2<Real>>,
+    #[serde(default)]
+    pub reference_angle: Option<Real>,
+    #[serde(default)]
+    pub length: Option<Real>,
+    #[serde(default)]
+    pub compliance: Real,
+    #[serde(default)]
+    pub break_force: Option<Real>, // Unbreakable if left out
+    #[serde(default)]
+    pub broken: bool,
+    #[serde(default = "yes")]
+    pub enabled: bool,
+    #[serde(default = "yes")]
+    pub visible: bool,
+
+    // Extensions
+    #[serde(default)]
+    pub motor: Option<Motor>,
+    #[serde(default)]
+    pub limits: Option<Limits>,
+    #[serde(default)]
+    pub frequency: Real,
+    #[serde(default)]
+    pub damping_ratio: Real,
+
+    // Coupling
+    #[serde(default)]
+    pub ground: Option<[Vector2<Crd>; 2]>, // Required by pulleys
+    #[serde(default = "one")]
+    pub ratio: Real,
+    #[serde(default)]
+    pub coupled: Option<[usize; 2]>,        // Required by gears; indices of earlier joints
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    /// Malformed file; the field is given as a path, e.g. `bodies[2].material.density`, unless the JSON itself is broken
+    Syntax { line: usize, column: usize, field: Option<String>, message: String },
+    /// Well-formed, but the values don't describe a valid world. Scenes parsed from text give the line & column of
+    /// the field, or of the nearest enclosing one it's missing from
+    Invalid { position: Option<(usize, usize)>, field: String, message: String },
+    Version(u32),
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+fn polygon() -> BodyForm { BodyForm::Polygon }
+fn rock() -> Material { Materials::ROCK }
+fn zero() -> Vector2<Real> { v2!(0.0) }
+fn one() -> Real { 1.0 }
+fn yes() -> bool { true }
+
+impl Scene {
+    /// Parse a scene from its JSON text
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        // Check the version first, so that files from other versions aren't reported as malformed
+        #[derive(Deserialize)]
+        struct Header { version: u32 }
+
+        let header: Header = deserialize(text)?;
+        if header.version != SCENE_VERSION {
+            return Err(SceneError::Version(header.version));
+        }
+
+        let scene: Scene = deserialize(text)?;
+        scene.validate().map_err(|e| match e {
+            SceneError::Invalid { field, message, .. } => SceneError::Invalid { position: locate(text, &field), field, message },
+            e => e,
+        })?;
+
+        Ok(scene)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("scenes only hold serialisable values")
+    }
+
+    /// Describe the bodies & joints in the system, and the engine's settings. Excluded bodies are left out;
+    /// joints attached to them can't be described.
+    pub fn capture(ssm: &SystemStateManager, engine: &Engine, exclude: &[BodyHandle]) -> Result<Self, SceneError> {
+        let bodies: Vec<BodyHandle> = ssm.bodies().handles().into_iter()
+            .filter(|b| !exclude.contains(b))
+            .collect();
+
+        Self::describe(ssm, engine, &bodies, &ssm.joints().iter().collect::<Vec<_>>())
+    }
+
+    /// Describe the bodies & joints in the group & its subgroups, and the engine's settings. Joints of the group
+    /// attached to bodies outside it can't be described; joints outside the group are left out.
+    pub fn capture_group(ssm: &SystemStateManager, engine: &Engine, group: &Group) -> Result<Self, SceneError> {
+        let (members, joint_members) = (group.all_bodies(), group.all_joints());
+        let bodies: Vec<BodyHandle> = ssm.bodies().handles().into_iter().filter(|b| members.contains(b)).collect();
+        let joints: Vec<&TJointRef> = ssm.joints().iter().filter(|&j| joint_members.iter().any(|m| Rc::ptr_eq(m, j))).collect();
+
+        Self::describe(ssm, engine, &bodies, &joints)
+    }
+
+    fn describe(ssm: &SystemStateManager, engine: &Engine, bodies: &[BodyHandle], joints: &[&TJointRef]) -> Result<Self, SceneError> {
+        let body_index = |b: &BodyHandle| bodies.iter().position(|other| other == b);
+        let joint_index = |j: &TJointRef| joints.iter().position(|&other| Rc::ptr_eq(other, j));
+        let (body_paths, joint_paths) = (ssm.root().body_paths(), ssm.root().joint_paths());
+
+        let body_descs = bodies.iter().map(|&handle| {
+            let kinematic = ssm.characters().iter().any(|c| c.borrow().body == handle);
+            let name = body_paths.iter().find(|(_, b)| *b == handle).map(|(path, _)| path.clone());
+            BodyDesc::capture(&ssm.bodies().body(handle), kinematic, name)
+        }).collect();
+
+        let mut joint_descs = Vec::new();
+        for (i, &joint_ref) in joints.iter().enumerate() {
+            let joint = joint_ref.borrow();
+            let field = format!("joints[{i}]");
+
+            let index = |b: &BodyHandle| body_index(b).ok_or_else(|| SceneError::Invalid {
+                position: None,
+                field: format!("{field}.bodies"),
+                message: "attached to a body which isn't part of the scene".to_string(),
+            });
+            let bodies = [index(&joint.bodies[0])?, index(&joint.bodies[1])?];
+            let coupled = match &joint.coupled {
+                Some([j1, j2]) => match (joint_index(j1), joint_index(j2)) {
+                    (Some(a), Some(b)) => Some([a, b]),
+                    _ => return Err(SceneError::Invalid {
+                        position: None,
+                        field: format!("{field}.coupled"),
+                        message: "couples a joint which isn't part of the scene".to_string(),
+                    }),
+                },
+                None => None,
+            };
+
+            let name = joint_paths.iter().find(|(_, j)| Rc::ptr_eq(j, joint_ref)).map(|(path, _)| path.clone());
+            joint_descs.push(JointDesc::capture(&joint, bodies, coupled, name));
+        }
+
+        Ok(Scene {
+            version: SCENE_VERSION,
+            engine: EngineDesc {
+                gravity: Some(engine.gravity()),
+                solver: Some(engine.solver()),
+                integrator: Some(engine.integrator()),
+                world_bounds: engine.world_bounds(),
+            },
+            bodies: body_descs,
+            joints: joint_descs,
+        })
+    }
+
+    /// Check that the values describe a valid world; done by `parse` & `build`
+    pub fn validate(&self) -> Result<(), SceneError> {
+        if let Some(bounds) = self.engine.world_bounds {
+            if bounds.min.x > bounds.max.x || bounds.min.y > bounds.max.y {
+                return Err(invalid("engine.world_bounds", "min is past max"));
+            }
+        }
+
+        for (i, desc) in self.bodies.iter().enumerate() {
+            desc.shape(&format!("bodies[{i}]"))?;
+            if let Some(name) = &desc.name { check_name(name, &format!("bodies[{i}].name"))?; }
+        }
+        for (i, desc) in self.joints.iter().enumerate() {
+            desc.check(&format!("joints[{i}]"), self.bodies.len(), i)?;
+            if let Some(name) = &desc.name { check_name(name, &format!("joints[{i}].name"))?; }
+        }
+
+        Ok(())
+    }
+
+    /// Add the scene's bodies & joints to the system, and apply its settings to the engine.
+    /// Nothing is changed if the scene is invalid.
+    pub fn build(&self, ssm: &mut SystemStateManager, engine: &mut Engine) -> Result<Vec<BodyHandle>, SceneError> {
+        self.validate()?;
+
+        let bodies: Vec<Body> = self.bodies.iter().map(BodyDesc::build).collect();
+
+        // Joints need the bodies in place; build them against a scratch system first
+        let mut scratch = SystemStateManager::new();
+        let handles: Vec<BodyHandle> = bodies.into_iter().zip(&self.bodies)
+            .map(|(body, desc)| match desc.body_type {
+                BodyType::Kinematic => scratch.add_character(body).borrow().body,
+                BodyType::Dynamic | BodyType::Static => scratch.add_body(body),
+            })
+            .collect();
+
+        let mut joint_refs: Vec<TJointRef> = Vec::new();
+        for desc in &self.joints {
+            let joint = desc.build(scratch.bodies(), &handles, &joint_refs);
+            joint_refs.push(scratch.add_joint(joint));
+        }
+
+        for (desc, &handle) in self.bodies.iter().zip(&handles) {
+            if let Some(name) = &desc.name { scratch.name_body(name, handle); }
+        }
+        for (desc, joint_ref) in self.joints.iter().zip(&joint_refs) {
+            if let Some(name) = &desc.name { scratch.name_joint(name, joint_ref); }
+        }
+
+        let handles = ssm.merge(scratch);
+        if let Some(gravity) = self.engine.gravity { engine.set_gravity(gravity); }
+        if let Some(solver) = self.engine.solver { engine.set_solver(solver); }
+        if let Some(integrator) = self.engine.integrator { engine.set_integrator(integrator); }
+        if let Some(bounds) = self.engine.world_bounds { engine.set_world_bounds(Some(bounds)); }
+
+        Ok(handles)
+    }
+}
+
+impl BodyDesc {
+    /// Describes a body which isn't part of any system yet; e.g. to spawn it through an input
+    pub fn of(body: Body) -> Self {
+        let mut arena = BodyArena::new();
+        let handle = arena.insert(body);
+        Self::capture(&arena.body(handle), false, None)
+    }
+
+    fn capture(body: &BodyRef, kinematic: bool, name: Option<String>) -> Self {
+        BodyDesc {
+            name,
+            form: body.form,
+            position: body.position,
+            rotation: body.rotation,
+            size: if body.is_rect() { Some(v2!(body.width.unwrap(), body.height.unwrap())) } else { None },
+            radius: body.radius,
+            sides: Some(body.sides),
+            vertices: Some(body.vertices.iter().map(|v| v.to_vec2()).collect()),
+            origin: Some(body.origin),
+            material: body.material,
+            body_type: if kinematic { BodyType::Kinematic } else if body.frozen { BodyType::Static } else { BodyType::Dynamic },
+
+            // Filters
+            collision_group: body.collision_group,
+            ignore_groups: body.ignore_groups.clone(),
+            enabled: body.enabled,
+            visible: body.visible,
+
+            // State
+            mass: Some(body.mass),
+            inertia: Some(body.inertia),
+            velocity: body.velocity,
+            angular_velocity: body.angular_velocity,
+            lifetime: body.lifetime,
+        }
+    }
+
+    /// Sides, width & height of the body's shape
+    fn shape(&self, field: &str) -> Result<(u32, Option<Real>, Option<Real>), SceneError> {
+        let invalid = |name: &str, message: &str| invalid(&format!("{field}.{name}"), message);
+
+        let (sides, width, height) = match (self.size, &self.vertices, self.sides) {
+            (Some(size), _, _) => (4, Some(size.x), Some(size.y)),
+            (None, Some(vertices), _) => (vertices.len() as u32, None, None),
+            (None, None, Some(sides)) => (sides, None, None),
+            (None, None, None) => return Err(invalid("size", "bodies need a size, a radius & sides, or vertices")),
+        };
+        if sides < 3 {
+            return Err(invalid(if self.vertices.is_some() { "vertices" } else { "sides" }, "bodies need at least 3 sides"));
+        }
+        if self.vertices.as_ref().is_some_and(|vertices| vertices.len() != sides as usize) {
+            return Err(invalid("vertices", "bodies given a size need exactly 4 vertices"));
+        }
+        if self.size.is_none() && self.vertices.is_none() && self.radius.is_none() {
+            return Err(invalid("radius", "regular polygons need a radius"));
+        }
+
+        Ok((sides, width, height))
+    }
+
+    /// Build the body; the description must have been validated
+    fn build(&self) -> Body {
+        let (sides, width, height) = self.shape("").expect("validated by Scene::validate");
+
+        let mut body = Body::new(self.form, v2!(0.0), self.radius, sides, width, height, self.material)
+            .set_collision_group(self.collision_group)
+            .set_ignore_groups(self.ignore_groups.clone())
+            .set_frozen(self.body_type != BodyType::Dynamic)
+            .set_enabled(self.enabled)
+            .set_visible(self.visible);
+
+        if let Some(vertices) = &self.vertices {
+            body.vertices = vertices.iter().enumerate().map(|(i, v)| vtx!(i as u32, v.x, v.y)).collect();
+        }
+        if let Some(origin) = self.origin { body.origin = origin; }
+        if let Some(mass) = self.mass { body.mass = mass; }
+        if let Some(inertia) = self.inertia { body.inertia = inertia; }
+
+        body.position = self.position;
+        body.prev_position = self.position;
+        body.rotation = self.rotation;
+        body.prev_rotation = self.rotation;
+        body.velocity = self.velocity;
+        body.angular_velocity = self.angular_velocity;
+        body.lifetime = self.lifetime;
+        body
+    }
+}
+
+impl JointDesc {
+    fn capture(joint: &Joint, bodies: [usize; 2], coupled: Option<[usize; 2]>, name: Option<String>) -> Self {
+        JointDesc {
+            name,
+            kind: joint.kind,
+            bodies,
+            anchors: joint.anchors,
+            axis: Some(joint.axis),
+            reference_angle: Some(joint.reference_angle),
+            length: Some(joint.length),
+            compliance: joint.compliance,
+            break_force: if joint.break_force.is_finite() { Some(joint.break_force) } else { None },
+            broken: joint.broken,
+            enabled: joint.enabled,
+            visible: joint.visible,
+
+            // Extensions
+            motor: joint.motor,
+            limits: joint.limits,
+            frequency: joint.frequency,
+            damping_ratio: joint.damping_ratio,
+
+            // Coupling
+            ground: Some(joint.ground),
+            ratio: joint.ratio,
+            coupled,
+        }
+    }
+
+    /// Check the joint against the number of bodies in the scene & of joints before it
+    fn check(&self, field: &str, bodies: usize, joints: usize) -> Result<(), SceneError> {
+        let invalid = |name: &str, message: &str| invalid(&format!("{field}.{name}"), message);
+
+        if self.bodies.iter().any(|&i| i >= bodies) {
+            return Err(invalid("bodies", &format!("no such body; the scene has {bodies} bodies")));
+        }
+        match self.kind {
+            JointKind::Pulley if self.ground.is_none() => Err(invalid("ground", "pulleys need ground points")),
+            JointKind::Gear => match self.coupled {
+                None => Err(invalid("coupled", "gears need the joints they couple")),
+                Some(coupled) if coupled.iter().any(|&i| i >= joints) => {
+                    Err(invalid("coupled", &format!("gears can only couple earlier joints; there are {joints} before it")))
+                }
+                Some(_) => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Build the joint; the description must have been validated
+    fn build(&self, arena: &BodyArena, bodies: &[BodyHandle], joints: &[TJointRef]) -> Joint {
+        let [b1, b2] = self.bodies.map(|i| bodies[i]);
+        let p_1 = arena.body(b1).world_point(self.anchors[0]);
+        let p_2 = arena.body(b2).world_point(self.anchors[1]);
+
+        let mut joint = match (self.kind, self.ground, self.coupled) {
+            (JointKind::Pulley, Some([g_1, g_2]), _) => Joint::pulley(arena, b1, b2, p_1, p_2, g_1, g_2, self.ratio),
+            (JointKind::Gear, _, Some([j1, j2])) => Joint::gear(arena, joints[j1].clone(), joints[j2].clone(), self.ratio),
+            (kind, _, _) => Joint::new(kind, arena, b1, b2, p_1, p_2),
+        };
+
+        joint.anchors = self.anchors;
+        if let Some(axis) = self.axis { joint.axis = axis; }
+        if let Some(reference_angle) = self.reference_angle { joint.reference_angle = reference_angle; }
+        if let Some(length) = self.length { joint.length = length; }
+        if let Some(ground) = self.ground { joint.ground = ground; }
+        joint.compliance = self.compliance;
+        joint.break_force = self.break_force.unwrap_or(Real::INFINITY);
+        joint.broken = self.broken;
+        joint.enabled = self.enabled;
+        joint.visible = self.visible;
+
+        // Extensions
+        joint.motor = self.motor;
+        joint.limits = self.limits;
+        joint.frequency = self.frequency;
+        joint.damping_ratio = self.damping_ratio;
+        joint
+    }
+}
+
+/// Names are paths, e.g. `level1/bridge/plank3`; they need at least one non-empty part
+fn check_name(name: &str, field: &str) -> Result<(), SceneError> {
+    if name.split('/').all(|part| part.is_empty()) {
+        return Err(invalid(field, "names can't be empty"));
+    }
+    Ok(())
+}
+
+fn invalid(field: &str, message: &str) -> SceneError {
+    SceneError::Invalid { position: None, field: field.to_string(), message: message.to_string() }
+}
+
+/// Line & column of the value at the field (a path, as serde_path_to_error writes them) in the JSON text; or of the
+/// nearest enclosing field, if it's left out
+fn locate(text: &str, field: &str) -> Option<(usize, usize)> {
+    enum Frame { Object(Option<String>), Array(usize) }
+
+    fn path(stack: &[Frame]) -> String {
+        stack.iter().fold(String::new(), |mut path, frame| {
+            match frame {
+                Frame::Object(Some(key)) if path.is_empty() => path.push_str(key),
+                Frame::Object(Some(key)) => { path.push('.'); path.push_str(key); }
+                Frame::Object(None) => {}
+                Frame::Array(i) => path.push_str(&format!("[{i}]")),
+            }
+            path
+        })
+    }
+    fn read_string(chars: &mut impl Iterator<Item = ((usize, usize), char)>) -> String {
+        let mut string = String::new();
+        while let Some((_, c)) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => string.extend(chars.next().map(|(_, c)| c)),
+                c => string.push(c),
+            }
+        }
+        string
+    }
+
+    // Find the path & position of every value; objects & arrays are entered, strings & other scalars are skipped
+    let mut found: Vec<(String, (usize, usize))> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut expect_key = false;
+    let mut chars = text.lines().enumerate()
+        .flat_map(|(i, line)| line.chars().enumerate().map(move |(j, c)| ((i + 1, j + 1), c)))
+        .peekable();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            ',' => match stack.last_mut() {
+                Some(Frame::Array(i)) => *i += 1,
+                _ => expect_key = true,
+            },
+            '}' | ']' => { stack.pop(); }
+            c if c.is_whitespace() || c == ':' => {}
+            '"' if expect_key => {
+                let key = read_string(&mut chars);
+                if let Some(Frame::Object(k)) = stack.last_mut() { *k = Some(key); }
+                expect_key = false;
+            }
+            _ => {
+                found.push((path(&stack), position));
+                match c {
+                    '{' => { stack.push(Frame::Object(None)); expect_key = true; }
+                    '[' => stack.push(Frame::Array(0)),
+                    '"' => { read_string(&mut chars); }
+                    _ => while chars.next_if(|&(_, c)| c.is_alphanumeric() || ".+-".contains(c)).is_some() {},
+                }
+            }
+        }
+    }
+
+    // Walk up the path until a field in the text is found
+    let mut field = field;
+    loop {
+        if let Some(&(_, position)) = found.iter().find(|(path, _)| path == field) { return Some(position); }
+        field = &field[..field.rfind(['.', '['])?];
+    }
+}
+
+/// Deserialize JSON, reporting the line & field of any error
+fn deserialize<'a, T: Deserialize<'a>>(text: &'a str) -> Result<T, SceneError> {
+    let de = &mut serde_json::Deserializer::from_str(text);
+
+    serde_path_to_error::deserialize(de).map_err(|e| {
+        let field = Some(e.path().to_string()).filter(|path| path != "." && path != "?");
+        let inner = e.into_inner();
+
+        // serde_json appends the position to its messages; it's reported separately
+        let message = inner.to_string();
+        let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m).to_string();
+
+        SceneError::Syntax { line: inner.line(), column: inner.column(), field, message }
+    })
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "couldn't access scene file: {e}"),
+            SceneError::Syntax { line, column, field: Some(field), message } => {
+                write!(f, "line {line}, column {column}: field `{field}`: {message}")
+            }
+            SceneError::Syntax { line, column, field: None, message } => write!(f, "line {line}, column {column}: {message}"),
+            SceneError::Invalid { position: Some((line, column)), field, message } => {
+                write!(f, "line {line}, column {column}: field `{field}`: {message}")
+            }
+            SceneError::Invalid { position: None, field, message } => write!(f, "field `{field}`: {message}"),
+            SceneError::Version(version) => {
+                write!(f, "unsupported scene version {version}; expected version {SCENE_VERSION}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::common::Shared;
+    use crate::{poly, rect};
+
+    fn new_engine() -> Engine {
+        Engine::new(Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        })))
+    }
+
+    #[test]
+    fn captured_scenes_round_trip() {
+        let (mut ssm, mut engine) = (SystemStateManager::new(), new_engine());
+        engine.set_solver(Solver::XPBD);
+        let floor = ssm.add_body(rect!(v2!(0.0, 11.0), 20.0, 1.0).set_frozen(true));
+        let wheel = ssm.add_named_body("level1/wheel", poly!(v2!(5.0, 5.0), 0.5, 7, Materials::RUBBER));
+        let arm = ssm.add_body(rect!(v2!(8.0, 5.0), 2.0, 0.2).set_rotation(0.3));
+        ssm.add_character(rect!(v2!(12.0, 9.0), 0.5, 1.0));
+
+        let bodies = ssm.bodies();
+        let axle = Joint::new(JointKind::Revolute, bodies, floor, wheel, v2!(5.5, 5.5), v2!(5.5, 5.5));
+        let pivot = Joint::new(JointKind::Revolute, bodies, floor, arm, v2!(8.0, 5.1), v2!(8.0, 5.1)).set_break_force(50.0);
+        let axle = ssm.add_named_joint("level1/axle", axle);
+        let pivot = ssm.add_joint(pivot);
+        let gear = Joint::gear(ssm.bodies(), axle, pivot, 2.0);
+        ssm.add_joint(gear);
+        for _ in 0..10 {
+            engine.step(&mut ssm, 1.0 / 60.0);
+        }
+
+        let scene = Scene::capture(&ssm, &engine, &[]).unwrap();
+        let parsed = Scene::parse(&scene.to_json()).unwrap();
+        assert_eq!(parsed, scene);
+
+        let (mut ssm, mut engine) = (SystemStateManager::new(), new_engine());
+        parsed.build(&mut ssm, &mut engine).unwrap();
+        assert_eq!(Scene::capture(&ssm, &engine, &[]).unwrap(), scene);
+    }
+
+    #[test]
+    fn malformed_files_give_the_line_and_field() {
+        let text = r#"{
+            "version": 2,
+            "bodies": [
+                { "position": { "x": 0.0, "y": 0.0 }, "size": { "x": 1.0, "y": 1.0 } },
+                { "position": { "x": 2.0, "y": 0.0 }, "radius": 1.0, "sides": 5,
+                  "material": { "density": "heavy", "e": 0.2, "ks": 0.1, "kd": 0.1 } }
+            ]
+        }"#;
+
+        match Scene::parse(text) {
+            Err(SceneError::Syntax { line: 6, field: Some(field), .. }) => assert_eq!(field, "bodies[1].material.density"),
+            other => panic!("expected a syntax error on line 6, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_values_give_the_line_and_field() {
+        let scene = |bodies: &str, joints: &str| format!(r#"{{
+            "version": 2,
+            "bodies": [
+                {{ "position": {{ "x": 0.0, "y": 0.0 }}, "size": {{ "x": 1.0, "y": 1.0 }} }},
+                {bodies}
+            ],
+            "joints": [
+                {joints}
+            ]
+        }}"#);
+        let joint = r#"{ "kind": "Distance", "bodies": [0, 1], "anchors": [{ "x": 0.0, "y": 0.0 }, { "x": 0.0, "y": 0.0 }] }"#;
+        let error = |text: String| match Scene::parse(&text) {
+            Err(SceneError::Invalid { position: Some((line, _)), field, .. }) => (line, field),
+            other => panic!("expected an invalid value, got {other:?}"),
+        };
+
+        // Missing fields are reported at the body they're missing from
+        let square = r#"{ "position": { "x": 2.0, "y": 0.0 }, "size": { "x": 1.0, "y": 1.0 } }"#;
+        assert_eq!(error(scene(r#"{ "position": { "x": 2.0, "y": 0.0 } }"#, joint)), (5, "bodies[1].size".to_string()));
+        assert_eq!(error(scene(r#"{ "position": { "x": 2.0, "y": 0.0 }, "radius": 1.0, "sides": 2 }"#, joint)), (5, "bodies[1].sides".to_string()));
+        assert_eq!(error(scene(square, &joint.replace("[0, 1]", "[0, 2]"))), (8, "joints[0].bodies".to_string()));
+
+        let gear = r#"{ "kind": "Gear", "bodies": [0, 1], "anchors": [{ "x": 0.0, "y": 0.0 }, { "x": 0.0, "y": 0.0 }], "coupled": [0, 1] }"#;
+        assert_eq!(error(scene(square, gear)), (8, "joints[0].coupled".to_string()));
+    }
+}
@@ -0,0 +1,214 @@
+/*
+    snapshot.rs
+    ----------------------------------------
+    Description:
+    * Provides snapshots of the simulated state, for undo, trying out branches, rollback, or sending over the wire
+    * A snapshot is a plain value holding no references, so it can be serialized (to_bytes & from_bytes) and kept
+      independently of the world it was taken from
+    * Bodies are stored with their arena slots & generations, so their handles stay valid across a restore
+    * Other objects are matched by their index in the system: restoring writes each copy back into the reference at
+      the same index, so anything holding them (vehicles, the caller) stays valid. Objects past the snapshot's count
+      are dropped from the system; missing ones are recreated, under new references
+    * Joints linked by a gear, named joints & the engine's reported broken joints are stored as indices into the
+      system's joints; broken joints since removed from the system are no longer reported after a restore
+    * Contacts & the broad phase grid are rebuilt every substep, so there is no solver state beyond the objects'
+    * The simulation's random number generator is captured too, so that random spawns repeat after a restore
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::BodyArena;
+use crate::app::engine::{Despawn, Engine};
+use crate::app::joints::Joint;
+use crate::app::objects::{CharacterController, ParticleSystem, Rope, SoftBody};
+use crate::app::ssm::{Group, SystemStateManager};
+use crate::common::{set_rng, with_rng, TJointRef, Real};
+
+/* ------------------- STRUCTURES ------------------- */
+/// Internal struct holding a copy of the world's state at some step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    bodies: BodyArena,
+    joints: Vec<(Joint, Option<[usize; 2]>)>, // With the indices of the joints a gear couples
+    soft_bodies: Vec<SoftBody>,
+    ropes: Vec<Rope>,
+    characters: Vec<CharacterController>,
+    particles: ParticleSystem,
+    root: Group,                       // Named bodies & groups
+    joint_names: Vec<(String, usize)>, // Paths of named joints, with their indices
+    rng: ChaCha12Rng,
+
+    // Clock
+    time: Real,
+    steps: u64,
+
+    // Events of the last step
+    broken_joints: Vec<usize>,
+    despawned: Vec<Despawn>,
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Snapshot {
+    pub fn capture(ssm: &SystemStateManager, engine: &Engine) -> Self {
+        let joint_refs = ssm.joints();
+        let index = |j: &TJointRef| joint_refs.iter().position(|r| Rc::ptr_eq(r, j));
+
+        let joints = joint_refs.iter().map(|joint_ref| {
+            let mut joint = joint_ref.borrow().clone();
+            let coupled = joint.coupled.take().and_then(|[j1, j2]| Some([index(&j1)?, index(&j2)?]));
+            (joint, coupled)
+        }).collect();
+
+        Snapshot {
+            bodies: ssm.bodies().clone(),
+            joints,
+            soft_bodies: ssm.soft_bodies().iter().map(|s| s.borrow().clone()).collect(),
+            ropes: ssm.ropes().iter().map(|r| r.borrow().clone()).collect(),
+            characters: ssm.characters().iter().map(|c| c.borrow().clone()).collect(),
+            particles: ssm.particles().borrow().clone(),
+            root: ssm.root().without_joints(),
+            joint_names: ssm.root().joint_paths().iter().filter_map(|(path, j)| Some((path.clone(), index(j)?))).collect(),
+            rng: with_rng(|rng| rng.clone()),
+
+            // Clock
+            time: engine.time(),
+            steps: engine.steps(),
+
+            // Events of the last step
+            broken_joints: engine.broken_joints().iter().filter_map(index).collect(),
+            despawned: engine.despawned().clone(),
+        }
+    }
+
+    /// Bring the system, the engine's clock & events, and the random number generator back to the captured state
+    pub fn restore(&self, ssm: &mut SystemStateManager, engine: &mut Engine) {
+        *ssm.bodies_mut() = self.bodies.clone();
+
+        let joints: Vec<Joint> = self.joints.iter().map(|(joint, _)| joint.clone()).collect();
+        write_back(ssm.joints_mut(), &joints);
+        write_back(ssm.soft_bodies_mut(), &self.soft_bodies);
+        write_back(ssm.ropes_mut(), &self.ropes);
+        write_back(ssm.characters_mut(), &self.characters);
+        ssm.particles().borrow_mut().clone_from(&self.particles);
+
+        // Relink gears & names, which refer to joints by index
+        let joint_refs = ssm.joints().clone();
+        for (joint_ref, (_, coupled)) in joint_refs.iter().zip(&self.joints) {
+            joint_ref.borrow_mut().coupled = coupled.map(|c| c.map(|i| joint_refs[i].clone()));
+        }
+        ssm.set_root(self.root.clone());
+        for (path, i) in &self.joint_names {
+            ssm.name_joint(path, &joint_refs[*i]);
+        }
+
+        set_rng(self.rng.clone());
+        engine.set_clock(self.time, self.steps);
+        engine.set_events(self.broken_joints.iter().map(|&i| joint_refs[i].clone()).collect(), self.despawned.clone());
+    }
+
+    /// Serializes the snapshot (bincode)
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    /// Step count of the engine when the snapshot was taken
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+/// Writes each value into the reference at the same index, creating references for the missing ones & dropping the
+/// extra ones
+fn write_back<T: Clone>(refs: &mut Vec<Rc<RefCell<T>>>, values: &[T]) {
+    refs.truncate(values.len());
+    for (i, value) in values.iter().enumerate() {
+        match refs.get(i) {
+            Some(r) => r.borrow_mut().clone_from(value),
+            None => refs.push(Rc::new(RefCell::new(value.clone()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use sdl2::pixels::Color;
+
+    use super::*;
+    use crate::app::joints::JointKind;
+    use crate::app::objects::Body;
+    use crate::app::replay::state_hash;
+    use crate::common::{BodyForm, Materials, Shared, Vector2};
+    use crate::{rect, v2};
+
+    /// A pendulum geared to a wheel, with a weak joint which breaks on the first step, & a crate falling on a floor
+    fn world() -> (SystemStateManager, Engine) {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        let engine = Engine::new(shared);
+
+        let mut ssm = SystemStateManager::new();
+        let floor = ssm.add_body(rect!(v2!(10.0, 11.6), 20.0, 0.4).set_frozen(true));
+        let arm = ssm.add_body(rect!(v2!(4.0, 3.0), 2.0, 0.2));
+        let wheel = ssm.add_body(rect!(v2!(8.0, 3.0), 0.6, 0.6));
+        let weight = ssm.add_body(rect!(v2!(12.0, 4.0), 0.3, 0.3));
+        ssm.add_body(rect!(v2!(15.0, 8.0), 0.5, 0.5));
+
+        let bodies = ssm.bodies();
+        let pivot = Joint::new(JointKind::Revolute, bodies, floor, arm, v2!(3.0, 3.0), v2!(3.0, 3.0));
+        let axle = Joint::new(JointKind::Revolute, bodies, floor, wheel, v2!(8.0, 3.0), v2!(8.0, 3.0));
+        let weak = Joint::new(JointKind::Distance, bodies, floor, weight, v2!(12.0, 2.0), v2!(12.0, 4.0)).set_break_force(0.0);
+        let pivot = ssm.add_named_joint("pendulum/pivot", pivot);
+        let axle = ssm.add_joint(axle);
+        let gear = Joint::gear(ssm.bodies(), pivot, axle, 2.0);
+        ssm.add_named_joint("pendulum/gear", gear);
+        ssm.add_joint(weak);
+
+        (ssm, engine)
+    }
+
+    /// Steps the world, bursting particles half way, so that the random number generator is drawn from
+    fn run(ssm: &mut SystemStateManager, engine: &mut Engine, steps: u32) {
+        for step in 0..steps {
+            if step == steps / 2 {
+                ssm.particles().borrow_mut().burst(v2!(15.0, 4.0), 8, 2.0, 0.05, 1.0, Color::WHITE);
+            }
+            engine.step(ssm, 1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn restoring_from_bytes_repeats_the_run() {
+        let (mut ssm, mut engine) = world();
+        engine.step(&mut ssm, 1.0 / 60.0);
+        assert_eq!(engine.broken_joints().len(), 1);
+
+        let bytes = Snapshot::capture(&ssm, &engine).to_bytes().unwrap();
+        run(&mut ssm, &mut engine, 30);
+        let hash = state_hash(&ssm, &engine);
+
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        snapshot.restore(&mut ssm, &mut engine);
+        assert_eq!(Snapshot::capture(&ssm, &engine), snapshot);
+        assert!(Rc::ptr_eq(&ssm.joint("pendulum/pivot").unwrap(), &ssm.joints()[0]));
+
+        run(&mut ssm, &mut engine, 30);
+        assert_eq!(state_hash(&ssm, &engine), hash);
+    }
+}
@@ -4,60 +4,550 @@
     Description:
     * Provides access to folder-like structure for the physics engine
     * Separates different object types (Body, Collection, etc.) into separate vectors
-    * TODO: Create hierarchy/directory-like structure to keep track of object groups
+    * Bodies live in a generational arena, and are referred to by BodyHandle
+    * Bodies & joints can also be named, and kept in nested groups, like files in directories; they are then looked up
+      by path, e.g. `level1/bridge/plank3`. Whole groups can be enabled, frozen, hidden, moved or removed at once
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
-use crate::app::objects::Body;
-use crate::common::TBodyRef;
+use serde::{Deserialize, Serialize};
+
+use crate::app::arena::{BodyArena, BodyHandle};
+use crate::app::joints::Joint;
+use crate::app::objects::{Body, CharacterController, ParticleSystem, Rope, RopeBuilder, SoftBody};
+use crate::common::{TCharacterRef, TJointRef, TParticlesRef, TRopeRef, TSoftBodyRef, Vector2, Real};
+use crate::v2;
 
 /* ------------------- STRUCTURES ------------------- */
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct SystemStateManager {
-    bodies: Vec<TBodyRef>,
-    // collections: Vec<Self>
+    bodies: BodyArena,
+    joints: Vec<TJointRef>,
+    soft_bodies: Vec<TSoftBodyRef>,
+    particles: TParticlesRef,
+    ropes: Vec<TRopeRef>,
+    characters: Vec<TCharacterRef>,
+    root: Group, // Named bodies & joints; the rest of the system is unnamed
+}
+
+/// A named group of bodies & joints, and of further groups.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    bodies: Vec<(String, BodyHandle)>,
+    #[serde(skip)]
+    joints: Vec<(String, TJointRef)>, // Not serialized, being shared; see joint_paths
+    groups: Vec<Group>,
 }
 
 /* -------------------- FUNCTIONS ------------------- */
 impl SystemStateManager {
     pub fn new() -> Self {
         SystemStateManager {
-            bodies: Vec::new(),
-            // collections: Vec::new(),
+            bodies: BodyArena::new(),
+            joints: Vec::new(),
+            soft_bodies: Vec::new(),
+            particles: Rc::from(RefCell::from(ParticleSystem::new())),
+            ropes: Vec::new(),
+            characters: Vec::new(),
+            root: Group::new(""),
+        }
+    }
+
+    pub fn add_body(&mut self, body: Body) -> BodyHandle {
+        self.bodies.insert(body)
+    }
+
+    pub fn add_bodies(&mut self, bodies: Vec<Body>) -> Vec<BodyHandle> {
+        bodies.into_iter().map(|body| self.add_body(body)).collect()
+    }
+
+    pub fn add_joint(&mut self, joint: Joint) -> TJointRef {
+        let joint_ref: TJointRef = Rc::from(RefCell::from(joint));
+        self.joints.push(joint_ref.clone());
+        joint_ref
+    }
+
+    pub fn add_soft_body(&mut self, soft_body: SoftBody) -> TSoftBodyRef {
+        let soft_ref: TSoftBodyRef = Rc::from(RefCell::from(soft_body));
+        self.soft_bodies.push(soft_ref.clone());
+        soft_ref
+    }
+
+    pub fn add_rope(&mut self, rope: Rope) -> TRopeRef {
+        let rope_ref: TRopeRef = Rc::from(RefCell::from(rope));
+        self.ropes.push(rope_ref.clone());
+        rope_ref
+    }
+
+    /// Adds the body, and a kinematic character controller driving it
+    pub fn add_character(&mut self, body: Body) -> TCharacterRef {
+        let handle = self.add_body(body);
+        let character_ref: TCharacterRef = Rc::from(RefCell::from(CharacterController::new(&mut self.bodies, handle)));
+        self.characters.push(character_ref.clone());
+        character_ref
+    }
+
+    pub fn add_chain(&mut self, builder: RopeBuilder) -> (Vec<BodyHandle>, Vec<TJointRef>) {
+        builder.build_chain(self)
+    }
+
+    /// Adds the body under the given path, e.g. `level1/bridge/plank3`; missing groups along the path are created
+    pub fn add_named_body(&mut self, path: &str, body: Body) -> BodyHandle {
+        let handle = self.add_body(body);
+        self.name_body(path, handle);
+        handle
+    }
+
+    /// Adds the joint under the given path; missing groups along the path are created
+    pub fn add_named_joint(&mut self, path: &str, joint: Joint) -> TJointRef {
+        let joint_ref = self.add_joint(joint);
+        self.name_joint(path, &joint_ref);
+        joint_ref
+    }
+
+    /// Files a body already in the system under the given path; replaces whatever was named so before
+    pub fn name_body(&mut self, path: &str, handle: BodyHandle) {
+        let (group, name) = split_path(path);
+        let group = self.add_group(group);
+
+        group.bodies.retain(|(n, _)| n != name);
+        group.bodies.push((name.to_string(), handle));
+    }
+
+    /// Files a joint already in the system under the given path; replaces whatever was named so before
+    pub fn name_joint(&mut self, path: &str, joint_ref: &TJointRef) {
+        let (group, name) = split_path(path);
+        let group = self.add_group(group);
+
+        group.joints.retain(|(n, _)| n != name);
+        group.joints.push((name.to_string(), joint_ref.clone()));
+    }
+
+    /// Returns the group at the given path, creating it & any missing groups along the way
+    pub fn add_group(&mut self, path: &str) -> &mut Group {
+        let mut group = &mut self.root;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            let i = match group.groups.iter().position(|g| g.name == name) {
+                Some(i) => i,
+                None => {
+                    group.groups.push(Group::new(name));
+                    group.groups.len() - 1
+                }
+            };
+            group = &mut group.groups[i];
+        }
+        group
+    }
+
+    /// Removes the group at the given path from the system, along with everything in it. Joints & characters attached
+    /// to its bodies are removed too, wherever they are.
+    pub fn remove_group(&mut self, path: &str) -> Option<Group> {
+        let (parent, name) = split_path(path);
+        let parent = self.root.group_mut(parent)?;
+        let i = parent.groups.iter().position(|g| g.name == name)?;
+        let group = parent.groups.remove(i);
+
+        self.remove(&group.all_bodies(), &group.all_joints());
+        Some(group)
+    }
+
+    /// Removes the body from the system, along with the joints & characters attached to it; ropes tied to it come
+    /// loose. None if the handle is stale
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Body> {
+        if !self.bodies.contains(handle) { return None; }
+        self.remove(&[handle], &[]).pop()
+    }
+
+    /// Removes the joint from the system, along with any gears coupling it
+    pub fn remove_joint(&mut self, joint_ref: &TJointRef) {
+        self.remove(&[], std::slice::from_ref(joint_ref));
+    }
+
+    /// Removes the bodies & joints from the system, with everything attached to the bodies; returns the removed bodies
+    fn remove(&mut self, bodies: &[BodyHandle], joints: &[TJointRef]) -> Vec<Body> {
+        let removed_body = |b: &BodyHandle| bodies.contains(b);
+        let mut removed: Vec<TJointRef> = joints.to_vec();
+
+        // Joints attached to removed bodies go too, as do gears coupling removed joints
+        for joint_ref in &self.joints {
+            let joint = joint_ref.borrow();
+            let coupled = joint.coupled.iter().flatten().any(|c| removed.iter().any(|r| Rc::ptr_eq(r, c)));
+            if joint.bodies.iter().any(removed_body) || coupled {
+                removed.push(joint_ref.clone());
+            }
         }
+        let removed_joint = |j: &TJointRef| removed.iter().any(|r| Rc::ptr_eq(r, j));
+
+        let removed_bodies: Vec<Body> = bodies.iter().filter_map(|&handle| self.bodies.remove(handle)).collect();
+        self.joints.retain(|j| !removed_joint(j));
+        self.characters.retain(|c| !removed_body(&c.borrow().body));
+        self.root.forget(&removed_body, &removed_joint);
+
+        // Ropes tied to removed bodies come loose
+        for rope_ref in &self.ropes {
+            for anchor in rope_ref.borrow_mut().anchors.iter_mut() {
+                if anchor.as_ref().is_some_and(|a| removed_body(&a.body)) {
+                    *anchor = None;
+                }
+            }
+        }
+
+        removed_bodies
     }
 
-    pub fn add_body(&mut self, body: Body) {
-        let body_ref: TBodyRef = Rc::from(RefCell::from(body));
+    /// Moves the bodies, joints, soft bodies, ropes & characters of the other system into this one, along with
+    /// their names. The bodies get new handles, returned in the other system's order; whatever refers to them
+    /// is updated
+    pub fn merge(&mut self, mut other: SystemStateManager) -> Vec<BodyHandle> {
+        let mut handles: HashMap<BodyHandle, BodyHandle> = HashMap::new();
+        let mut merged: Vec<BodyHandle> = Vec::new();
+        for handle in other.bodies.handles() {
+            let body = other.bodies.remove(handle).expect("handle was just listed");
+            merged.push(self.bodies.insert(body));
+            handles.insert(handle, merged[merged.len() - 1]);
+        }
+
+        for joint_ref in &other.joints {
+            let mut joint = joint_ref.borrow_mut();
+            joint.bodies = joint.bodies.map(|b| handles[&b]);
+        }
+        for rope_ref in &other.ropes {
+            for anchor in rope_ref.borrow_mut().anchors.iter_mut().flatten() {
+                anchor.body = handles[&anchor.body];
+            }
+        }
+        for character_ref in &other.characters {
+            let mut character = character_ref.borrow_mut();
+            character.body = handles[&character.body];
+            character.ground = character.ground.and_then(|g| handles.get(&g).copied());
+        }
+
+        self.joints.extend(other.joints);
+        self.soft_bodies.extend(other.soft_bodies);
+        self.ropes.extend(other.ropes);
+        self.characters.extend(other.characters);
 
-        if self.bodies.contains(&body_ref) {
-            return;
+        for (path, handle) in other.root.body_paths() {
+            self.name_body(&path, handles[&handle]);
+        }
+        for (path, joint_ref) in other.root.joint_paths() {
+            self.name_joint(&path, &joint_ref);
         }
 
-        self.bodies.push(body_ref);
+        merged
     }
 
-    pub fn add_bodies(&mut self, bodies: Vec<Body>) {
-        for body in bodies {
-            let body_ref: TBodyRef = Rc::from(RefCell::from(body));
+    /// Moves every body in the group at the given path; false if there is no such group
+    pub fn translate_group(&mut self, path: &str, d: Vector2<Real>) -> bool {
+        let Some(group) = self.root.group(path) else { return false; };
+        for handle in group.all_bodies() {
+            let mut body = self.bodies.body_mut(handle);
+            body.position = body.position + d;
+            body.prev_position = body.prev_position + d;
+        }
+        true
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn bodies(&self) -> &BodyArena {
+        &self.bodies
+    }
+    pub fn bodies_mut(&mut self) -> &mut BodyArena {
+        &mut self.bodies
+    }
+    pub fn joints(&self) -> &Vec<TJointRef> {
+        &self.joints
+    }
+    pub fn joints_mut(&mut self) -> &mut Vec<TJointRef> {
+        &mut self.joints
+    }
+    pub fn soft_bodies(&self) -> &Vec<TSoftBodyRef> {
+        &self.soft_bodies
+    }
+    pub fn soft_bodies_mut(&mut self) -> &mut Vec<TSoftBodyRef> {
+        &mut self.soft_bodies
+    }
+    pub fn particles(&self) -> &TParticlesRef {
+        &self.particles
+    }
+    pub fn ropes(&self) -> &Vec<TRopeRef> {
+        &self.ropes
+    }
+    pub fn ropes_mut(&mut self) -> &mut Vec<TRopeRef> {
+        &mut self.ropes
+    }
+    pub fn characters(&self) -> &Vec<TCharacterRef> {
+        &self.characters
+    }
+    pub fn characters_mut(&mut self) -> &mut Vec<TCharacterRef> {
+        &mut self.characters
+    }
+    /// The top of the group hierarchy
+    pub fn root(&self) -> &Group {
+        &self.root
+    }
+    pub fn group(&self, path: &str) -> Option<&Group> {
+        self.root.group(path)
+    }
+    /// The body at the given path, e.g. `level1/bridge/plank3`
+    pub fn body(&self, path: &str) -> Option<BodyHandle> {
+        self.root.body(path)
+    }
+    pub fn joint(&self, path: &str) -> Option<TJointRef> {
+        self.root.joint(path)
+    }
 
-            if self.bodies.contains(&body_ref) {
-                continue;
+    /* --------------------- SETTERS -------------------- */
+    /// Replaces the group hierarchy; joints aren't carried by serialized groups, so they're filed with name_joint
+    pub fn set_root(&mut self, root: Group) {
+        self.root = root;
+    }
+    /// Enables or disables every body & joint in the group at the given path; disabled ones don't take part in the
+    /// simulation. False if there is no such group
+    pub fn set_group_enabled(&mut self, path: &str, enabled: bool) -> bool {
+        let Some(group) = self.root.group(path) else { return false; };
+        for handle in group.all_bodies() {
+            self.bodies.body_mut(handle).enabled = enabled;
+        }
+        for joint_ref in group.all_joints() {
+            joint_ref.borrow_mut().enabled = enabled;
+        }
+        true
+    }
+    pub fn set_group_visible(&mut self, path: &str, visible: bool) -> bool {
+        let Some(group) = self.root.group(path) else { return false; };
+        for handle in group.all_bodies() {
+            self.bodies.body_mut(handle).visible = visible;
+        }
+        for joint_ref in group.all_joints() {
+            joint_ref.borrow_mut().visible = visible;
+        }
+        true
+    }
+    /// Freezes every body in the group at the given path in place, or lets them move again
+    pub fn set_group_frozen(&mut self, path: &str, frozen: bool) -> bool {
+        let Some(group) = self.root.group(path) else { return false; };
+        for handle in group.all_bodies() {
+            let mut body = self.bodies.body_mut(handle);
+            body.frozen = frozen;
+            if frozen {
+                body.velocity = v2!(0.0);
+                body.angular_velocity = 0.0;
             }
+        }
+        true
+    }
+}
+
+impl Group {
+    pub fn new(name: &str) -> Self {
+        Group { name: name.to_string(), ..Default::default() }
+    }
 
-            self.bodies.push(body_ref);
+    /// Every body in the group & its subgroups
+    pub fn all_bodies(&self) -> Vec<BodyHandle> {
+        let mut bodies: Vec<BodyHandle> = self.bodies.iter().map(|&(_, b)| b).collect();
+        for group in &self.groups {
+            bodies.extend(group.all_bodies());
         }
+        bodies
     }
 
-    // pub fn contains_body(&self, body: Body) {
-    //     let body_ref =
-    // }
+    /// Every joint in the group & its subgroups
+    pub fn all_joints(&self) -> Vec<TJointRef> {
+        let mut joints: Vec<TJointRef> = self.joints.iter().map(|(_, j)| j.clone()).collect();
+        for group in &self.groups {
+            joints.extend(group.all_joints());
+        }
+        joints
+    }
+
+    /// Every body in the group & its subgroups, with its path relative to the group
+    pub fn body_paths(&self) -> Vec<(String, BodyHandle)> {
+        let mut paths: Vec<(String, BodyHandle)> = self.bodies.clone();
+        for group in &self.groups {
+            paths.extend(group.body_paths().into_iter().map(|(path, b)| (format!("{}/{path}", group.name), b)));
+        }
+        paths
+    }
+
+    /// Every joint in the group & its subgroups, with its path relative to the group
+    pub fn joint_paths(&self) -> Vec<(String, TJointRef)> {
+        let mut paths: Vec<(String, TJointRef)> = self.joints.clone();
+        for group in &self.groups {
+            paths.extend(group.joint_paths().into_iter().map(|(path, j)| (format!("{}/{path}", group.name), j)));
+        }
+        paths
+    }
+
+    /// A copy of the group without its joints, nor those of its subgroups; serialized groups can't carry the shared
+    /// joints, so they're filed again from their paths
+    pub fn without_joints(&self) -> Group {
+        Group {
+            name: self.name.clone(),
+            bodies: self.bodies.clone(),
+            joints: Vec::new(),
+            groups: self.groups.iter().map(Group::without_joints).collect(),
+        }
+    }
+
+    /// Drop the names of removed bodies & joints
+    fn forget(&mut self, removed_body: &dyn Fn(&BodyHandle) -> bool, removed_joint: &dyn Fn(&TJointRef) -> bool) {
+        self.bodies.retain(|(_, b)| !removed_body(b));
+        self.joints.retain(|(_, j)| !removed_joint(j));
+        for group in self.groups.iter_mut() {
+            group.forget(removed_body, removed_joint);
+        }
+    }
 
     /* --------------------- GETTERS -------------------- */
-    pub fn bodies(&self) -> &Vec<TBodyRef> {
-        return &self.bodies;
+    pub fn bodies(&self) -> &Vec<(String, BodyHandle)> {
+        &self.bodies
+    }
+    pub fn joints(&self) -> &Vec<(String, TJointRef)> {
+        &self.joints
+    }
+    pub fn groups(&self) -> &Vec<Group> {
+        &self.groups
     }
-}
\ No newline at end of file
+    /// The subgroup at the given path, relative to this group
+    pub fn group(&self, path: &str) -> Option<&Group> {
+        let mut group = self;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            group = group.groups.iter().find(|g| g.name == name)?;
+        }
+        Some(group)
+    }
+    fn group_mut(&mut self, path: &str) -> Option<&mut Group> {
+        let mut group = self;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            group = group.groups.iter_mut().find(|g| g.name == name)?;
+        }
+        Some(group)
+    }
+    /// The body at the given path, relative to this group
+    pub fn body(&self, path: &str) -> Option<BodyHandle> {
+        let (group, name) = split_path(path);
+        self.group(group)?.bodies.iter().find(|(n, _)| n == name).map(|&(_, b)| b)
+    }
+    /// The joint at the given path, relative to this group
+    pub fn joint(&self, path: &str) -> Option<TJointRef> {
+        let (group, name) = split_path(path);
+        self.group(group)?.joints.iter().find(|(n, _)| n == name).map(|(_, j)| j.clone())
+    }
+
+}
+
+/// Splits a path into that of its group, and the name within it; e.g. `level1/bridge/plank3` into
+/// `level1/bridge` & `plank3`
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::joints::JointKind;
+    use crate::app::objects::BodyView;
+    use crate::common::{BodyForm, Materials};
+    use crate::rect;
+
+    /// A level holding a bridge of three planks, hinged together, with a troll on it & the last plank tied to an
+    /// unnamed post; & a door beside the bridge
+    fn level() -> (SystemStateManager, [BodyHandle; 5], [TJointRef; 2]) {
+        let mut ssm = SystemStateManager::new();
+        let planks: Vec<BodyHandle> = (1..=3)
+            .map(|i| ssm.add_named_body(&format!("level1/bridge/plank{i}"), rect!(v2!(i as Real, 5.0), 1.0, 0.2)))
+            .collect();
+        let door = ssm.add_named_body("level1/door", rect!(v2!(8.0, 4.0), 0.2, 1.0));
+        let post = ssm.add_body(rect!(v2!(4.5, 5.0), 0.2, 1.0).set_frozen(true));
+        let troll = ssm.add_character(rect!(v2!(2.0, 4.0), 0.5, 1.0)).borrow().body;
+        ssm.name_body("level1/bridge/troll", troll);
+
+        let hinge = Joint::new(JointKind::Revolute, ssm.bodies(), planks[0], planks[1], v2!(2.0, 5.1), v2!(2.0, 5.1));
+        let hinge = ssm.add_named_joint("level1/bridge/hinge", hinge);
+        let tie = Joint::new(JointKind::Distance, ssm.bodies(), post, planks[2], v2!(4.6, 5.0), v2!(4.0, 5.1));
+        let tie = ssm.add_joint(tie);
+
+        (ssm, [planks[0], planks[1], planks[2], door, post], [hinge, tie])
+    }
+
+    #[test]
+    fn paths_lead_to_nested_bodies_and_joints() {
+        let (mut ssm, [_, _, plank3, door, post], [hinge, _]) = level();
+        assert_eq!(ssm.body("level1/bridge/plank3"), Some(plank3));
+        assert_eq!(ssm.group("level1").unwrap().body("bridge/plank3"), Some(plank3));
+        assert_eq!(ssm.body("level1/door"), Some(door));
+        assert!(Rc::ptr_eq(&ssm.joint("level1/bridge/hinge").unwrap(), &hinge));
+        assert_eq!(ssm.group("level1/bridge").unwrap().bodies().len(), 4);
+
+        for missing in ["level1/plank3", "level2/bridge/plank3", "level1/bridge/plank4", "level1/bridge"] {
+            assert_eq!(ssm.body(missing), None, "{missing}");
+        }
+        assert!(ssm.joint("level1/hinge").is_none());
+
+        // Naming another body so replaces the first
+        ssm.name_body("level1/bridge/plank3", post);
+        assert_eq!(ssm.body("level1/bridge/plank3"), Some(post));
+        assert_eq!(ssm.group("level1/bridge").unwrap().bodies().len(), 4);
+    }
+
+    #[test]
+    fn removing_a_group_removes_what_hangs_off_it() {
+        let (mut ssm, [plank1, plank2, plank3, door, post], _) = level();
+
+        let bridge = ssm.remove_group("level1/bridge").unwrap();
+        assert_eq!(bridge.all_bodies().len(), 4);
+        for handle in bridge.all_bodies() {
+            assert!(!ssm.bodies().contains(handle));
+        }
+        assert!(![plank1, plank2, plank3].iter().any(|&plank| ssm.bodies().contains(plank)));
+
+        // The hinge was in the group, the tie & troll attached to its bodies; the rest of the level stays
+        assert!(ssm.joints().is_empty() && ssm.characters().is_empty());
+        assert!(ssm.group("level1/bridge").is_none() && ssm.body("level1/bridge/plank1").is_none());
+        assert_eq!(ssm.body("level1/door"), Some(door));
+        assert!(ssm.bodies().contains(post) && ssm.bodies().len() == 2);
+        assert!(ssm.remove_group("level1/bridge").is_none());
+    }
+
+    #[test]
+    fn group_changes_reach_every_nested_body() {
+        let (mut ssm, [plank1, plank2, plank3, door, post], [hinge, tie]) = level();
+        let level: Vec<BodyHandle> = ssm.group("level1").unwrap().all_bodies();
+        let centers = |ssm: &SystemStateManager| -> Vec<Vector2<Real>> {
+            ssm.bodies().handles().into_iter().map(|h| ssm.bodies().body(h).center()).collect()
+        };
+
+        let before = centers(&ssm);
+        assert!(ssm.translate_group("level1", v2!(1.0, -2.0)));
+        for ((handle, before), after) in ssm.bodies().handles().into_iter().zip(before).zip(centers(&ssm)) {
+            let d = if level.contains(&handle) { v2!(1.0, -2.0) } else { v2!(0.0, 0.0) };
+            assert!((after - before - d).mag() < 1e-4, "{handle:?} moved by {:?}", after - before);
+        }
+
+        ssm.bodies_mut().body_mut(plank2).velocity = v2!(0.0, 3.0);
+        assert!(ssm.set_group_frozen("level1/bridge", true));
+        assert!(ssm.bodies().body(plank2).frozen && ssm.bodies().body(plank2).velocity == v2!(0.0, 0.0));
+        assert!(!ssm.bodies().body(door).frozen);
+
+        assert!(ssm.set_group_enabled("level1", false));
+        assert!([plank1, plank2, plank3, door].iter().all(|&b| !ssm.bodies().body(b).enabled));
+        assert!(ssm.bodies().body(post).enabled);
+        assert!(!hinge.borrow().enabled && tie.borrow().enabled);
+        assert!(!hinge.borrow().is_active(ssm.bodies()) && !tie.borrow().is_active(ssm.bodies()));
+
+        assert!(ssm.set_group_visible("level1/bridge", false));
+        assert!(!ssm.bodies().body(plank3).visible && ssm.bodies().body(door).visible && !hinge.borrow().visible);
+
+        assert!(!ssm.translate_group("level2", v2!(1.0, 0.0)) && !ssm.set_group_frozen("level2", true));
+    }
+}
@@ -4,6 +4,8 @@
     Description:
     * Handles the render step of the simulation
     * Defines how each object should be drawn based on internal parameters/overall configuration
+    * The world is in metres; it is taken to pixels here, through the Camera, & nowhere else
+    * Objects entirely out of view are culled before drawing
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
@@ -18,11 +20,17 @@ use sdl2::rect::{Point, Rect};
 use sdl2::render::WindowCanvas;
 use sdl2::video::Window;
 
-use crate::common::{ConvertPrimitives, Disp, GRID_SIZE, Shared, TBodyRef, TSharedRef, Vector2, Colors};
+use crate::app::arena::BodyArena;
+use crate::app::camera::Camera;
+use crate::app::joints::JointKind;
+use crate::app::objects::{BodyRef, BodyView};
+use crate::common::{ConvertPrimitives, Crd, Disp, GRID_SIZE, AABB, Shared, TJointRef, TParticlesRef, TRopeRef, TSharedRef, TSoftBodyRef, Vector2, Colors, Real};
 use crate::v2;
 
 /* -------------------- VARIABLES ------------------- */
+pub const DEFAULT_PIXELS_PER_METRE: Real = 50.0;
 const POINT_SIZE: u32 = 4;
+const ROPE_SUBDIVISIONS: usize = 4; // Spline points drawn per rope segment
 
 /* ------------------- STRUCTURES ------------------- */
 pub struct Video {
@@ -31,6 +39,8 @@ pub struct Video {
     pub subsys: VideoSubsystem,
     pub canvas: WindowCanvas,
     pub colors: Colors,
+    pub camera: Camera,
+    view: AABB, // Part of the world in view this frame
 
     aabb: bool,
     grid: bool,
@@ -55,8 +65,9 @@ impl<'a> Video {
 
         // Define shared variables
         let window_size = canvas.window().size();
+        let camera = Camera::new(v2!(window_size.0, window_size.1), DEFAULT_PIXELS_PER_METRE);
         let shared = Rc::from(RefCell::from(Shared {
-            window_size: v2!(window_size.0, window_size.1),
+            world_size: v2!(window_size.0 as Real, window_size.1 as Real) / DEFAULT_PIXELS_PER_METRE,
             collision_grid: Vec::new(),
             broad_phase_pairs: Vec::new(),
             narrow_phase_pairs: Vec::new(),
@@ -68,6 +79,8 @@ impl<'a> Video {
             subsys,
             canvas,
             colors: Colors,
+            view: camera.view_bounds(),
+            camera,
             // window,
 
             aabb: false,
@@ -78,6 +91,15 @@ impl<'a> Video {
         }
     }
 
+    /// Pixel the point in the world is drawn at
+    pub fn to_screen(&self, p: Vector2<Crd>) -> Vector2<Disp> {
+        self.camera.to_screen(p)
+    }
+    /// Point in the world drawn at the pixel
+    pub fn to_world(&self, p: Vector2<Disp>) -> Vector2<Crd> {
+        self.camera.to_world(p)
+    }
+
     pub fn point(&mut self, c: Vector2<Disp>, color: Color) {
         let cached_color = self.canvas.draw_color();
         self.canvas.set_draw_color(color);
@@ -93,23 +115,23 @@ impl<'a> Video {
         self.canvas.set_draw_color(cached_color);
     }
 
-    pub fn draw_body(&mut self, body_ref: &TBodyRef) {
-        let body = body_ref.borrow_mut();
-        let vertices = &body.vertices;
+    pub fn draw_body(&mut self, body: &BodyRef) {
+        let geometry = body.geometry();
+        let vertices = &geometry.vertices;
+        if !geometry.aabb.overlaps(&self.view) { return; }
 
         // Draw AABB
         if self.aabb {
-            let points: Vec<Vector2<Disp>> = body.aabb().points.iter().map(|p| p.disp()).collect();
-            let x: Vec<i16> = points.clone().iter().map(|p| p.x as i16).collect();
-            let y: Vec<i16> = points.clone().iter().map(|p| p.y as i16).collect();
+            let points: Vec<Vector2<Disp>> = geometry.aabb.corners().iter().map(|&p| self.to_screen(p)).collect();
+            let (x, y) = coords(&points);
 
             let draw_color = self.canvas.draw_color();
             self.canvas.aa_polygon(x.as_slice(), y.as_slice(), Colors::AC3).unwrap();
             self.canvas.set_draw_color(draw_color);
         }
 
-        let mut x: Vec<i16> = vertices.clone().iter().map(|vtx| body.globalise(vtx.to_vec2()).x as i16).collect();
-        let mut y: Vec<i16> = vertices.clone().iter().map(|vtx| body.globalise(vtx.to_vec2()).y as i16).collect();
+        let screen: Vec<Vector2<Disp>> = vertices.iter().map(|&v| self.to_screen(v)).collect();
+        let (x, y) = coords(&screen);
 
         let draw_color = self.canvas.draw_color();
         self.canvas.filled_polygon(x.as_slice(), y.as_slice(), Colors::AC1).unwrap();
@@ -119,20 +141,109 @@ impl<'a> Video {
         // Draw points
         // Dependent on: self.points == true
         if self.points {
-            for i in 0..vertices.len() {
-                self.point(body.globalise(vertices[i].to_vec2()).disp(), Colors::AC2);
+            for p in screen {
+                self.point(p, Colors::AC2);
             }
             // Origin
-            self.point(body.globalise(v2!(0.0)).disp(), Colors::AC3);
+            self.point(self.to_screen(body.world_point(v2!(0.0))), Colors::AC3);
+        }
+    }
+
+    pub fn draw_soft_body(&mut self, soft_ref: &TSoftBodyRef) {
+        let soft = soft_ref.borrow();
+        let hull = soft.hull_points();
+        if !AABB::from_points(&hull).overlaps(&self.view) { return; }
+
+        let hull: Vec<Vector2<Disp>> = hull.into_iter().map(|p| self.to_screen(p)).collect();
+        let (x, y) = coords(&hull);
+
+        let draw_color = self.canvas.draw_color();
+        self.canvas.filled_polygon(x.as_slice(), y.as_slice(), Colors::AC3).unwrap();
+        self.canvas.aa_polygon(x.as_slice(), y.as_slice(), Colors::AC0).unwrap();
+        self.canvas.set_draw_color(draw_color);
+
+        // Draw points
+        // Dependent on: self.points == true
+        if self.points {
+            for point in &soft.points {
+                self.point(self.to_screen(point.position), Colors::AC2);
+            }
         }
     }
 
-    pub fn pre_draw(&mut self) {
+    /// Draws every particle as a square; particles are batched by colour into a single fill call each
+    pub fn draw_particles(&mut self, particles_ref: &TParticlesRef) {
+        let system = particles_ref.borrow();
+        let mut batches: Vec<(Color, Vec<Rect>)> = Vec::new();
+
+        for particle in &system.particles {
+            if !self.view.fattened(particle.radius).contains_point(particle.position) { continue; }
+
+            let size = (particle.radius * 2.0 * self.camera.scale()).max(1.0) as u32;
+            let c = self.to_screen(particle.position);
+            let rect = Rect::new(c.x - (size / 2) as Disp, c.y - (size / 2) as Disp, size, size);
+
+            match batches.iter_mut().find(|(color, _)| *color == particle.color) {
+                Some((_, rects)) => rects.push(rect),
+                None => batches.push((particle.color, vec![rect])),
+            }
+        }
+
+        let cached_color = self.canvas.draw_color();
+        for (color, rects) in batches {
+            self.canvas.set_draw_color(color);
+            self.canvas.fill_rects(rects.as_slice()).unwrap();
+        }
+        self.canvas.set_draw_color(cached_color);
+    }
+
+    pub fn draw_rope(&mut self, rope_ref: &TRopeRef) {
+        let rope = rope_ref.borrow();
+        if !AABB::from_points(&rope.points).fattened(rope.thickness).overlaps(&self.view) { return; }
+
+        let width = (rope.thickness * self.camera.scale()).clamp(1.0, u8::MAX as Real) as u8;
+
+        for line in rope.polylines(ROPE_SUBDIVISIONS) {
+            for i in 0..line.len() - 1 {
+                let (x, y) = coords(&[self.to_screen(line[i]), self.to_screen(line[i + 1])]);
+                self.canvas.thick_line(x[0], y[0], x[1], y[1], width, Colors::AC0).unwrap();
+            }
+        }
+    }
+
+    pub fn draw_joint(&mut self, joint_ref: &TJointRef, bodies: &BodyArena) {
+        let joint = joint_ref.borrow();
+        if joint.broken || joint.kind == JointKind::Gear { return; }
+        let [a_1, a_2] = joint.world_anchors(bodies).map(|a| self.to_screen(a));
+
+        // Pulley ropes run from each anchor over its ground point; mouse joints reach from the anchor to the target
+        match joint.kind {
+            JointKind::Pulley => {
+                let [g_1, g_2] = joint.ground.map(|g| self.to_screen(g));
+                self.line(a_1, g_1, Colors::AC0);
+                self.line(g_1, g_2, Colors::AC0);
+                self.line(g_2, a_2, Colors::AC0);
+            }
+            JointKind::Mouse => {
+                let target = self.to_screen(joint.ground[0]);
+                self.line(a_1, target, Colors::AC2);
+                self.point(target, Colors::AC2);
+            }
+            _ => self.line(a_1, a_2, Colors::AC0),
+        }
+        if self.points {
+            self.point(a_1, Colors::AC3);
+            self.point(a_2, Colors::AC3);
+        }
+    }
+
+    pub fn pre_draw(&mut self, bodies: &BodyArena) {
         self.canvas.clear();
         // TODO: Add bg color
+        self.view = self.camera.view_bounds();
 
-        let window_size: Vector2<Disp> = self.shared.borrow_mut().window_size.to();
-        let scalar: Vector2<Disp> = (window_size / GRID_SIZE.clone().to()).to();
+        let world_size = self.shared.borrow().world_size;
+        let scalar: Vector2<Crd> = world_size / GRID_SIZE.clone().to();
 
         // Draw collision grid
         if self.grid {
@@ -150,19 +261,20 @@ impl<'a> Video {
                         let cell = &collision_grid[i][j];
 
                         if cell.len() > 0 {
-                            let i = i as Disp;
-                            let j = j as Disp;
+                            let i = i as Real;
+                            let j = j as Real;
 
-                            let rect = Rect::new(j*scalar.x, i*scalar.y, scalar.to().x, scalar.to().y);
+                            let min = v2!(j * scalar.x, i * scalar.y);
+                            let corners = AABB::new(min, min + scalar).corners().map(|p| self.to_screen(p));
+                            let (x, y) = coords(&corners);
                             let cached_color = self.canvas.draw_color();
 
-                            let scaled = cell.len() as f64 / scale_max as f64;
+                            let scaled = cell.len() as Real / scale_max as Real;
                             let r = 0;
-                            let g = (150f64 * scaled) as u8;
-                            let b = (50f64 * scaled) as u8;
+                            let g = (150.0 * scaled) as u8;
+                            let b = (50.0 * scaled) as u8;
 
-                            self.canvas.set_draw_color(Color::RGB(r, g, b));
-                            self.canvas.fill_rect(rect).unwrap();
+                            self.canvas.filled_polygon(x.as_slice(), y.as_slice(), Color::RGB(r, g, b)).unwrap();
                             self.canvas.set_draw_color(cached_color);
                         }
                     }
@@ -172,13 +284,13 @@ impl<'a> Video {
             // Draw grid
             let color = Colors::AC0;
             for i in 0..=GRID_SIZE.x {
-                let i = i as Disp;
-                self.line(v2!(i * scalar.x, 0), v2!(i * scalar.x, window_size.y), color);
+                let x = i as Real * scalar.x;
+                self.line(self.to_screen(v2!(x, 0.0)), self.to_screen(v2!(x, world_size.y)), color);
             }
 
             for j in 0..=GRID_SIZE.y {
-                let j = j as Disp;
-                self.line(v2!(0, j * scalar.y), v2!(window_size.x, j * scalar.y), color);
+                let y = j as Real * scalar.y;
+                self.line(self.to_screen(v2!(0.0, y)), self.to_screen(v2!(world_size.x, y)), color);
             }
         }
 
@@ -190,26 +302,24 @@ impl<'a> Video {
             // println!("{:?}", narrow_phase_pairs);
 
             for pair in broad_phase_pairs {
-                let b1 = pair[0].borrow_mut();
-                let b2 = pair[1].borrow_mut();
+                let (Some(b1), Some(b2)) = (bodies.get(pair[0]), bodies.get(pair[1])) else { continue; };
 
-                self.line(b1.globalise(v2!(0.0)).disp(), b2.globalise(v2!(0.0)).disp(), Color::RGB(255, 165, 0));
+                self.line(self.to_screen(b1.world_point(v2!(0.0))), self.to_screen(b2.world_point(v2!(0.0))), Color::RGB(255, 165, 0));
             }
 
             for pair in narrow_phase_pairs {
-                let b1 = pair.bodies[0].borrow_mut();
-                let b2 = pair.bodies[1].borrow_mut();
+                let (Some(b1), Some(b2)) = (bodies.get(pair.bodies[0]), bodies.get(pair.bodies[1])) else { continue; };
 
-                self.line(b1.globalise(v2!(0.0)).disp(), b2.globalise(v2!(0.0)).disp(), Colors::AC2);
+                self.line(self.to_screen(b1.world_point(v2!(0.0))), self.to_screen(b2.world_point(v2!(0.0))), Colors::AC2);
 
                 for i in 0..pair.contacts.len() {
-                    self.point(pair.contacts[i].clone().to(), Color::CYAN);
+                    self.point(self.to_screen(pair.contacts[i]), Color::CYAN);
                 }
 
                 // println!("o={}", pair.overlap);
 
-                self.point(b1.center().to(), Color::YELLOW);
-                self.point(b2.center().to(), Color::YELLOW);
+                self.point(self.to_screen(b1.center()), Color::YELLOW);
+                self.point(self.to_screen(b2.center()), Color::YELLOW);
             }
 
         }
@@ -219,4 +329,25 @@ impl<'a> Video {
     pub fn window(&self) -> &Window {
         self.canvas.window()
     }
-}
\ No newline at end of file
+    pub fn pixels_per_metre(&self) -> Real {
+        self.camera.pixels_per_metre()
+    }
+    /// Metres of world the window shows, unzoomed
+    pub fn world_size(&self) -> Vector2<Crd> {
+        let (width, height) = self.window().size();
+        v2!(width as Real, height as Real) / self.camera.pixels_per_metre()
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    /// Sets the scale the world is drawn at; the world in view, & so the collision grid, change with it
+    pub fn set_pixels_per_metre(&mut self, pixels_per_metre: Real) {
+        self.camera.set_pixels_per_metre(pixels_per_metre);
+        self.shared.borrow_mut().world_size = self.world_size();
+    }
+}
+
+/// Pixel coordinates split into the i16s SDL2_gfx draws with; clamped, so that far off-screen points don't wrap around
+fn coords(points: &[Vector2<Disp>]) -> (Vec<i16>, Vec<i16>) {
+    let clamp = |n: Disp| n.clamp(i16::MIN as Disp, i16::MAX as Disp) as i16;
+    (points.iter().map(|p| clamp(p.x)).collect(), points.iter().map(|p| clamp(p.y)).collect())
+}
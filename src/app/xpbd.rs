@@ -0,0 +1,357 @@
+/*
+    xpbd.rs
+    ----------------------------------------
+    Description:
+    * Provides the Extended Position Based Dynamics (XPBD) solver; an alternative to the CollisionResolver
+    * Contacts & joints are solved as positional constraints with compliance, once per substep
+    * Contact points are kept in each body's local space; the penetration is measured from them as they are solved, so
+      corrections made by earlier contacts in the substep are accounted for. The points of a manifold are measured
+      together, then pushed apart evenly, so that a resting face isn't tipped towards the point solved first
+    * Velocities are derived from the positional change, then corrected for dynamic friction & restitution
+    * Contacts are solved island by island, in parallel; joints are solved after them, on the calling thread
+ */
+/* --------------------- IMPORTS -------------------- */
+// Crates
+use crate::app::arena::{BodyArena, BodyHandle, Island};
+use crate::app::objects::BodyView;
+use crate::app::parallel;
+use crate::common::{CollisionResult, TJointRef, Vector2, Crd, Real};
+
+/* -------------------- VARIABLES ------------------- */
+const RESTING_VELOCITY: Real = 0.04; // m/s; below this approach speed restitution is ignored, preventing jitter at rest
+const PARALLEL_CONTACTS: usize = 64;   // Fewer contacts than this are solved on the calling thread
+
+/* ------------------- STRUCTURES ------------------- */
+struct XpbdContact {
+    bodies: [BodyHandle; 2],
+    normal: Vector2<Real>,       // Points from bodies[0] to bodies[1]
+    anchors: [Vector2<Crd>; 2],  // Deepest point of each body in the contact, in its local space
+    r: [Vector2<Real>; 2],       // Contact point relative to each body's center of mass, as last solved
+    depth: Real,                 // Penetration, as last measured
+    lambda_n: Real,              // Normal & tangential Lagrange multipliers
+    lambda_t: Real,
+    v_n: Real,                   // Relative normal velocity before the position solve
+}
+
+pub struct XpbdSolver {
+    contacts: Vec<XpbdContact>,
+    compliance: Real, // Inverse stiffness of contacts; 0 is perfectly rigid
+    threads: usize,   // Threads that islands are solved on
+}
+
+/* -------------------- FUNCTIONS ------------------- */
+impl Default for XpbdSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XpbdSolver {
+    pub fn new() -> Self {
+        XpbdSolver {
+            contacts: Vec::new(),
+            compliance: 0.0,
+            threads: parallel::available_threads(),
+        }
+    }
+
+    /// Drop the contacts of a removed body
+    pub fn forget(&mut self, handle: BodyHandle) {
+        self.contacts.retain(|c| !c.bodies.contains(&handle));
+    }
+
+    /// Positional solve for a single substep of length h
+    pub fn solve_positions(&mut self, collisions: Vec<CollisionResult>, joints: &Vec<TJointRef>, bodies: &mut BodyArena, h: Real) {
+        self.contacts.clear();
+
+        for result in collisions {
+            let [mut h_1, mut h_2] = result.bodies;
+            let n = result.normal;
+
+            // Ensure normal always points from the first body to the second
+            if Vector2::dot(bodies.body(h_1).center(), n) >= Vector2::dot(bodies.body(h_2).center(), n) {
+                (h_1, h_2) = (h_2, h_1);
+            }
+
+            for point in result.contacts {
+                let (b1, b2) = (bodies.body(h_1), bodies.body(h_2));
+                let r = [point - b1.center(), point - b2.center()];
+
+                // Each body reaches half the overlap past the contact point into the other
+                let reach = n * (result.overlap * 0.5);
+                self.contacts.push(XpbdContact {
+                    bodies: [h_1, h_2],
+                    normal: n,
+                    anchors: [b1.local_point(point + reach), b2.local_point(point - reach)],
+                    r,
+                    depth: result.overlap,
+                    lambda_n: 0.0,
+                    lambda_t: 0.0,
+                    v_n: Vector2::dot(b2.point_velocity(r[1]) - b1.point_velocity(r[0]), n),
+                });
+            }
+        }
+
+        let threads = self.threads();
+        let mut work = Self::split(&mut self.contacts, bodies);
+        let alpha = self.compliance / (h * h);
+        parallel::for_each_mut(&mut work, threads, 1, |(island, contacts)| {
+            // The points of a manifold are pushed consecutively, & share their bodies
+            for manifold in contacts.chunk_by_mut(|a, b| a.bodies == b.bodies) {
+                Self::solve_manifold(manifold, island, alpha);
+            }
+        });
+        drop(work); // Writes the islands back to the arena
+
+        for joint_ref in joints {
+            joint_ref.borrow_mut().solve_position(bodies, h);
+        }
+    }
+
+    /// Non-penetration & static friction constraints for the points of a single manifold; alpha is the compliance over h²
+    fn solve_manifold(contacts: &mut [&mut XpbdContact], bodies: &mut Island, alpha: Real) {
+        let Some((mut b1, mut b2)) = bodies.pair_mut(contacts[0].bodies[0], contacts[0].bodies[1]) else { return; };
+        let n = contacts[0].normal;
+        let share = 1.0 / contacts.len() as Real;
+
+        // Penetration at the bodies' current positions
+        for contact in contacts.iter_mut() {
+            let p_1 = b1.world_point(contact.anchors[0]);
+            let p_2 = b2.world_point(contact.anchors[1]);
+            let p = (p_1 + p_2) * 0.5;
+            contact.r = [p - b1.center(), p - b2.center()];
+            contact.depth = Vector2::dot(p_1 - p_2, n);
+        }
+
+        // Non-penetration; each point corrects an even share of its depth
+        for contact in contacts.iter_mut() {
+            let [r_1, r_2] = contact.r;
+            let w = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+            if w == 0.0 || contact.depth <= 0.0 { continue; }
+
+            let d_lambda = (contact.depth * share - alpha * contact.lambda_n) / (w + alpha);
+            contact.lambda_n += d_lambda;
+            b1.apply_correction(n * -d_lambda, r_1);
+            b2.apply_correction(n * d_lambda, r_2);
+        }
+
+        // Static friction; cancel relative tangential motion of the contact points if it is within the friction cone.
+        // Measured at every point first, then each corrects an even share, as with the depth
+        let ks = (b1.material.ks + b2.material.ks) * 0.5;
+        let slips: Vec<Vector2<Real>> = contacts.iter().map(|contact| {
+            let [r_1, r_2] = contact.r;
+            let d_p = b2.point_displacement(r_2) - b1.point_displacement(r_1);
+            d_p - n * Vector2::dot(d_p, n)
+        }).collect();
+        for (contact, d_p_t) in contacts.iter_mut().zip(slips) {
+            if contact.lambda_n == 0.0 { continue; }
+
+            let c_t = d_p_t.mag();
+            if c_t == 0.0 { continue; }
+
+            let [r_1, r_2] = contact.r;
+            let t = d_p_t.norm();
+            let w_t = b1.generalised_inv_mass(r_1, t) + b2.generalised_inv_mass(r_2, t);
+            let lambda_t = c_t * share / w_t;
+
+            if lambda_t < ks * contact.lambda_n {
+                contact.lambda_t = lambda_t;
+                b1.apply_correction(t * lambda_t, r_1);
+                b2.apply_correction(t * -lambda_t, r_2);
+            }
+        }
+    }
+
+    /// Velocity solve for a single substep of length h; applies dynamic friction & restitution, including joint limits
+    pub fn solve_velocities(&mut self, joints: &Vec<TJointRef>, bodies: &mut BodyArena, h: Real) {
+        for joint_ref in joints {
+            joint_ref.borrow_mut().solve_limit_velocity(bodies);
+        }
+
+        let threads = self.threads();
+        let mut work = Self::split(&mut self.contacts, bodies);
+        parallel::for_each_mut(&mut work, threads, 1, |(island, contacts)| {
+            for contact in contacts.iter() {
+                Self::solve_contact_velocity(contact, island, h);
+            }
+        });
+    }
+
+    /// Dynamic friction & restitution for a single contact
+    fn solve_contact_velocity(contact: &XpbdContact, bodies: &mut Island, h: Real) {
+        if contact.lambda_n == 0.0 { return; }
+
+        let Some((mut b1, mut b2)) = bodies.pair_mut(contact.bodies[0], contact.bodies[1]) else { return; };
+        let [r_1, r_2] = contact.r;
+        let n = contact.normal;
+
+        let e = (b1.material.e + b2.material.e) * 0.5;
+        let kd = (b1.material.kd + b2.material.kd) * 0.5;
+
+        let rel_v = b2.point_velocity(r_2) - b1.point_velocity(r_1);
+        let v_n = Vector2::dot(rel_v, n);
+        let v_t = rel_v - n * v_n;
+
+        // Dynamic friction; an impulse bounded by kd times the normal one, and never reversing the sliding direction
+        if contact.lambda_t == 0.0 && v_t.mag() > 0.0 {
+            let t = v_t.norm();
+            let w = b1.generalised_inv_mass(r_1, t) + b2.generalised_inv_mass(r_2, t);
+            if w > 0.0 {
+                let impulse = t * -(kd * contact.lambda_n / h).min(v_t.mag() / w);
+                b1.apply_impulse(impulse * -1.0, r_1);
+                b2.apply_impulse(impulse, r_2);
+            }
+        }
+
+        // Restitution
+        let e = if contact.v_n.abs() <= RESTING_VELOCITY { 0.0 } else { e };
+        let target = (-e * contact.v_n).max(0.0);
+        if v_n >= target { return; }
+
+        let w = b1.generalised_inv_mass(r_1, n) + b2.generalised_inv_mass(r_2, n);
+        if w == 0.0 { return; }
+
+        let impulse = n * ((target - v_n) / w);
+        b1.apply_impulse(impulse * -1.0, r_1);
+        b2.apply_impulse(impulse, r_2);
+    }
+
+    /// Splits the bodies into the islands linked by the contacts, along with the contacts of each island in order
+    fn split<'a>(contacts: &'a mut [XpbdContact], bodies: &'a mut BodyArena) -> Vec<(Island<'a>, Vec<&'a mut XpbdContact>)> {
+        let pairs: Vec<[BodyHandle; 2]> = contacts.iter().map(|contact| contact.bodies).collect();
+        let (islands, of_pair) = bodies.islands(&pairs);
+
+        let mut work: Vec<(Island, Vec<&mut XpbdContact>)> = islands.into_iter().map(|island| (island, Vec::new())).collect();
+        for (contact, island) in contacts.iter_mut().zip(of_pair) {
+            if let Some(island) = island { work[island].1.push(contact); }
+        }
+        work
+    }
+
+    /// Threads to solve the current contacts on
+    fn threads(&self) -> usize {
+        if self.contacts.len() < PARALLEL_CONTACTS { 1 } else { self.threads }
+    }
+
+    /* --------------------- GETTERS -------------------- */
+    pub fn compliance(&self) -> Real {
+        self.compliance
+    }
+
+    /* --------------------- SETTERS -------------------- */
+    /// Sets the inverse stiffness of contacts; 0 keeps them perfectly rigid
+    pub fn set_compliance(&mut self, compliance: Real) {
+        self.compliance = compliance.max(0.0);
+    }
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::app::engine::{Engine, Solver};
+    use crate::app::objects::Body;
+    use crate::app::ssm::SystemStateManager;
+    use crate::common::{BodyForm, Material, Materials, Shared, Vector2M};
+    use crate::{rect, v2};
+
+    const DT: Real = 1.0 / 60.0;
+
+    /// An XPBD engine, & a world with a frozen floor whose top is at y = 10, holding a 1 m box dropped from the given
+    /// height above it; both of the given material
+    fn world(material: Material, height: Real) -> (SystemStateManager, Engine, BodyHandle) {
+        let shared = Rc::new(RefCell::new(Shared {
+            world_size: v2!(20.0, 12.0),
+            collision_grid: vec![],
+            broad_phase_pairs: vec![],
+            narrow_phase_pairs: vec![],
+        }));
+        let mut engine = Engine::new(shared);
+        engine.set_solver(Solver::XPBD);
+
+        let mut ssm = SystemStateManager::new();
+        ssm.add_body(rect!(v2!(0.0, 10.0), 20.0, 1.0, material).set_frozen(true));
+        let box_ = ssm.add_body(rect!(v2!(9.5, 9.0 - height), 1.0, 1.0, material));
+        (ssm, engine, box_)
+    }
+
+    /// How far the box has sunk into the floor
+    fn depth(ssm: &SystemStateManager, box_: BodyHandle) -> Real {
+        ssm.bodies().body(box_).center().y + 0.5 - 10.0
+    }
+
+    #[test]
+    fn resting_box_stays_put() {
+        let (mut ssm, mut engine, box_) = world(Materials::ROCK, 0.0);
+        let start = ssm.bodies().body(box_).position;
+
+        // The points of the manifold share the depth, so the box neither sinks nor tips
+        for _ in 0..300 {
+            engine.step(&mut ssm, DT);
+
+            let body = ssm.bodies().body(box_);
+            assert!((body.position - start).mag() < 0.005, "drifted to {:?}", body.position);
+            assert!(body.rotation.abs() < 0.01, "tipped to {}", body.rotation);
+        }
+    }
+
+    #[test]
+    fn compliant_contacts_give_under_load() {
+        let deepest = |compliance: Real| {
+            let (mut ssm, mut engine, box_) = world(Materials::ROCK, 0.0);
+            engine.set_contact_compliance(compliance);
+            // Deepest it sinks; compliant contacts are undamped springs, so the box doesn't settle on them
+            (0..120).map(|_| {
+                engine.step(&mut ssm, DT);
+                depth(&ssm, box_)
+            }).fold(Real::MIN, Real::max)
+        };
+
+        let (rigid, soft) = (deepest(0.0), deepest(1e-3));
+        assert!(rigid < 0.001, "rigid contact sank {rigid} m");
+        assert!(soft > rigid + 0.001, "compliant contact sank {soft} m, rigid {rigid} m");
+    }
+
+    #[test]
+    fn static_friction_holds_below_the_friction_angle() {
+        // Gravity tilted rather than the floor; the same as a slope, without its corners
+        let slide = |angle: Real| {
+            let (mut ssm, mut engine, box_) = world(Materials::RUBBER, 0.0);
+            engine.set_gravity(v2!(angle.sin(), angle.cos(), 9.81));
+            let start = ssm.bodies().body(box_).position;
+            for _ in 0..120 {
+                engine.step(&mut ssm, DT);
+            }
+            ssm.bodies().body(box_).position.x - start.x
+        };
+
+        // Rubber's static coefficient is 0.9, an angle of about 42°; its dynamic one 0.7
+        let (held, slid) = (slide(Real::to_radians(20.0)), slide(Real::to_radians(50.0)));
+        assert!(held.abs() < 0.01, "slid {held} m below the friction angle");
+        assert!(slid > 1.0, "slid {slid} m above the friction angle");
+    }
+
+    #[test]
+    fn restitution_is_ignored_at_resting_speeds() {
+        // Dropped from a metre, a bouncy box leaves the floor again
+        let (mut ssm, mut engine, box_) = world(Materials::BOUNCY, 1.0);
+        let mut bounced = false;
+        for _ in 0..60 {
+            engine.step(&mut ssm, DT);
+            bounced |= depth(&ssm, box_) < -0.1 && ssm.bodies().body(box_).velocity.y < -1.0;
+        }
+        assert!(bounced);
+
+        // Set on the floor, it approaches no faster than gravity over a substep, & stays there
+        let (mut ssm, mut engine, box_) = world(Materials::BOUNCY, 0.0);
+        for _ in 0..120 {
+            engine.step(&mut ssm, DT);
+            assert!(depth(&ssm, box_).abs() < 0.001, "left the floor by {}", -depth(&ssm, box_));
+        }
+    }
+}
@@ -3,62 +3,97 @@
     ----------------------------------------
     Description:
     * Provides access to commonly-used functions, data types, etc. to the physics engine
+    * Real is the scalar the engine runs on; f64 by default, or f32 with the `f32` feature
+    * The world is in SI units; metres, kilograms & seconds. Only Video deals in pixels
  */
 /* --------------------- IMPORTS -------------------- */
 // Crates
 use std::cell::RefCell;
 use std::fmt::Debug;
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 use std::rc::Rc;
 
 use num::cast::AsPrimitive;
 use num::Num;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
 
-use crate::app::objects::Body;
+use crate::app::arena::BodyHandle;
+use crate::app::engine::Despawn;
+use crate::app::joints::Joint;
+use crate::app::objects::{CharacterController, ParticleSystem, Rope, SoftBody, Vehicle};
 
 /* -------------------- VARIABLES ------------------- */
 // General
 pub type Disp = i32;
-pub type Crd = f64;
+pub type Crd = Real;
 pub type TSharedRef = Rc<RefCell<Shared>>;
 
+// Scalars; the engine runs on f64, or on f32 with the `f32` feature
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(feature = "f32")]
+pub type Real = f32;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+
+// Randomness
+pub const DEFAULT_SEED: u64 = 0;
+
+thread_local! {
+    // Every random choice in the simulation is drawn from here, so that runs with the same seed are identical
+    static RNG: RefCell<ChaCha12Rng> = RefCell::new(ChaCha12Rng::seed_from_u64(DEFAULT_SEED));
+}
+
 // Collision
 pub const GRID_SIZE: Vector2<usize> = crate::v2!(20, 20);
 pub const PRECISION: i32 = 6;
-pub const VERY_SMALL: f64 = 0.01;
-pub type TBodyRef = Rc<RefCell<Body>>;
-pub type TCollisionGrid = Vec<Vec<Vec<TBodyRef>>>;
-pub type TCollisionPairs = Vec<[TBodyRef; 2]>;
+pub const LENGTH_TOLERANCE: Real = 0.0002;  // m; lengths closer than this are equal, & shorter vectors have no direction
+pub const DIST_SQ_TOLERANCE: Real = 4.0e-6; // m²; squared distances closer than this are equal
+pub type TCollisionGrid = Vec<Vec<Vec<BodyHandle>>>;
+pub type TCollisionPairs = Vec<[BodyHandle; 2]>;
+pub type TSoftBodyRef = Rc<RefCell<SoftBody>>;
+pub type TParticlesRef = Rc<RefCell<ParticleSystem>>;
+pub type TRopeRef = Rc<RefCell<Rope>>;
+pub type TVehicleRef = Rc<RefCell<Vehicle>>;
+pub type TCharacterRef = Rc<RefCell<CharacterController>>;
+
+// Constraints
+pub type TJointRef = Rc<RefCell<Joint>>;
+pub type TJointListener = Box<dyn FnMut(&TJointRef)>; // Called with every joint that broke during a step
+pub type TDespawnListener = Box<dyn FnMut(&Despawn)>; // Called with every body despawned during a step
 
 /* ------------------- STRUCTURES ------------------- */
 pub struct Shared {
-    pub window_size: Vector2<u32>,
+    pub world_size: Vector2<Crd>, // Metres of world in view; spanned by the collision grid
     pub collision_grid: TCollisionGrid,
     pub broad_phase_pairs: TCollisionPairs,
     pub narrow_phase_pairs: Vec<CollisionResult>,
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Vector2<T> {
     pub x: T,
     pub y: T,
 }
-impl<T: Copy + Num + AsPrimitive<f64> + AsPrimitive<Disp>> Vector2<T> {
+impl<T: Scalar> Vector2<T> {
     pub fn from(value: T) -> Vector2<T> {
         Vector2 {
             x: value,
             y: value,
         }
     }
-    pub fn mag(self) -> f64 {
-        let v: Vector2<f64> = self.to();
+    pub fn mag(self) -> Real {
+        let v: Vector2<Real> = self.to();
         let v2 = v * v;
         (v2.x + v2.y).sqrt()
     }
-    pub fn norm(self) -> Vector2<f64> {
-        let v: Vector2<f64> = self.to();
+    pub fn norm(self) -> Vector2<Real> {
+        let v: Vector2<Real> = self.to();
         let n = self.mag();
 
         if !n.is_nan() && n != 0.0 {
@@ -77,30 +112,30 @@ impl<T: Copy + Num + AsPrimitive<f64> + AsPrimitive<Disp>> Vector2<T> {
     pub fn dot(v1: Vector2<T>, v2: Vector2<T>) -> T {
         return (v1.x * v2.x) + (v1.y * v2.y);
     }
-    pub fn cross(v1: Vector2<T>, v2: Vector2<T>) -> f64 {
-        let v1: Vector2<f64> = v1.to();
-        let v2: Vector2<f64> = v2.to();
+    pub fn cross(v1: Vector2<T>, v2: Vector2<T>) -> Real {
+        let v1: Vector2<Real> = v1.to();
+        let v2: Vector2<Real> = v2.to();
         return (v1.x * v2.y) - (v1.y * v2.x)
     }
-    pub fn cross_vc(v: Vector2<T>, c: T) -> Vector2<f64> {
-        let v: Vector2<f64> = v.to();
-        let c: Vector2<f64> = Vector2::from(c).to();
+    pub fn cross_vc(v: Vector2<T>, c: T) -> Vector2<Real> {
+        let v: Vector2<Real> = v.to();
+        let c: Vector2<Real> = Vector2::from(c).to();
         return Vector2 { x: c.y * v.y, y: c.x * v.x * -1.0 }
     }
-    pub fn cross_cv(c: T, v: Vector2<T>) -> Vector2<f64> {
-        let v: Vector2<f64> = v.to();
-        let c: Vector2<f64> = Vector2::from(c).to();
+    pub fn cross_cv(c: T, v: Vector2<T>) -> Vector2<Real> {
+        let v: Vector2<Real> = v.to();
+        let c: Vector2<Real> = Vector2::from(c).to();
         return Vector2 { x: c.y * v.y * -1.0, y: c.x * v.x }
     }
-    pub fn project(v1: Vector2<T>, v2: Vector2<T>) -> Vector2<f64> {
-        let v1: Vector2<f64> = v1.to();
-        let v2: Vector2<f64> = v2.to();
+    pub fn project(v1: Vector2<T>, v2: Vector2<T>) -> Vector2<Real> {
+        let v1: Vector2<Real> = v1.to();
+        let v2: Vector2<Real> = v2.to();
         let dot = Vector2::dot(v1, v2);
 
         return v2 * (dot / v2.mag().powi(2));
     }
-    pub fn p_dist(p: Vector2<f64>, l1: Vector2<f64>, l2: Vector2<f64>) -> (f64, Vector2<f64>) {
-        let mut out: Vector2<f64>;
+    pub fn p_dist(p: Vector2<Real>, l1: Vector2<Real>, l2: Vector2<Real>) -> (Real, Vector2<Real>) {
+        let mut out: Vector2<Real>;
 
         let l1_l2 = l2 - l1;
         let l1_p = p - l1;
@@ -122,8 +157,48 @@ impl<T: Copy + Num + AsPrimitive<f64> + AsPrimitive<Disp>> Vector2<T> {
             out
         )
     }
-    pub fn almost_eq(v1: Vector2<f64>, v2: Vector2<f64>) -> bool {
-        return (v1.x - v2.x).powi(2) + (v1.y - v2.y).powi(2) <= VERY_SMALL * VERY_SMALL
+    pub fn almost_eq(v1: Vector2<Real>, v2: Vector2<Real>) -> bool {
+        return (v1.x - v2.x).powi(2) + (v1.y - v2.y).powi(2) <= LENGTH_TOLERANCE * LENGTH_TOLERANCE
+    }
+}
+
+// Numbers a Vector2 can do maths on; lengths, directions & cross products are taken in Real
+pub trait Scalar: Copy + Num + AsPrimitive<Real> + AsPrimitive<Disp> {}
+impl<T: Copy + Num + AsPrimitive<Real> + AsPrimitive<Disp>> Scalar for T {}
+
+// Numbers the narrow phase & impulse response run on (see collision::contact); Real, or Fixed with the `fixed` feature
+pub trait Number: Copy + PartialOrd + Debug
+    + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self> + Neg<Output=Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const HALF: Self;
+    const MAX: Self;
+    const MIN: Self;
+    const LENGTH_TOLERANCE: Self;
+    const DIST_SQ_TOLERANCE: Self;
+
+    fn ratio(num: i32, den: i32) -> Self;
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+}
+impl Number for Real {
+    const ZERO: Real = 0.0;
+    const ONE: Real = 1.0;
+    const HALF: Real = 0.5;
+    const MAX: Real = Real::MAX;
+    const MIN: Real = Real::MIN;
+    const LENGTH_TOLERANCE: Real = LENGTH_TOLERANCE;
+    const DIST_SQ_TOLERANCE: Real = DIST_SQ_TOLERANCE;
+
+    fn ratio(num: i32, den: i32) -> Real {
+        num as Real / den as Real
+    }
+    fn abs(self) -> Real {
+        Real::abs(self)
+    }
+    fn sqrt(self) -> Real {
+        Real::sqrt(self)
     }
 }
 
@@ -204,11 +279,11 @@ impl<T: Div<Output=T> + Copy> Div<T> for Vector2<T> {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Vector2M<T> {
     pub x: T,
     pub y: T,
-    pub m: f64,
+    pub m: Real,
 }
 impl<T> Vector2M<T> {
     pub fn to_vec2(self) -> Vector2<T> {
@@ -216,7 +291,152 @@ impl<T> Vector2M<T> {
     }
 }
 
+/// Rotation, kept as the cosine & sine of its angle, so applying it needs no trigonometry.
 #[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Rot {
+    pub c: Real,
+    pub s: Real,
+}
+impl Rot {
+    pub const IDENTITY: Rot = Rot { c: 1.0, s: 0.0 };
+
+    pub fn new(angle: Real) -> Rot {
+        let (s, c) = angle.sin_cos();
+        Rot { c, s }
+    }
+    pub fn angle(self) -> Real {
+        self.s.atan2(self.c)
+    }
+    pub fn inverse(self) -> Rot {
+        Rot { c: self.c, s: -self.s }
+    }
+    pub fn mat(self) -> Mat22 {
+        Mat22 { col1: Vector2 { x: self.c, y: self.s }, col2: Vector2 { x: -self.s, y: self.c } }
+    }
+    pub fn rotate(self, v: Vector2<Real>) -> Vector2<Real> {
+        Vector2 { x: v.x * self.c - v.y * self.s, y: v.x * self.s + v.y * self.c }
+    }
+    /// Rotates back by the angle; the inverse of `rotate`
+    pub fn inv_rotate(self, v: Vector2<Real>) -> Vector2<Real> {
+        Vector2 { x: v.x * self.c + v.y * self.s, y: -v.x * self.s + v.y * self.c }
+    }
+}
+
+/// 2x2 matrix, stored by columns.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Mat22 {
+    pub col1: Vector2<Real>,
+    pub col2: Vector2<Real>,
+}
+impl Mat22 {
+    pub const IDENTITY: Mat22 = Mat22 { col1: Vector2 { x: 1.0, y: 0.0 }, col2: Vector2 { x: 0.0, y: 1.0 } };
+
+    pub fn new(col1: Vector2<Real>, col2: Vector2<Real>) -> Mat22 {
+        Mat22 { col1, col2 }
+    }
+    pub fn determinant(self) -> Real {
+        self.col1.x * self.col2.y - self.col2.x * self.col1.y
+    }
+    pub fn transpose(self) -> Mat22 {
+        Mat22 { col1: Vector2 { x: self.col1.x, y: self.col2.x }, col2: Vector2 { x: self.col1.y, y: self.col2.y } }
+    }
+    /// None if the matrix is singular
+    pub fn inverse(self) -> Option<Mat22> {
+        let det = self.determinant();
+        if det == 0.0 { return None; }
+
+        Some(Mat22 {
+            col1: Vector2 { x: self.col2.y / det, y: -self.col1.y / det },
+            col2: Vector2 { x: -self.col2.x / det, y: self.col1.x / det },
+        })
+    }
+    /// Solves self * x = b for x; None if the matrix is singular
+    pub fn solve(self, b: Vector2<Real>) -> Option<Vector2<Real>> {
+        let det = self.determinant();
+        if det == 0.0 { return None; }
+
+        Some(Vector2 {
+            x: (self.col2.y * b.x - self.col2.x * b.y) / det,
+            y: (self.col1.x * b.y - self.col1.y * b.x) / det,
+        })
+    }
+}
+
+/// Rigid transform; a rotation about the origin, then a translation.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Transform {
+    pub p: Vector2<Real>,
+    pub q: Rot,
+}
+impl Transform {
+    pub const IDENTITY: Transform = Transform { p: Vector2 { x: 0.0, y: 0.0 }, q: Rot::IDENTITY };
+
+    pub fn new(p: Vector2<Real>, angle: Real) -> Transform {
+        Transform { p, q: Rot::new(angle) }
+    }
+    pub fn inverse(self) -> Transform {
+        let q = self.q.inverse();
+        Transform { p: q.rotate(self.p) * -1.0, q }
+    }
+    pub fn world_point(self, local: Vector2<Real>) -> Vector2<Real> {
+        self.p + self.q.rotate(local)
+    }
+    pub fn local_point(self, world: Vector2<Real>) -> Vector2<Real> {
+        self.q.inv_rotate(world - self.p)
+    }
+    pub fn world_vector(self, local: Vector2<Real>) -> Vector2<Real> {
+        self.q.rotate(local)
+    }
+    pub fn local_vector(self, world: Vector2<Real>) -> Vector2<Real> {
+        self.q.inv_rotate(world)
+    }
+}
+
+// Composition; (a * b) applies b, then a
+impl Mul for Rot {
+    type Output = Rot;
+    fn mul(self, rhs: Rot) -> Rot {
+        Rot { c: self.c * rhs.c - self.s * rhs.s, s: self.s * rhs.c + self.c * rhs.s }
+    }
+}
+impl Mul<Vector2<Real>> for Rot {
+    type Output = Vector2<Real>;
+    fn mul(self, rhs: Vector2<Real>) -> Vector2<Real> {
+        self.rotate(rhs)
+    }
+}
+impl Mul for Mat22 {
+    type Output = Mat22;
+    fn mul(self, rhs: Mat22) -> Mat22 {
+        Mat22 { col1: self * rhs.col1, col2: self * rhs.col2 }
+    }
+}
+impl Mul<Vector2<Real>> for Mat22 {
+    type Output = Vector2<Real>;
+    fn mul(self, rhs: Vector2<Real>) -> Vector2<Real> {
+        Vector2 { x: self.col1.x * rhs.x + self.col2.x * rhs.y, y: self.col1.y * rhs.x + self.col2.y * rhs.y }
+    }
+}
+impl Add for Mat22 {
+    type Output = Mat22;
+    fn add(self, rhs: Mat22) -> Mat22 {
+        Mat22 { col1: self.col1 + rhs.col1, col2: self.col2 + rhs.col2 }
+    }
+}
+impl Mul for Transform {
+    type Output = Transform;
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform { p: self.world_point(rhs.p), q: self.q * rhs.q }
+    }
+}
+impl Mul<Vector2<Real>> for Transform {
+    type Output = Vector2<Real>;
+    fn mul(self, rhs: Vector2<Real>) -> Vector2<Real> {
+        self.world_point(rhs)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Vertex {
     pub id: u32,
     pub x: Crd,
@@ -228,35 +448,125 @@ impl Vertex {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct Axis {
-    pub v2: Vector2<f64>,
-    pub parent: TBodyRef,
-}
-
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum BodyForm {
     Polygon,
     Circle
 }
 
-#[derive(Debug, Clone)]
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct AABB {
-    pub points: Vec<Vector2<Crd>>,
+    pub min: Vector2<Crd>,
+    pub max: Vector2<Crd>,
+}
+impl AABB {
+    pub fn new(min: Vector2<Crd>, max: Vector2<Crd>) -> AABB {
+        AABB { min, max }
+    }
+    /// Smallest box holding every point; inverted (min > max, overlapping nothing) if there are none
+    pub fn from_points(points: &[Vector2<Crd>]) -> AABB {
+        let empty = AABB { min: Vector2::from(Crd::MAX), max: Vector2::from(Crd::MIN) };
+        points.iter().fold(empty, |aabb, &p| AABB {
+            min: Vector2 { x: aabb.min.x.min(p.x), y: aabb.min.y.min(p.y) },
+            max: Vector2 { x: aabb.max.x.max(p.x), y: aabb.max.y.max(p.y) },
+        })
+    }
+    pub fn from_circle(center: Vector2<Crd>, radius: Real) -> AABB {
+        AABB { min: center - Vector2::from(radius), max: center + Vector2::from(radius) }
+    }
+
+    pub fn union(self, other: AABB) -> AABB {
+        AABB {
+            min: Vector2 { x: self.min.x.min(other.min.x), y: self.min.y.min(other.min.y) },
+            max: Vector2 { x: self.max.x.max(other.max.x), y: self.max.y.max(other.max.y) },
+        }
+    }
+    /// Grown by the margin on every side
+    pub fn fattened(self, margin: Real) -> AABB {
+        AABB { min: self.min - Vector2::from(margin), max: self.max + Vector2::from(margin) }
+    }
+    pub fn overlaps(&self, other: &AABB) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+    /// Whether the other box lies wholly inside this one
+    pub fn contains(&self, other: &AABB) -> bool {
+        self.min.x <= other.min.x && self.min.y <= other.min.y && other.max.x <= self.max.x && other.max.y <= self.max.y
+    }
+    pub fn contains_point(&self, p: Vector2<Crd>) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x && self.min.y <= p.y && p.y <= self.max.y
+    }
+    /// Slab test of the segment from origin to origin + delta; the fraction of delta at which it enters the box,
+    /// 0 if it starts inside, or None if it misses
+    pub fn ray_cast(&self, origin: Vector2<Crd>, delta: Vector2<Crd>) -> Option<Real> {
+        let (mut t_enter, mut t_exit): (Real, Real) = (0.0, 1.0);
+
+        for (o, d, min, max) in [(origin.x, delta.x, self.min.x, self.max.x), (origin.y, delta.y, self.min.y, self.max.y)] {
+            if d == 0.0 {
+                // Parallel to the slab; must already be within it
+                if o < min || o > max { return None; }
+                continue;
+            }
+
+            let (t_1, t_2) = ((min - o) / d, (max - o) / d);
+            t_enter = t_enter.max(t_1.min(t_2));
+            t_exit = t_exit.min(t_1.max(t_2));
+            if t_enter > t_exit { return None; }
+        }
+
+        Some(t_enter)
+    }
+
+    pub fn center(&self) -> Vector2<Crd> {
+        (self.min + self.max) / 2.0
+    }
+    pub fn size(&self) -> Vector2<Crd> {
+        self.max - self.min
+    }
+    /// Corners in winding order, starting at min
+    pub fn corners(&self) -> [Vector2<Crd>; 4] {
+        [self.min, Vector2 { x: self.min.x, y: self.max.y }, self.max, Vector2 { x: self.max.x, y: self.min.y }]
+    }
 }
 
 #[derive(Debug)]
 pub struct Projection {
-    pub min: f64,
-    pub max: f64,
+    pub min: Real,
+    pub max: Real,
 }
 
 #[derive(Debug, Clone)]
 pub struct CollisionResult {
-    pub bodies: [TBodyRef; 2],
-    pub normal: Vector2<f64>,
-    pub overlap: f64,
-    pub contacts: Vec<Vector2<f64>>,
+    pub bodies: [BodyHandle; 2],
+    pub normal: Vector2<Real>,
+    pub overlap: Real,
+    pub contacts: Vec<Vector2<Real>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SoftCollisionResult {
+    pub soft_body: TSoftBodyRef,
+    pub body: BodyHandle,
+    pub edge: [usize; 2],        // Indices of the hull edge's point masses
+    pub normal: Vector2<Real>,    // Points from the rigid body towards the edge
+    pub overlap: Real,
+    pub contact: Vector2<Real>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParticleCollisionResult {
+    pub particle: usize,      // Index into the ParticleSystem (or Rope)
+    pub body: BodyHandle,
+    pub normal: Vector2<Real>, // Points from the body towards the particle
+    pub overlap: Real,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastResult {
+    pub body: BodyHandle,
+    pub normal: Vector2<Real>, // Points from the body towards the cast shape
+    pub t: Real,               // Fraction of the cast travelled before the hit
+    pub depth: Real,           // Penetration if the shape already overlapped the body at the start of the cast
 }
 
 pub struct Materials;
@@ -265,16 +575,17 @@ impl Materials {
     pub const ROCK: Material = Material { density: 0.5, e: 0.3, ks: 0.12, kd: 0.06 };
     pub const METAL: Material = Material { density: 0.7, e: 0.15, ks: 0.15, kd: 0.08 };
     pub const BOUNCY: Material = Material { density: 0.2, e: 1.2, ks: 0.08, kd: 0.01 };
+    pub const RUBBER: Material = Material { density: 0.4, e: 0.2, ks: 0.9, kd: 0.7 };
 
     // pub const STATIC: Material = Material { density: 0.0, e: 0.4, ks: 0.14, kd: 0.4 };
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Material {
-    pub density: f64,
-    pub e: f64,
-    pub ks: f64,
-    pub kd: f64,
+    pub density: Real, // Kilograms per square metre
+    pub e: Real,
+    pub ks: Real,
+    pub kd: Real,
 }
 
 pub struct Colors;
@@ -287,10 +598,10 @@ impl Colors {
     pub const AC3: Color = Color::RGB(36, 123, 160);
 
     pub fn random_accent() -> Color {
-        let rng = rand::thread_rng().gen_range(0..3);
+        let i = with_rng(|rng| rng.gen_range(0..3));
         let colors = [Self::AC1, Self::AC2, Self::AC3];
 
-        colors[rng]
+        colors[i]
     }
 }
 
@@ -325,10 +636,130 @@ macro_rules! vtx {
 }
 
 /* ------------------- FUNCTIONS ------------------- */
-pub fn round(n: f64) -> f64 {
-    return (n * 10f64.powi(PRECISION)).round() / 10f64.powi(PRECISION)
+pub fn round(n: Real) -> Real {
+    return (n * Real::powi(10.0, PRECISION)).round() / Real::powi(10.0, PRECISION)
+}
+
+pub fn almost_eq(f1: Real, f2: Real) -> bool {
+    return (f1 - f2).abs() < LENGTH_TOLERANCE
+}
+
+/// almost_eq, for squared distances
+pub fn almost_eq_sq(d1: Real, d2: Real) -> bool {
+    (d1 - d2).abs() < DIST_SQ_TOLERANCE
+}
+
+/// Run f with the simulation's random number generator
+pub fn with_rng<T>(f: impl FnOnce(&mut ChaCha12Rng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Restart the simulation's random number generator from the given seed
+pub fn seed_rng(seed: u64) {
+    set_rng(ChaCha12Rng::seed_from_u64(seed));
 }
 
-pub fn almost_eq(f1: f64, f2: f64) -> bool {
-    return (f1 - f2).abs() < VERY_SMALL
+/// Replace the simulation's random number generator; used to restore a copy of its state
+pub fn set_rng(rng: ChaCha12Rng) {
+    RNG.with(|r| *r.borrow_mut() = rng);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: Real = 1e-5;
+
+    fn close(a: Vector2<Real>, b: Vector2<Real>) -> bool {
+        (a - b).mag() < EPSILON
+    }
+
+    #[test]
+    fn transforms_invert_and_compose() {
+        let (a, b) = (Transform::new(v2!(3.0, -1.0), 0.7), Transform::new(v2!(-2.0, 5.0), -2.4));
+        let p = v2!(1.5, 0.25);
+
+        assert!(close(a.local_point(a.world_point(p)), p));
+        assert!(close(a.world_point(a.local_point(p)), p));
+        assert!(close(a.local_vector(a.world_vector(p)), p));
+        assert!(close(a.inverse() * (a * p), p));
+        assert!(close(a.inverse().world_point(p), a.local_point(p)));
+
+        // (a * b) applies b, then a
+        assert!(close((a * b) * p, a * (b * p)));
+        assert!(((a * b).q.angle() - (0.7 - 2.4)).abs() < EPSILON);
+        let identity = a * a.inverse();
+        assert!(close(identity.p, v2!(0.0, 0.0)) && identity.q.angle().abs() < EPSILON);
+    }
+
+    #[test]
+    fn rotations_match_their_matrices() {
+        let (q, p) = (Rot::new(1.2), v2!(-0.5, 2.0));
+        assert!(close(q.rotate(p), q.mat() * p));
+        assert!(close(q.inv_rotate(p), q.mat().transpose() * p));
+        assert!(close(q.inverse().rotate(q.rotate(p)), p));
+        assert!(((q * Rot::new(0.3)).angle() - 1.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn matrices_invert_and_solve() {
+        let m = Mat22::new(v2!(2.0, 1.0), v2!(-1.0, 3.0));
+        let inverse = m.inverse().unwrap();
+        for product in [m * inverse, inverse * m] {
+            assert!(close(product.col1, Mat22::IDENTITY.col1) && close(product.col2, Mat22::IDENTITY.col2));
+        }
+
+        let b = v2!(4.0, -2.0);
+        let x = m.solve(b).unwrap();
+        assert!(close(m * x, b) && close(x, inverse * b));
+
+        let singular = Mat22::new(v2!(1.0, 2.0), v2!(2.0, 4.0));
+        assert_eq!((singular.inverse(), singular.solve(b)), (None, None));
+    }
+
+    #[test]
+    fn rays_enter_boxes_through_their_slabs() {
+        let aabb = AABB::new(v2!(1.0, 1.0), v2!(3.0, 2.0));
+
+        // Diagonal, entering through the bottom edge a quarter of the way along
+        let t = aabb.ray_cast(v2!(0.0, 0.0), v2!(4.0, 4.0)).unwrap();
+        assert!((t - 0.25).abs() < EPSILON);
+        assert_eq!(aabb.ray_cast(v2!(0.0, 0.0), v2!(4.0, -4.0)), None);
+        assert_eq!(aabb.ray_cast(v2!(0.0, 0.0), v2!(0.8, 0.8)), None); // Stops short
+
+        // Parallel to an axis; hits only when within the other slab, edges included
+        assert!((aabb.ray_cast(v2!(0.0, 1.5), v2!(10.0, 0.0)).unwrap() - 0.1).abs() < EPSILON);
+        assert!((aabb.ray_cast(v2!(0.0, 2.0), v2!(10.0, 0.0)).unwrap() - 0.1).abs() < EPSILON);
+        assert_eq!(aabb.ray_cast(v2!(0.0, 2.5), v2!(10.0, 0.0)), None);
+        assert!((aabb.ray_cast(v2!(2.0, 5.0), v2!(0.0, -5.0)).unwrap() - 0.6).abs() < EPSILON);
+
+        // From inside, whichever way it points
+        assert_eq!(aabb.ray_cast(v2!(2.0, 1.5), v2!(5.0, 0.0)), Some(0.0));
+        assert_eq!(aabb.ray_cast(v2!(2.0, 1.5), v2!(0.0, 0.0)), Some(0.0));
+    }
+
+    #[test]
+    fn boxes_overlap_and_contain() {
+        let aabb = AABB::new(v2!(0.0, 0.0), v2!(2.0, 2.0));
+
+        // Touching edges & corners overlap; a gap doesn't
+        assert!(aabb.overlaps(&AABB::new(v2!(2.0, 0.5), v2!(3.0, 1.5))));
+        assert!(aabb.overlaps(&AABB::new(v2!(2.0, 2.0), v2!(3.0, 3.0))));
+        assert!(!aabb.overlaps(&AABB::new(v2!(2.1, 0.5), v2!(3.0, 1.5))));
+        assert!(!aabb.overlaps(&AABB::from_points(&[])));
+
+        let inner = AABB::new(v2!(0.5, 0.5), v2!(2.0, 1.0));
+        assert!(aabb.contains(&inner) && aabb.contains(&aabb) && !inner.contains(&aabb));
+        assert!(aabb.overlaps(&inner) && inner.overlaps(&aabb));
+        assert!(aabb.contains_point(v2!(2.0, 0.0)) && !aabb.contains_point(v2!(2.0, -0.1)));
+
+        let fat = aabb.fattened(0.25);
+        assert_eq!((fat.min, fat.max), (v2!(-0.25, -0.25), v2!(2.25, 2.25)));
+        assert!(fat.contains(&aabb) && !aabb.contains(&fat));
+
+        let circle = AABB::from_circle(v2!(1.0, -1.0), 0.5);
+        assert_eq!((circle.min, circle.max), (v2!(0.5, -1.5), v2!(1.5, -0.5)));
+        assert_eq!(AABB::from_points(&[v2!(1.0, -0.5), v2!(0.5, -1.5), v2!(1.5, -1.0)]), circle);
+        assert_eq!(aabb.union(circle), AABB::new(v2!(0.0, -1.5), v2!(2.0, 2.0)));
+    }
 }
@@ -10,10 +10,12 @@
 pub mod app;
 pub mod common;
 
-use std::f64::consts::PI;
+use std::env;
+use crate::common::consts::PI;
+use std::process;
 // Crates
 use crate::common::{BodyForm, Vector2, Materials};
-use crate::app::{App, objects::Body};
+use crate::app::{App, bench, objects::Body};
 
 /* -------------------- VARIABLES ------------------- */
 
@@ -23,29 +25,103 @@ use crate::app::{App, objects::Body};
 
 /* -------------------- FUNCTIONS ------------------- */
 fn main() {
+    // SCENE SETUP; a scene file may be given as an argument, e.g. `cargo run -- scenes/pentagon.json`.
+    // `--record run.rec` records the session, `--replay run.rec` replays a recorded one.
+    // `--bench [bodies]` runs the headless benchmark instead; build with `--release`.
+    // `--lockstep [steps]` prints the state hash of the fixed-point world after some steps; needs `--features fixed`.
+    let mut args = env::args().skip(1).peekable();
+    let (mut scene, mut record, mut replay, mut bench_bodies, mut lockstep_steps) = (None, None, None, None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => record = args.next(),
+            "--replay" => replay = args.next(),
+            "--bench" => {
+                let bodies = args.peek().and_then(|n| n.parse().ok());
+                if bodies.is_some() { args.next(); }
+                bench_bodies = Some(bodies.unwrap_or(bench::DEFAULT_BODIES));
+            }
+            "--lockstep" => {
+                let steps = args.peek().and_then(|n| n.parse().ok());
+                if steps.is_some() { args.next(); }
+                lockstep_steps = Some(steps);
+            }
+            _ => scene = Some(arg),
+        }
+    }
+
+    if let Some(bodies) = bench_bodies {
+        bench::run(bodies).print();
+        return;
+    }
+    if let Some(steps) = lockstep_steps {
+        lockstep(steps);
+        return;
+    }
+
     let mut my_app = App::new("DEFAULT", 1000, 600);
 
-    // PENTAGON SETUP
+    if let Some(path) = &replay {
+        if let Err(e) = my_app.replay(path) {
+            eprintln!("{path}: {e}");
+            process::exit(1);
+        }
+
+        my_app.start();
+        return;
+    }
+    match scene {
+        Some(path) => if let Err(e) = my_app.load_scene(&path) {
+            eprintln!("{path}: {e}");
+            process::exit(1);
+        },
+        None => setup(&mut my_app),
+    }
+    if let Some(path) = record {
+        if let Err(e) = my_app.record(&path, common::DEFAULT_SEED) {
+            eprintln!("{path}: {e}");
+            process::exit(1);
+        }
+    }
+
+    my_app.start();
+}
+
+/// The default scene
+fn setup(my_app: &mut App) {
+    // PENTAGON SETUP; lengths in metres
     let bodies = vec![
-        rect!(v2!(50, 250), 500, 50, Materials::BOUNCY).set_frozen(true).set_rotation(PI / 12f64),
-        poly!(v2!(50, 0), 50, 5),
-        // poly!(v2!(250, 250), 80, 4).set_rotation(PI / 4f64),
-        // poly!(v2!(250, 0), 80, 4).set_rotation(PI / 4f64),
-        // poly!(v2!(250, 100), 80, 4).set_rotation(PI / 4f64),
-        // poly!(v2!(250, 350), 80, 4).set_rotation(PI / 4f64),
-        // poly!(v2!(450, 0), 50, 20, Materials::BOUNCY),
-        // poly!(v2!(500, 0), 120, 3),
-        // poly!(v2!(640, 250), 30, 3).set_rotation(PI / 3.0),
+        rect!(v2!(1.0, 5.0), 10.0, 1.0, Materials::BOUNCY).set_frozen(true).set_rotation(PI / 12.0),
+        poly!(v2!(1.0, 0.0), 1.0, 5),
+        // poly!(v2!(5.0, 5.0), 1.6, 4).set_rotation(PI / 4.0),
+        // poly!(v2!(5.0, 0.0), 1.6, 4).set_rotation(PI / 4.0),
+        // poly!(v2!(5.0, 2.0), 1.6, 4).set_rotation(PI / 4.0),
+        // poly!(v2!(5.0, 7.0), 1.6, 4).set_rotation(PI / 4.0),
+        // poly!(v2!(9.0, 0.0), 1.0, 20, Materials::BOUNCY),
+        // poly!(v2!(10.0, 0.0), 2.4, 3),
+        // poly!(v2!(12.8, 5.0), 0.6, 3).set_rotation(PI / 3.0),
     ];
 
 
     // RECT SETUP
     // let bodies = vec![
-    //     rect!(v2!(370, 300), 200, 100).set_frozen(true),
-    //     rect!(v2!(450, 170), 100, 50, 1.5),
+    //     rect!(v2!(7.4, 6.0), 4.0, 2.0).set_frozen(true),
+    //     rect!(v2!(9.0, 3.4), 2.0, 1.0, Materials::METAL),
     // ];
 
     my_app.add_bodies(bodies);
 
-    my_app.start();
+    // VEHICLE SETUP; drive with the arrow keys
+    // my_app.add_vehicle(app::objects::VehicleBuilder::new(v2!(4.0, 10.0)));
+}
+
+/// Runs the fixed-point world headless, & prints its state hash
+#[cfg(feature = "fixed")]
+fn lockstep(steps: Option<u64>) {
+    let steps = steps.unwrap_or(app::lockstep::DEFAULT_STEPS);
+    println!("{steps} steps: {:016x}", app::lockstep::run(steps));
+}
+#[cfg(not(feature = "fixed"))]
+fn lockstep(_steps: Option<u64>) {
+    eprintln!("--lockstep needs the `fixed` feature; e.g. `cargo run --features fixed -- --lockstep`");
+    process::exit(1);
 }